@@ -0,0 +1,12 @@
+//! Compiles runtime.c into walter, the JIT runs programs with it. Built programs link their own
+//! copy, see `compiler::linking`
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/compiler/runtime.c");
+
+    #[cfg(feature = "llvm")]
+    cc::Build::new()
+        .file("src/compiler/runtime.c")
+        .compile("redditlang_runtime");
+}
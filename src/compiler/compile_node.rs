@@ -1,16 +1,25 @@
 use inkwell::{
-    types::BasicTypeEnum,
-    values::BasicValueEnum,
-    AddressSpace,
+    types::{BasicType, BasicTypeEnum},
+    values::{BasicMetadataValueEnum, BasicValue, BasicValueEnum, InstructionValue, PointerValue},
+    AddressSpace, FloatPredicate, IntPredicate,
 };
 
 use crate::{
-    compiler::ScopeVariable,
-    parser::{Assignment, Break, Expr, Loop, Term, Type, Variable},
+    bug,
+    parser::{ConditionalOperator, MathOperator},
+    semantic::{
+        stdlib::std_function, Callee, TypedCall, TypedExpr, TypedExprKind, TypedNode, ValidType,
+        VariableRef,
+    },
     utils::Result as ResultE,
 };
 
-use super::{compile, CompileMetadata, Compiler, LoopMetadata};
+use super::{
+    compile, compile_block, declare_extern, declare_function, declare_local, enter_block,
+    from_c_string, is_terminated,
+    runtime::{call_runtime, call_runtime_out, call_std, to_pointer},
+    to_c_string, CompileMetadata, Compiler, LoopMetadata,
+};
 
 pub trait Compile<'a> {
     fn compile(
@@ -24,662 +33,692 @@ pub trait Compute<'a, T> {
     fn compute(&self, compiler: &Compiler<'a>, compile_meta: &CompileMetadata<'a>) -> ResultE<T>;
 }
 
-impl<'a> Compile<'a> for Variable {
+impl<'a> Compile<'a> for TypedNode {
     fn compile(
         &self,
         compiler: &Compiler<'a>,
         compile_meta: &mut CompileMetadata<'a>,
     ) -> ResultE<()> {
-        fn var<'a>(
-            x: BasicValueEnum<'a>,
-            ident: &str,
-            compiler: &Compiler<'a>,
-            compile_meta: &mut CompileMetadata<'a>,
-            r#type: ValidType,
-        ) {
-            let alloca = compiler.builder.build_alloca(x.get_type(), &ident);
-            compiler.builder.build_store(alloca, x);
-
-            compile_meta.function_scope.variables.insert(
-                ident.to_string(),
-                ScopeVariable {
-                    ptr: alloca,
-                    r#type,
-                },
-            ); // allows shadowing
-        }
+        match self {
+            TypedNode::Variable(variable, value) | TypedNode::Assignment(variable, value) => {
+                if let (TypedNode::Variable(..), VariableRef::Local(id)) = (self, variable) {
+                    declare_local(compiler, compile_meta, *id);
+                }
+                let ptr = variable_ptr(compiler, compile_meta, variable, &value.r#type);
+                let r#type = &value.r#type;
+                let value = value.compute(compiler, compile_meta)?;
+                let store = compiler.builder.build_store(ptr, value);
+                set_volatile(compile_meta, variable, store);
+
+                if compile_meta.trace.is_some() {
+                    trace_variable(compiler, compile_meta, variable, r#type, value);
+                }
+            }
+            TypedNode::Loop(body) => {
+                let fn_value = compile_meta.fn_value;
+                let loop_block = compiler.context.append_basic_block(fn_value, "loop");
+                let exit_block = compiler.context.append_basic_block(fn_value, "exit");
 
-        let r#type = ValidType::try_from(&self.declaration.r#type)?;
+                // RT JMP start loop_block
+                compiler.builder.build_unconditional_branch(loop_block);
 
-        match &self.value {
-            Expr::BinaryExpr(_) => todo!(),
-            Expr::ConditionalExpr(_) => todo!(),
-            Expr::IndexExpr(_) => todo!(),
-            Expr::Term(term) => {
-                // All terms, besides `Ident` are available at compile time.
+                // COMP JMP end loop_block
+                compiler.builder.position_at_end(loop_block);
+                let outer_loop = compile_meta.r#loop.replace(LoopMetadata {
+                    exit_block,
+                    loop_block,
+                    tries: compile_meta.tries,
+                });
 
-                if !matches!(term, Term::Null | Term::Ident(..)) {
-                    if !r#type.same_as_term(term)? {
-                        return Err(
-                            format!("Invalid type, got {:?}, expected {:?}", term, r#type).into(),
-                        );
-                    }
-                }
+                compile_block(compiler, body, compile_meta)?;
 
-                let ident = self.declaration.ident.0.as_str();
-
-                match term {
-                    Term::Number(n) => {
-                        var(
-                            compiler.context.f64_type().const_float(*n).into(),
-                            ident,
-                            &compiler,
-                            compile_meta,
-                            r#type,
-                        );
-                    }
-                    Term::String(s) => {
-                        var(
-                            compiler
-                                .builder
-                                .build_global_string_ptr(s, ".str")
-                                .as_pointer_value()
-                                .into(),
-                            ident,
-                            &compiler,
-                            compile_meta,
-                            r#type,
-                        );
-                        //compiler.context.i64_type().const_int(x.len() as u64, false);
-                    }
-                    Term::Ident(variable) => {
-                        let variable = compile_meta
-                            .function_scope
-                            .variables
-                            .get(&variable.0)
-                            .ok_or(format!("Use of undefined variable {}", variable.0))?;
-                        if r#type != variable.r#type {
-                            return Err(format!(
-                                "Attempt to assign {:?} to variable of type {:?}",
-                                variable.r#type, r#type
-                            )
-                            .into());
-                        }
-                        var(variable.ptr.into(), ident, &compiler, compile_meta, r#type);
-                    }
-                    Term::Boolean(b) => {
-                        var(
-                            compiler
-                                .context
-                                .bool_type()
-                                .const_int((*b).into(), false)
-                                .into(),
-                            ident,
-                            &compiler,
-                            compile_meta,
-                            r#type,
-                        );
-                    }
-                    Term::Array(_) => {
-                        todo!(); // recursion
-                    }
-                    Term::Null => {
-                        let t = r#type.get_llvm_type(&compiler);
-                        var(
-                            t.const_zero().into(),
-                            ident,
-                            &compiler,
-                            compile_meta,
-                            r#type,
-                        );
-                    }
+                compile_meta.r#loop = outer_loop;
+
+                if !is_terminated(compiler) {
+                    compiler.builder.build_unconditional_branch(loop_block);
                 }
+
+                // COMP JMP end exit_block
+                compiler.builder.position_at_end(exit_block);
             }
-            Expr::CallExpr(_call) => {
-                // compile call, store into x address, set variable to *x
-                todo!();
+            TypedNode::Break => {
+                let r#loop = compile_meta
+                    .r#loop
+                    .as_ref()
+                    .unwrap_or_else(|| bug!("BREAK_OUTSIDE_LOOP"));
+                exit_tries(compiler, compile_meta.tries - r#loop.tries);
+                compiler
+                    .builder
+                    .build_unconditional_branch(r#loop.exit_block);
             }
-        }
-        Ok(())
-    }
-}
-
-impl<'a> Compile<'a> for Assignment {
-    fn compile(
-        &self,
-        compiler: &Compiler<'a>,
-        compile_meta: &mut CompileMetadata<'a>,
-    ) -> ResultE<()> {
-        let var = compile_meta
-            .function_scope
-            .variables
-            .get(&self.ident.0)
-            .ok_or::<Box<dyn std::error::Error>>(
-                format!("Assignment to undefined variable {}", self.ident.0).into(),
-            )?;
-
-        let value = match &self.value {
-            Expr::BinaryExpr(_) => todo!(),
-            Expr::ConditionalExpr(_) => todo!(),
-            Expr::IndexExpr(_) => todo!(),
-            Expr::Term(term) => {
-                // All terms, besides `Ident` are available at compile time.
-
-                if !matches!(term, Term::Null | Term::Ident(..)) {
-                    if !var.r#type.same_as_term(&term)? {
-                        return Err(format!(
-                            "Invalid type, got {:?}, expected {:?}",
-                            term, var.r#type
-                        )
-                        .into());
+            TypedNode::If(cases, r#else) => {
+                let fn_value = compile_meta.fn_value;
+                let after_block = compiler.context.append_basic_block(fn_value, "if_after");
+
+                for (condition, body) in cases {
+                    let condition = condition.compute(compiler, compile_meta)?.into_int_value();
+                    let then_block = compiler.context.append_basic_block(fn_value, "if");
+                    let else_block = compiler.context.append_basic_block(fn_value, "if_else");
+                    compiler
+                        .builder
+                        .build_conditional_branch(condition, then_block, else_block);
+
+                    compiler.builder.position_at_end(then_block);
+                    compile_block(compiler, body, compile_meta)?;
+                    if !is_terminated(compiler) {
+                        compiler.builder.build_unconditional_branch(after_block);
                     }
+
+                    compiler.builder.position_at_end(else_block);
                 }
 
-                match term {
-                    Term::Number(x) => compiler.context.f64_type().const_float(*x).into(),
-                    Term::String(x) => {
-                        let mut chars = x
-                            .chars()
-                            .map(|x| compiler.context.i8_type().const_int(x.into(), false))
-                            .collect::<Vec<_>>();
-                        chars.push(compiler.context.i8_type().const_int(0, false)); // null terminator
+                if let Some(body) = r#else {
+                    compile_block(compiler, body, compile_meta)?;
+                }
+                if !is_terminated(compiler) {
+                    compiler.builder.build_unconditional_branch(after_block);
+                }
 
-                        compiler
-                            .context
-                            .i8_type()
-                            .const_array(chars.as_slice())
-                            .into()
-                    }
-                    Term::Boolean(x) => compiler
-                        .context
-                        .bool_type()
-                        .const_int((*x).into(), false)
-                        .into(),
-                    Term::Array(_) => todo!(),
-                    Term::Null => var.r#type.get_llvm_type(compiler).const_zero(),
-                    Term::Ident(x) => {
-                        let variable = compile_meta
-                            .function_scope
-                            .variables
-                            .get(&x.0)
-                            .ok_or(format!("Use of undefined variable {}", x.0))?;
-                        if var.r#type != variable.r#type {
-                            return Err(format!(
-                                "Attempt to assign {:?} to variable of type {:?}",
-                                variable.r#type, var.r#type
-                            )
-                            .into());
-                        }
+                compiler.builder.position_at_end(after_block);
+            }
+            TypedNode::Call(call) => {
+                call.compute(compiler, compile_meta)?;
+            }
+            TypedNode::Return(value) => {
+                let value = value.compute(compiler, compile_meta)?;
+                exit_tries(compiler, compile_meta.tries);
+                if compile_meta.entry {
+                    // Exit code
+                    let code = compiler.builder.build_float_to_signed_int(
+                        value.into_float_value(),
+                        compiler.context.i32_type(),
+                        "exit_code",
+                    );
+                    compiler.builder.build_return(Some(&code));
+                } else {
+                    compiler.builder.build_return(Some(&value));
+                }
+            }
+            TypedNode::Throw(bullet) => {
+                let bullet = bullet.compute(compiler, compile_meta)?;
+                call_runtime(compiler, "rl_throw", &[to_pointer(compiler, bullet).into()]);
+                compiler.builder.build_unreachable();
+            }
+            TypedNode::TryCatch(r#try, bullet, catch) => {
+                let fn_value = compile_meta.fn_value;
+                let try_block = compiler.context.append_basic_block(fn_value, "try");
+                let catch_block = compiler.context.append_basic_block(fn_value, "catch");
+                let after_block = compiler.context.append_basic_block(fn_value, "try_after");
+
+                // `setjmp` returns again with a non zero value when a bullet is thrown
+                let buf = call_runtime(compiler, "rl_try_enter", &[])
+                    .unwrap_or_else(|| bug!("VOID_TRY_ENTER"));
+                let thrown = call_runtime(compiler, "setjmp", &[buf.into()])
+                    .unwrap_or_else(|| bug!("VOID_SETJMP"))
+                    .into_int_value();
+                let thrown = compiler.builder.build_int_compare(
+                    IntPredicate::NE,
+                    thrown,
+                    thrown.get_type().const_zero(),
+                    "thrown",
+                );
+                compiler
+                    .builder
+                    .build_conditional_branch(thrown, catch_block, try_block);
+
+                compiler.builder.position_at_end(try_block);
+                compile_meta.tries += 1;
+                compile_block(compiler, r#try, compile_meta)?;
+                compile_meta.tries -= 1;
+                if !is_terminated(compiler) {
+                    exit_tries(compiler, 1);
+                    compiler.builder.build_unconditional_branch(after_block);
+                }
 
-                        variable.ptr.into()
+                // The runtime already exited the `try`
+                compiler.builder.position_at_end(catch_block);
+                match bullet {
+                    // The bullet is a local of the block of `catch`
+                    Some(VariableRef::Local(id)) => {
+                        let span = compile_meta.declarations[id.0].span;
+                        let outer_scope = enter_block(compiler, compile_meta, span);
+                        declare_local(compiler, compile_meta, *id);
+                        let value =
+                            call_runtime_out(compiler, "rl_bullet", &ValidType::String, &[]);
+                        compiler
+                            .builder
+                            .build_store(compile_meta.locals[id.0], value);
+                        compile(compiler, catch, compile_meta)?;
+                        compile_meta.scope = outer_scope;
                     }
+                    Some(VariableRef::Global(symbol)) => bug!("GLOBAL_BULLET({})", symbol),
+                    None => compile_block(compiler, catch, compile_meta)?,
+                }
+                if !is_terminated(compiler) {
+                    compiler.builder.build_unconditional_branch(after_block);
                 }
+
+                compiler.builder.position_at_end(after_block);
             }
-            Expr::CallExpr(_) => todo!(),
-        };
+            TypedNode::Import(entry) => {
+                let entry = compiler.module.get_function(entry).unwrap_or_else(|| {
+                    let main_type = compiler.context.i32_type().fn_type(&[], false);
+                    compiler.module.add_function(entry, main_type, None)
+                });
+                compiler.builder.build_call(entry, &[], "");
+            }
+        }
 
-        compiler.builder.build_store(var.ptr, value);
         Ok(())
     }
 }
 
-impl<'a> Compile<'a> for Loop {
-    fn compile(
+/// None if the function does not return a value
+impl<'a> Compute<'a, Option<BasicValueEnum<'a>>> for TypedCall {
+    fn compute(
         &self,
         compiler: &Compiler<'a>,
-        compile_meta: &mut CompileMetadata<'a>,
-    ) -> ResultE<()> {
-        let fn_value = compile_meta.fn_value;
-        let loop_block = compiler.context.append_basic_block(fn_value, "loop");
-        let exit_block = compiler.context.append_basic_block(fn_value, "exit");
-
-        // RT JMP start loop_block
-        compiler.builder.build_unconditional_branch(loop_block);
-
-        // COMP JMP end loop_block
-        compiler.builder.position_at_end(loop_block);
-        //        let (exit_block, break_values, value) = self.gen_loop_block_expr(body_expr, exit_block);
-        //
-        compile_meta.r#loop = Some(LoopMetadata {
-            exit_block,
-            loop_block,
-        });
-
-        compile(&compiler, &self.0, compile_meta)?;
+        compile_meta: &CompileMetadata<'a>,
+    ) -> ResultE<Option<BasicValueEnum<'a>>> {
+        let args = self
+            .args
+            .iter()
+            .map(|x| x.compute(compiler, compile_meta))
+            .collect::<ResultE<Vec<BasicValueEnum>>>()?;
 
-        compile_meta.r#loop = None;
+        let function = match &self.callee {
+            Callee::Function(x) => declare_function(compiler, x, &self.signature),
+            Callee::Std(x) => return Ok(call_std(compiler, x, &self.signature, &args)),
+            Callee::Extern(x) => declare_extern(compiler, x, &self.signature),
+        };
 
-        compiler
+        // C functions take and return strings as `const char *`
+        let is_extern = matches!(self.callee, Callee::Extern(_));
+        let args = args
+            .into_iter()
+            .zip(&self.signature.params)
+            .map(|(x, r#type)| match r#type {
+                ValidType::String if is_extern => to_c_string(compiler, x).into(),
+                _ => x.into(),
+            })
+            .collect::<Vec<BasicMetadataValueEnum>>();
+
+        let value = compiler
             .builder
-            .position_at_end(exit_block.get_previous_basic_block().unwrap());
-
-        // RT JMP start loop_block
-        compiler.builder.build_unconditional_branch(loop_block);
-
-        // COMP JMP end exit_block
-        compiler.builder.position_at_end(exit_block);
-
-        // RT JMP start exit_block
-        // compiler.builder.build_unconditional_branch(exit_block);
-
-        //if value.is_some() {
-        //    self.builder.build_unconditional_branch(loop_block);
-        //}
-        Ok(())
+            .build_call(function, args.as_slice(), "return")
+            .try_as_basic_value()
+            .left();
+        Ok(match (value, &self.signature.ret) {
+            (Some(x), Some(ValidType::String)) if is_extern => Some(from_c_string(compiler, x)),
+            (value, _) => value,
+        })
     }
 }
 
-impl<'a> Compile<'a> for Break {
-    fn compile(
+impl<'a> Compute<'a, BasicValueEnum<'a>> for TypedExpr {
+    fn compute(
         &self,
         compiler: &Compiler<'a>,
-        compile_meta: &mut CompileMetadata<'a>,
-    ) -> ResultE<()> {
-        let r#loop = compile_meta
-            .r#loop
-            .as_ref()
-            .ok_or("Break used outside of a loop".to_string())?;
-        compiler
-            .builder
-            .build_unconditional_branch(r#loop.exit_block);
-        compiler
-            .context
-            .insert_basic_block_after(r#loop.loop_block, "_break_seperator");
+        compile_meta: &CompileMetadata<'a>,
+    ) -> ResultE<BasicValueEnum<'a>> {
+        let f64_type = compiler.context.f64_type();
+        let i64_type = compiler.context.i64_type();
+
+        Ok(match &self.kind {
+            TypedExprKind::Number(x) => f64_type.const_float(*x).into(),
+            TypedExprKind::String(x) => const_string(compiler, x),
+            TypedExprKind::Boolean(x) => compiler
+                .context
+                .bool_type()
+                .const_int((*x).into(), false)
+                .into(),
+            TypedExprKind::Null => self.r#type.get_llvm_type(compiler).const_zero(),
+            TypedExprKind::Array(items) => {
+                let element_type = match &self.r#type {
+                    ValidType::Array(x) => x.get_llvm_type(compiler),
+                    _ => bug!("ARRAY_NOT_ARRAY_TYPE({:?})", self.r#type),
+                };
+
+                let len = i64_type.const_int(items.len() as u64, false);
+                let size = compiler.builder.build_int_mul(
+                    len,
+                    element_type
+                        .size_of()
+                        .unwrap_or_else(|| bug!("UNSIZED_ELEMENT({:?})", self.r#type)),
+                    "array_size",
+                );
+                let data = call_runtime(compiler, "rl_alloc", &[size.into()])
+                    .unwrap_or_else(|| bug!("VOID_ALLOC"))
+                    .into_pointer_value();
+                let data = compiler.builder.build_pointer_cast(
+                    data,
+                    element_type.ptr_type(AddressSpace::default()),
+                    "array",
+                );
 
-        Ok(())
-    }
-}
+                for (i, item) in items.iter().enumerate() {
+                    let value = item.compute(compiler, compile_meta)?;
+                    let element = unsafe {
+                        compiler.builder.build_gep(
+                            element_type,
+                            data,
+                            &[i64_type.const_int(i as u64, false)],
+                            "element",
+                        )
+                    };
+                    compiler.builder.build_store(element, value);
+                }
 
-/*
-impl<'a> Compile<'a> for Call {
-    fn compile(&self, compiler: &Compiler<'a>, compile_meta: &mut CompileMetadata<'a>) {
-        let function = compiler
-            .module
-            .get_function(self.ident.0.as_str())
-            .unwrap_or_else(|| error!("Use of undefined function `{}`", self.ident.0));
+                let array = self
+                    .r#type
+                    .get_llvm_type(compiler)
+                    .into_struct_type()
+                    .get_undef();
+                let array = compiler
+                    .builder
+                    .build_insert_value(array, len, 0, "array")
+                    .unwrap()
+                    .into_struct_value();
+                compiler
+                    .builder
+                    .build_insert_value(array, data, 1, "array")
+                    .unwrap()
+                    .into_struct_value()
+                    .into()
+            }
+            TypedExprKind::Variable(variable) => {
+                let ptr = variable_ptr(compiler, compile_meta, variable, &self.r#type);
+                let value =
+                    compiler
+                        .builder
+                        .build_load(self.r#type.get_llvm_type(compiler), ptr, "load");
+                let load = value
+                    .as_instruction_value()
+                    .unwrap_or_else(|| bug!("LOAD_NOT_INSTRUCTION({:?})", variable));
+                set_volatile(compile_meta, variable, load);
+                value
+            }
+            TypedExprKind::Call(call) => call
+                .compute(compiler, compile_meta)?
+                .unwrap_or_else(|| bug!("VOID_CALL_EXPR({:?})", call.callee)),
+            TypedExprKind::Binary(lhs, _, rhs) if self.r#type == ValidType::String => {
+                let lhs = lhs.compute(compiler, compile_meta)?;
+                let rhs = rhs.compute(compiler, compile_meta)?;
+                concat(compiler, lhs, rhs)
+            }
+            TypedExprKind::Binary(lhs, operator, rhs) => {
+                let lhs = lhs.compute(compiler, compile_meta)?.into_float_value();
+                let rhs = rhs.compute(compiler, compile_meta)?.into_float_value();
+
+                match operator {
+                    MathOperator::Add => compiler.builder.build_float_add(lhs, rhs, "expr_add"),
+                    MathOperator::Subtract => {
+                        compiler.builder.build_float_sub(lhs, rhs, "expr_sub")
+                    }
+                    MathOperator::Multiply => {
+                        compiler.builder.build_float_mul(lhs, rhs, "expr_mul")
+                    }
+                    MathOperator::Divide => compiler.builder.build_float_div(lhs, rhs, "expr_div"),
+                    MathOperator::XOR => {
+                        let lhs = compiler
+                            .builder
+                            .build_float_to_signed_int(lhs, i64_type, "");
+                        let rhs = compiler
+                            .builder
+                            .build_float_to_signed_int(rhs, i64_type, "");
+                        let result = compiler.builder.build_xor(lhs, rhs, "expr_xor");
+                        compiler
+                            .builder
+                            .build_signed_int_to_float(result, f64_type, "expr_xor")
+                    }
+                    MathOperator::Modulus => compiler.builder.build_float_rem(lhs, rhs, "expr_mod"),
+                }
+                .into()
+            }
+            TypedExprKind::Conditional(lhs, operator, rhs) => {
+                let lhs_value = lhs.compute(compiler, compile_meta)?;
+                let rhs_value = rhs.compute(compiler, compile_meta)?;
+
+                match &lhs.r#type {
+                    ValidType::Number => {
+                        let predicate = match operator {
+                            ConditionalOperator::Equality => FloatPredicate::OEQ,
+                            ConditionalOperator::AntiEquality => FloatPredicate::UNE,
+                            ConditionalOperator::GreaterThan => FloatPredicate::OGT,
+                            ConditionalOperator::GreaterThanOrEqual => FloatPredicate::OGE,
+                            ConditionalOperator::LessThan => FloatPredicate::OLT,
+                            ConditionalOperator::LessThanOrEqual => FloatPredicate::OLE,
+                        };
+                        compiler.builder.build_float_compare(
+                            predicate,
+                            lhs_value.into_float_value(),
+                            rhs_value.into_float_value(),
+                            "expr_cmp",
+                        )
+                    }
+                    ValidType::Boolean => compiler.builder.build_int_compare(
+                        int_equality(operator),
+                        lhs_value.into_int_value(),
+                        rhs_value.into_int_value(),
+                        "expr_cmp",
+                    ),
+                    // Compared to 0 like `strcmp`, `wat` is before every other string
+                    ValidType::String => {
+                        let ordering = call_runtime(
+                            compiler,
+                            "rl_compare",
+                            &[
+                                to_pointer(compiler, lhs_value).into(),
+                                to_pointer(compiler, rhs_value).into(),
+                            ],
+                        )
+                        .unwrap_or_else(|| bug!("VOID_COMPARE"))
+                        .into_int_value();
+                        compiler.builder.build_int_compare(
+                            int_ordering(operator),
+                            ordering,
+                            ordering.get_type().const_zero(),
+                            "expr_cmp",
+                        )
+                    }
+                    ValidType::Array(_) => bug!("ARRAY_COMPARISON({:?})", operator),
+                }
+                .into()
+            }
+            TypedExprKind::Interpolation(parts) => {
+                let mut text = const_string(compiler, "");
+                for part in parts {
+                    let value = part.compute(compiler, compile_meta)?;
+                    let value = match &part.r#type {
+                        ValidType::String => value,
+                        ValidType::Number => {
+                            let nums = std_function("nums")
+                                .unwrap_or_else(|| bug!("UNKNOWN_STD_FUNCTION(nums)"));
+                            call_std(compiler, nums.name, &nums.signature(), &[value])
+                                .unwrap_or_else(|| bug!("VOID_NUMS"))
+                        }
+                        ValidType::Boolean => compiler.builder.build_select(
+                            value.into_int_value(),
+                            const_string(compiler, "Yup"),
+                            const_string(compiler, "Nope"),
+                            "boolean",
+                        ),
+                        _ => bug!("INVALID_INTERPOLATION({:?})", part.r#type),
+                    };
+                    text = concat(compiler, text, value);
+                }
+                text
+            }
+            TypedExprKind::Index(array, index) => {
+                let array = array.compute(compiler, compile_meta)?.into_struct_value();
+                let index = index.compute(compiler, compile_meta)?.into_float_value();
 
-        if function.is_null() || function.is_undef() {
-            error!("Function `{}` is null or undefined", self.ident.0);
-        }
+                let length = compiler
+                    .builder
+                    .build_extract_value(array, 0, "length")
+                    .unwrap()
+                    .into_int_value();
+                let data = compiler
+                    .builder
+                    .build_extract_value(array, 1, "data")
+                    .unwrap()
+                    .into_pointer_value();
 
-        let args: Vec<BasicMetadataValueEnum<'_>> = self
-            .args
-            .iter()
-            /*.map(|x| x.compute(compiler, compile_meta).unwrap())
-            .map(|z| match z {
-                Value::Number(x) => x.into(),
-                Value::Boolean(x) => x.into(),
-                Value::String(x, _) => x.into(),
-                Value::Array(x) => x.into(),
-                Value::Null => todo!(),
-            })*/
-            .collect();
-
-        compiler
-            .builder
-            .build_call(function, args.as_slice(), "return");
-    }
-}*/
-
-/*impl<'a> Compile<'a> for Loop {
-    fn compile(&self, compiler: &Compiler<'a>, compile_meta: &mut CompileMetadata<'a>) {
-        let function = compile_meta.basic_block.get_parent().unwrap();
-        let loop_block = compiler.context.append_basic_block(function, "loop");
-        let exit_block = compiler.context.append_basic_block(function, "exit");
-
-        compiler.builder.build_unconditional_branch(loop_block);
-
-        compiler.builder.position_at_end(loop_block);
-        compile(
-            &compiler,
-            &self.body,
-            &mut CompileMetadata {
-                basic_block: exit_block,
-                function_scope: compile_meta.function_scope.clone(),
-            },
-        );
-
-        // break, edit: no this does not fucking work. whuyyuyuyuyyyyyyyyyyyyyyyyy
-        if loop_block.get_terminator().is_none() {
-            compiler.builder.build_unconditional_branch(loop_block);
-        }
+                // Checked before casting, so huge numbers, infinities and NaN are out of bounds too
+                let f64_type = compiler.context.f64_type();
+                let elements = compiler
+                    .builder
+                    .build_signed_int_to_float(length, f64_type, "elements");
+                let end =
+                    compiler
+                        .builder
+                        .build_float_add(elements, f64_type.const_float(1.0), "end");
+                let above = compiler.builder.build_float_compare(
+                    FloatPredicate::OGE,
+                    index,
+                    f64_type.const_float(1.0),
+                    "above",
+                );
+                let below =
+                    compiler
+                        .builder
+                        .build_float_compare(FloatPredicate::OLT, index, end, "below");
+                let in_bounds = compiler.builder.build_and(above, below, "in_bounds");
+
+                let fn_value = compile_meta.fn_value;
+                let in_bounds_block = compiler.context.append_basic_block(fn_value, "in_bounds");
+                let out_of_bounds_block = compiler
+                    .context
+                    .append_basic_block(fn_value, "out_of_bounds");
+                compiler.builder.build_conditional_branch(
+                    in_bounds,
+                    in_bounds_block,
+                    out_of_bounds_block,
+                );
 
-        compiler.builder.position_at_end(exit_block);
+                // The same bullet as the interpreter
+                compiler.builder.position_at_end(out_of_bounds_block);
+                let nums =
+                    std_function("nums").unwrap_or_else(|| bug!("UNKNOWN_STD_FUNCTION(nums)"));
+                let mut bullet = const_string(compiler, "Index ");
+                for part in [
+                    call_std(compiler, nums.name, &nums.signature(), &[index.into()]),
+                    Some(const_string(
+                        compiler,
+                        " is out of bounds of an array with ",
+                    )),
+                    call_std(compiler, nums.name, &nums.signature(), &[elements.into()]),
+                    Some(const_string(compiler, " elements")),
+                ] {
+                    let part = part.unwrap_or_else(|| bug!("VOID_NUMS"));
+                    bullet = concat(compiler, bullet, part);
+                }
+                call_runtime(compiler, "rl_throw", &[to_pointer(compiler, bullet).into()]);
+                compiler.builder.build_unreachable();
+
+                // Array indexes start with 1
+                compiler.builder.position_at_end(in_bounds_block);
+                let index = compiler
+                    .builder
+                    .build_float_to_signed_int(index, i64_type, "index");
+                let index =
+                    compiler
+                        .builder
+                        .build_int_sub(index, i64_type.const_int(1, false), "index");
+
+                let element_type = self.r#type.get_llvm_type(compiler);
+                let element = unsafe {
+                    compiler
+                        .builder
+                        .build_gep(element_type, data, &[index], "element")
+                };
+                compiler
+                    .builder
+                    .build_load(element_type, element, "element")
+            }
+        })
     }
 }
 
-impl<'a> Compile<'a> for Break {
-    fn compile(&self, compiler: &Compiler, compile_meta: &mut CompileMetadata<'_>) {
-        compiler
-            .builder
-            .build_unconditional_branch(compile_meta.basic_block);
-        // compiler.builder.position_at_end(*return_block);
+/// Leaves the innermost `count` `try` blocks, used when jumping out of them
+fn exit_tries(compiler: &Compiler, count: usize) {
+    for _ in 0..count {
+        call_runtime(compiler, "rl_try_exit", &[]);
     }
 }
 
-impl<'a> Compile<'a> for IfBlock {
-    fn compile(&self, _compiler: &Compiler<'a>, _compile_meta: &mut CompileMetadata<'_>) {
-        todo!();
-        // let function = basic_block.get_parent().unwrap();
-        // let after_block = compiler.context.append_basic_block(function, "if_after");
-        // let mut else_block = after_block;
-
-        // let cases = self
-        //     .if_nodes
-        //     .iter()
-        //     .map(|x| match x {
-        //         IfNode::Case(x) => {
-        //             let condition = to_boolean
-        // (&compiler, x.expr.compute(&compiler).unwrap());
-
-        //             compiler.builder.position_at_end(*basic_block);
-        //             let alloca = compiler
-        //                 .builder
-        //                 .build_alloca(condition.get_type(), "if_cond");
-        //             compiler.builder.build_store(alloca, condition);
-        //             let value =
-        //                 compiler
-        //                     .builder
-        //                     .build_ptr_to_int(alloca, condition.get_type(), "read");
-
-        //             let block = compiler.context.append_basic_block(function, "if");
-        //             compiler.builder.position_at_end(block);
-
-        //             compile(&compiler, &x.body, &block);
-
-        //             compiler.builder.position_at_end(block);
-        //             compiler.builder.build_unconditional_branch(after_block);
-
-        //             Some((value, block))
-        //         }
-        //         IfNode::Else(x) => {
-        //             let block = compiler.context.append_basic_block(function, "else");
-        //             else_block = block;
-        //             compiler.builder.position_at_end(block);
-
-        //             compile(&compiler, &x.body, &basic_block);
-
-        //             compiler.builder.position_at_end(block);
-        //             compiler.builder.build_unconditional_branch(after_block);
-
-        //             None
-        //         }
-        //     })
-        //     .filter(|x| x.is_some()) // collect on results can go to Result<Vec<_>>!!!! FIX THIS
-        //     .map(|x| x.unwrap())
-        //     .collect::<Vec<_>>();
-
-        // // Will run the first block with a true value
-        // compiler.builder.position_at_end(*basic_block);
-        // compiler.builder.build_switch(
-        //     compiler.context.bool_type().const_all_ones(),
-        //     else_block,
-        //     &cases,
-        // );
-        // compiler.builder.position_at_end(after_block);
+fn int_equality(operator: &ConditionalOperator) -> IntPredicate {
+    match operator {
+        ConditionalOperator::Equality => IntPredicate::EQ,
+        ConditionalOperator::AntiEquality => IntPredicate::NE,
+        _ => bug!("INVALID_BOOLEAN_COMPARISON({:?})", operator),
     }
-}*/
-
-/*fn to_boolean<'a>(compiler: &Compiler<'a>, value: Value<'a>) -> IntValue<'a> {
-    let float = |x: FloatValue<'a>| {
-        compiler.builder.build_float_compare(
-            FloatPredicate::ONE,
-            x,
-            x.get_type().const_zero(),
-            "expr_truthy",
-        )
-    };
-
-    let int = |x: IntValue<'a>| {
-        compiler.builder.build_int_compare(
-            IntPredicate::NE,
-            x,
-            x.get_type().const_zero(),
-            "expr_truthy",
-        )
-    };
+}
 
-    match value {
-        Value::Number(x) => float(x),
-        Value::Boolean(x) => x, // Is already a 0 or a 1
-        Value::Null => compiler.context.bool_type().const_zero(),
-        Value::String(_ptr, len) => int(len),
-        Value::Array(_) => todo!(),
+fn int_ordering(operator: &ConditionalOperator) -> IntPredicate {
+    match operator {
+        ConditionalOperator::Equality => IntPredicate::EQ,
+        ConditionalOperator::AntiEquality => IntPredicate::NE,
+        ConditionalOperator::GreaterThan => IntPredicate::SGT,
+        ConditionalOperator::GreaterThanOrEqual => IntPredicate::SGE,
+        ConditionalOperator::LessThan => IntPredicate::SLT,
+        ConditionalOperator::LessThanOrEqual => IntPredicate::SLE,
     }
-}*/
-
-#[derive(Debug, PartialEq)]
-pub enum ValidType {
-    Number,
-    Boolean,
-    String,
-    Array(Box<ValidType>), // Array is generic
-                           // Null, // it is not a type, but a value that is any type
 }
 
-impl TryFrom<Type> for ValidType {
-    type Error = String;
+/// A string constant, `{ length, data }`
+fn const_string<'a>(compiler: &Compiler<'a>, text: &str) -> BasicValueEnum<'a> {
+    let data = compiler
+        .builder
+        .build_global_string_ptr(text, ".str")
+        .as_pointer_value();
+    let length = compiler
+        .context
+        .i64_type()
+        .const_int(text.len() as u64, false);
+    compiler
+        .context
+        .const_struct(&[length.into(), data.into()], false)
+        .into()
+}
 
-    fn try_from(value: Type) -> Result<Self, Self::Error> {
-        (&value).try_into()
-    }
+/// Joins two strings, `wat` is joined as `wat`
+fn concat<'a>(
+    compiler: &Compiler<'a>,
+    lhs: BasicValueEnum<'a>,
+    rhs: BasicValueEnum<'a>,
+) -> BasicValueEnum<'a> {
+    call_runtime_out(
+        compiler,
+        "rl_concat",
+        &ValidType::String,
+        &[
+            to_pointer(compiler, lhs).into(),
+            to_pointer(compiler, rhs).into(),
+        ],
+    )
 }
 
-impl<'a> TryFrom<&'a Type> for ValidType {
-    type Error = String;
-
-    fn try_from(value: &'a Type) -> Result<Self, Self::Error> {
-        match value.root_type.0.as_str() {
-            "Number" => Ok(Self::Number),
-            "Boolean" => Ok(Self::Boolean),
-            "String" => Ok(Self::String),
-            "Array" => {
-                let generic1 = &value.generics[0];
-                let generic1 = ValidType::try_from(generic1)?;
-                Ok(Self::Array(Box::from(generic1)))
-            }
-            "Null" => Err("Null is not a valid type, did you mean to use `wat`?".to_string()),
-            _ => Err(format!("Invalid type, got {}", value.root_type.0)),
-        }
+/// Pointer to a variable, declares globals of other modules
+fn variable_ptr<'a>(
+    compiler: &Compiler<'a>,
+    compile_meta: &CompileMetadata<'a>,
+    variable: &VariableRef,
+    r#type: &ValidType,
+) -> PointerValue<'a> {
+    match variable {
+        VariableRef::Local(id) => compile_meta.locals[id.0],
+        VariableRef::Global(symbol) => compiler
+            .module
+            .get_global(symbol)
+            .unwrap_or_else(|| {
+                compiler
+                    .module
+                    .add_global(r#type.get_llvm_type(compiler), None, symbol)
+            })
+            .as_pointer_value(),
     }
 }
 
-impl ValidType {
-    pub fn same_as_term(&self, term: &Term) -> Result<bool, String> {
-        match term {
-            Term::Number(_) => {
-                Ok(matches!(self, ValidType::Number))
-            },
-            Term::String(_) => {
-                Ok(matches!(self, ValidType::String))
-            },
-            Term::Boolean(_) => {
-                Ok(matches!(self, ValidType::Boolean))
-            },
-            Term::Array(_) => {
-                Ok(matches!(self, ValidType::Array(..))) // TODO: check array type
-            },
-            Term::Ident(_) => {
-                Err("term is an Ident, we do not know the type of an arbitrary identifier at compile time.".to_string())
-            },
-            Term::Null => {
-                Err("Null is not a valid type.".to_string())
-            },
+/// Makes a load or store of a local that is assigned in a `test` volatile
+fn set_volatile(
+    compile_meta: &CompileMetadata,
+    variable: &VariableRef,
+    instruction: InstructionValue,
+) {
+    if let VariableRef::Local(id) = variable {
+        if compile_meta.volatile[id.0] {
+            instruction
+                .set_volatile(true)
+                .unwrap_or_else(|x| bug!("SET_VOLATILE({})", x));
         }
     }
+}
+
+/// Prints the new value of a variable of a `debug` function, ex. `[main.rl:4] x = 5`
+fn trace_variable<'a>(
+    compiler: &Compiler<'a>,
+    compile_meta: &CompileMetadata<'a>,
+    variable: &VariableRef,
+    r#type: &ValidType,
+    value: BasicValueEnum<'a>,
+) {
+    let trace = compile_meta
+        .trace
+        .as_ref()
+        .unwrap_or_else(|| bug!("UNTRACED_FUNCTION"));
+    let name = match variable {
+        VariableRef::Local(id) => &trace.locals[id.0],
+        // `<module>::meth::<name>`
+        VariableRef::Global(symbol) => symbol.rsplit("::").next().unwrap_or(symbol),
+    };
+    let location = format!("{}:{}", trace.file, compile_meta.span.line);
+
+    let location = compiler
+        .builder
+        .build_global_string_ptr(&location, ".trace.location")
+        .as_pointer_value();
+    let name = compiler
+        .builder
+        .build_global_string_ptr(name, ".trace.name")
+        .as_pointer_value();
+
+    let (function, value) = match r#type {
+        ValidType::Number => ("rl_trace_number", value),
+        ValidType::Boolean => ("rl_trace_boolean", value),
+        ValidType::String => ("rl_trace_string", to_pointer(compiler, value).into()),
+        // Only the length of arrays is printed
+        ValidType::Array(_) => (
+            "rl_trace_array",
+            compiler
+                .builder
+                .build_extract_value(value.into_struct_value(), 0, "length")
+                .unwrap_or_else(|| bug!("ARRAY_WITHOUT_LENGTH")),
+        ),
+        ValidType::Infer(_) => bug!("UNRESOLVED_TYPE({:?})", r#type),
+    };
+    call_runtime(
+        compiler,
+        function,
+        &[location.into(), name.into(), value.into()],
+    );
+}
 
+impl ValidType {
     pub fn get_llvm_type<'a>(&self, compiler: &Compiler<'a>) -> BasicTypeEnum<'a> {
         match self {
             ValidType::Number => compiler.context.f64_type().into(),
             ValidType::Boolean => compiler.context.bool_type().into(),
+            // { length, data }, the data is null terminated and null for `wat`
             ValidType::String => compiler
                 .context
-                .i8_type()
-                .ptr_type(AddressSpace::default())
+                .struct_type(
+                    &[
+                        compiler.context.i64_type().into(),
+                        compiler
+                            .context
+                            .i8_type()
+                            .ptr_type(AddressSpace::default())
+                            .into(),
+                    ],
+                    false,
+                )
                 .into(),
+            // { length, elements }
             ValidType::Array(x) => {
-                let inner_type = x.get_llvm_type(&compiler);
-                inner_type.into_pointer_type().into()
-            }
-        }
-    }
-}
-
-/*impl<'a> Compute<'a, Value<'a>> for Expr {
-    fn compute(
-        &self,
-        compiler: &Compiler<'a>,
-        compile_meta: &CompileMetadata<'a>,
-    ) -> Result<Value<'a>, Box<dyn std::error::Error>> {
-        match self {
-            Expr::BinaryExpr(x) => {
-                let mut result = compiler.context.f64_type().const_zero();
-                for term in &x.terms {
-                    let operator = term.operator.as_ref().unwrap_or(&MathOperator::Add);
-                    let operand = compiler.context.f64_type().const_float(match term.operand {
-                        Term::Number(x) => x,
-                        _ => bug!("INVALID_OPERAND({:?})", term.operand),
-                    });
-
-                    result = match operator {
-                        MathOperator::Add => compiler
-                            .builder
-                            .build_float_add(result, operand, "expr_add"),
-                        MathOperator::Subtract => compiler
-                            .builder
-                            .build_float_sub(result, operand, "expr_sub"),
-                        MathOperator::Multiply => compiler
-                            .builder
-                            .build_float_mul(result, operand, "expr_mul"),
-                        MathOperator::Divide => compiler
-                            .builder
-                            .build_float_div(result, operand, "expr_div"),
-                        MathOperator::XOR => {
-                            let result_flt =
-                                result.const_to_signed_int(compiler.context.i64_type());
-                            let operand_flt =
-                                operand.const_to_signed_int(compiler.context.i64_type());
-
-                            result_flt
-                                .const_xor(operand_flt)
-                                .const_signed_to_float(compiler.context.f64_type())
-                        }
-                        MathOperator::Modulus => compiler
-                            .builder
-                            .build_float_rem(result, operand, "expr_mod"),
-                    };
-                }
-
-                Ok(Value::Number(result))
-            }
-            Expr::ConditionalExpr(_) => todo!(),
-            Expr::IndexExpr(_) => todo!(),
-            Expr::Term(x) => Ok(x.compute(compiler, compile_meta)?),
-            Expr::Null => Ok(Value::Null),
-            Expr::CallExpr(_x) => todo!(),
-        }
-    }
-}
-
-impl<'a> Compute<'a, Value<'a>> for Term {
-    fn compute(
-        &self,
-        compiler: &Compiler<'a>,
-        compile_meta: &CompileMetadata<'a>,
-    ) -> Result<Value<'a>, Box<dyn std::error::Error>> {
-        Ok(match self {
-            Term::Number(x) => Value::Number(compiler.context.f64_type().const_float(*x)),
-            Term::String(x) => Value::String(
+                let inner_type = x.get_llvm_type(compiler);
                 compiler
-                    .builder
-                    .build_global_string_ptr(x, ".str")
-                    .as_pointer_value(),
-                compiler.context.i64_type().const_int(x.len() as u64, false),
-            ),
-            Term::Ident(x) => {
-                let ptr = compile_meta
-                    .function_scope
-                    .variables
-                    .get(&x.0)
-                    .unwrap_or_else(|| error!("Use of undefined variable {}", &x.0.bold()));
-
-                let loaded = compiler.builder.build_load(
-                    ptr.get_type(),
-                    *ptr,
-                    format!("{}_access", x.0).as_str(),
-                );
-
-                match loaded {
-                    BasicValueEnum::FloatValue(x) => Value::Number(x),
-                    BasicValueEnum::IntValue(x) => Value::Boolean(x),
-                    BasicValueEnum::PointerValue(x) => {
-                        Value::String(x, compiler.context.i64_type().const_int(3, false))
-                    }
-                    BasicValueEnum::ArrayValue(x) => Value::Array(x),
-                    _ => bug!("UNKNOWN_VAR_TYPE({:?})", loaded),
-                }
+                    .context
+                    .struct_type(
+                        &[
+                            compiler.context.i64_type().into(),
+                            inner_type.ptr_type(AddressSpace::default()).into(),
+                        ],
+                        false,
+                    )
+                    .into()
             }
-        })
-    }
-}
-
-impl<'a> Compile<'a> for Variable {
-    fn compile(&self, compiler: &Compiler<'a>, compile_meta: &mut CompileMetadata<'a>) {
-        let value = self.value.compute(compiler, compile_meta).unwrap();
-
-        fn var<'a>(
-            x: BasicValueEnum<'a>,
-            ident: &String,
-            compiler: &Compiler<'a>,
-            compile_meta: &mut CompileMetadata<'a>,
-        ) {
-            let alloca = compiler.builder.build_alloca(x.get_type(), &ident);
-            compiler.builder.build_store(alloca, x);
-
-            compile_meta
-                .function_scope
-                .variables
-                .insert(ident.to_string(), alloca); // allows shadowing
-        }
-
-        let ident = &self.declaration.ident.0;
-
-        match value {
-            Value::Number(x) => var(x.into(), ident, &compiler, compile_meta),
-            Value::Boolean(x) => var(x.into(), ident, &compiler, compile_meta),
-            Value::String(x, _) => var(x.into(), ident, &compiler, compile_meta),
-            Value::Array(x) => var(x.into(), ident, &compiler, compile_meta),
-            Value::Null => todo!(),
+            ValidType::Infer(_) => bug!("UNRESOLVED_TYPE({:?})", self),
         }
     }
 }
-
-impl<'a> Compile<'a> for Assignment {
-    fn compile(&self, compiler: &Compiler<'a>, compile_meta: &mut CompileMetadata<'a>) {
-        let ptr = *compile_meta
-            .function_scope
-            .variables
-            .get(&self.ident.0)
-            .unwrap_or_else(|| error!("Assignment to undefined variable {}", &self.ident.0.bold()));
-
-        match self.value.compute(compiler, compile_meta).unwrap() {
-            Value::Number(x) => {
-                compiler.builder.build_store(ptr, x);
-            }
-            Value::Boolean(x) => {
-                compiler.builder.build_store(ptr, x);
-            }
-            Value::String(x, _) => {
-                compiler.builder.build_store(ptr, x);
-            }
-            Value::Array(x) => {
-                compiler.builder.build_store(ptr, x);
-            }
-            Value::Null => {
-                compiler
-                    .builder
-                    .build_store(ptr, ptr.get_type().const_null());
-            }
-        }
-    }
-}*/
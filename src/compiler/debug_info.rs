@@ -0,0 +1,290 @@
+use std::path::Path;
+
+use inkwell::{
+    basic_block::BasicBlock,
+    debug_info::{
+        debug_metadata_version, AsDIScope, DICompileUnit, DIFlags, DIFlagsConstants, DIScope,
+        DIType, DWARFEmissionKind, DWARFSourceLanguage, DebugInfoBuilder,
+    },
+    module::{FlagBehavior, Module},
+    values::{FunctionValue, PointerValue},
+    AddressSpace,
+};
+
+use crate::{
+    bug,
+    parser::Span,
+    semantic::{Local, Signature, ValidType},
+};
+
+use super::Compiler;
+
+// DW_ATE_* encodings of basic types
+const DW_ATE_BOOLEAN: u32 = 0x02;
+const DW_ATE_FLOAT: u32 = 0x04;
+const DW_ATE_SIGNED: u32 = 0x05;
+const DW_ATE_SIGNED_CHAR: u32 = 0x06;
+
+/// DWARF of a module, it describes the source file, functions, locals and the line of every
+/// statement
+pub struct DebugInfo<'ctx> {
+    builder: DebugInfoBuilder<'ctx>,
+    compile_unit: DICompileUnit<'ctx>,
+    /// Size of a pointer on the target
+    pointer_bits: u64,
+    optimized: bool,
+}
+
+impl<'ctx> DebugInfo<'ctx> {
+    /// Creates the compile unit of a source file
+    pub fn new(module: &Module<'ctx>, path: &Path, optimized: bool, pointer_bits: u64) -> Self {
+        let context = module.get_context();
+        module.add_basic_value_flag(
+            "Debug Info Version",
+            FlagBehavior::Warning,
+            context
+                .i32_type()
+                .const_int(debug_metadata_version() as u64, false),
+        );
+        module.add_basic_value_flag(
+            "Dwarf Version",
+            FlagBehavior::Warning,
+            context.i32_type().const_int(4, false),
+        );
+
+        let file_name = path.file_name().unwrap().to_str().unwrap();
+        let directory = path.parent().unwrap().to_str().unwrap();
+
+        // There is no DWARF language for RedditLang, debuggers understand C
+        let (builder, compile_unit) = module.create_debug_info_builder(
+            true,
+            DWARFSourceLanguage::C,
+            file_name,
+            directory,
+            "walter",
+            optimized,
+            "",
+            0,
+            "",
+            DWARFEmissionKind::Full,
+            0,
+            false,
+            false,
+            "",
+            "",
+        );
+
+        Self {
+            builder,
+            compile_unit,
+            pointer_bits,
+            optimized,
+        }
+    }
+
+    /// Resolves every temporary node, has to be called before the module is verified
+    pub fn finalize(&self) {
+        self.builder.finalize();
+    }
+
+    fn basic_type(&self, name: &str, size_in_bits: u64, encoding: u32) -> DIType<'ctx> {
+        self.builder
+            .create_basic_type(name, size_in_bits, encoding, DIFlags::ZERO)
+            .unwrap_or_else(|x| bug!("INVALID_DEBUG_TYPE({})", x))
+            .as_type()
+    }
+
+    fn pointer_type(&self, name: &str, pointee: DIType<'ctx>) -> DIType<'ctx> {
+        self.builder
+            .create_pointer_type(
+                name,
+                pointee,
+                self.pointer_bits,
+                self.pointer_bits as u32,
+                AddressSpace::default(),
+            )
+            .as_type()
+    }
+
+    /// `{ length, <pointer> }`, the layout of strings and arrays
+    fn length_struct(&self, name: &str, pointer: &str, pointee: DIType<'ctx>) -> DIType<'ctx> {
+        let file = self.compile_unit.get_file();
+        let scope = file.as_debug_info_scope();
+
+        let length_type = self.basic_type("length", 64, DW_ATE_SIGNED);
+        let length = self.builder.create_member_type(
+            scope,
+            "length",
+            file,
+            0,
+            64,
+            64,
+            0,
+            DIFlags::ZERO,
+            length_type,
+        );
+        let pointer_type = self.pointer_type(pointer, pointee);
+        let pointer = self.builder.create_member_type(
+            scope,
+            pointer,
+            file,
+            0,
+            self.pointer_bits,
+            self.pointer_bits as u32,
+            64,
+            DIFlags::ZERO,
+            pointer_type,
+        );
+
+        self.builder
+            .create_struct_type(
+                scope,
+                name,
+                file,
+                0,
+                64 + self.pointer_bits,
+                64,
+                DIFlags::ZERO,
+                None,
+                &[length.as_type(), pointer.as_type()],
+                0,
+                None,
+                "",
+            )
+            .as_type()
+    }
+
+    /// Same layout as `ValidType::get_llvm_type`
+    pub fn r#type(&self, r#type: &ValidType) -> DIType<'ctx> {
+        match r#type {
+            ValidType::Number => self.basic_type("Number", 64, DW_ATE_FLOAT),
+            ValidType::Boolean => self.basic_type("Boolean", 8, DW_ATE_BOOLEAN),
+            // { length, data }
+            ValidType::String => {
+                let char = self.basic_type("char", 8, DW_ATE_SIGNED_CHAR);
+                self.length_struct("String", "data", char)
+            }
+            // { length, elements }
+            ValidType::Array(x) => {
+                self.length_struct(&r#type.to_string(), "elements", self.r#type(x))
+            }
+            ValidType::Infer(_) => bug!("UNRESOLVED_TYPE({:?})", r#type),
+        }
+    }
+
+    /// Attaches a subprogram to a function and returns its scope. Entries of modules don't have
+    /// a signature, they return an exit code
+    pub fn function(
+        &self,
+        fn_value: FunctionValue<'ctx>,
+        name: &str,
+        signature: Option<&Signature>,
+        span: Span,
+    ) -> DIScope<'ctx> {
+        let file = self.compile_unit.get_file();
+        let (ret, params) = match signature {
+            Some(signature) => (
+                signature.ret.as_ref().map(|x| self.r#type(x)),
+                signature
+                    .params
+                    .iter()
+                    .map(|x| self.r#type(x))
+                    .collect::<Vec<_>>(),
+            ),
+            None => (Some(self.basic_type("int", 32, DW_ATE_SIGNED)), vec![]),
+        };
+        let subroutine_type =
+            self.builder
+                .create_subroutine_type(file, ret, &params, DIFlags::ZERO);
+
+        let subprogram = self.builder.create_function(
+            self.compile_unit.as_debug_info_scope(),
+            name,
+            Some(fn_value.get_name().to_str().unwrap()),
+            file,
+            span.line as u32,
+            subroutine_type,
+            false,
+            true,
+            span.line as u32,
+            DIFlags::PUBLIC,
+            self.optimized,
+        );
+        fn_value.set_subprogram(subprogram);
+
+        subprogram.as_debug_info_scope()
+    }
+
+    /// Sets the location of the instructions built after it
+    pub fn set_location(&self, compiler: &Compiler<'ctx>, span: Span, scope: DIScope<'ctx>) {
+        let location = self.builder.create_debug_location(
+            compiler.context,
+            span.line as u32,
+            span.col as u32,
+            scope,
+            None,
+        );
+        compiler.builder.set_current_debug_location(location);
+    }
+
+    /// Scope of a nested block, it starts at `span`
+    pub fn lexical_block(&self, parent: DIScope<'ctx>, span: Span) -> DIScope<'ctx> {
+        self.builder
+            .create_lexical_block(
+                parent,
+                self.compile_unit.get_file(),
+                span.line as u32,
+                span.col as u32,
+            )
+            .as_debug_info_scope()
+    }
+
+    /// Describes the alloca of a local at the end of `block`, `arg_no` is the 1-based position of
+    /// a parameter
+    pub fn declare_local(
+        &self,
+        compiler: &Compiler<'ctx>,
+        ptr: PointerValue<'ctx>,
+        local: &Local,
+        arg_no: Option<u32>,
+        scope: DIScope<'ctx>,
+        block: BasicBlock<'ctx>,
+    ) {
+        let file = self.compile_unit.get_file();
+        let r#type = self.r#type(&local.r#type);
+        let line = local.span.line as u32;
+
+        let variable = match arg_no {
+            Some(arg_no) => self.builder.create_parameter_variable(
+                scope,
+                &local.name,
+                arg_no,
+                file,
+                line,
+                r#type,
+                true,
+                DIFlags::ZERO,
+            ),
+            None => self.builder.create_auto_variable(
+                scope,
+                &local.name,
+                file,
+                line,
+                r#type,
+                true,
+                DIFlags::ZERO,
+                0,
+            ),
+        };
+
+        let location = self.builder.create_debug_location(
+            compiler.context,
+            line,
+            local.span.col as u32,
+            scope,
+            None,
+        );
+        self.builder
+            .insert_declare_at_end(ptr, Some(variable), None, location, block);
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    ffi::{c_char, c_int, c_void},
+    io::{self, Write},
+    path::Path,
+    ptr, slice,
+};
+
+use colored::Colorize;
+use inkwell::{
+    execution_engine::ExecutionEngine, module::Module, support::load_library_permanently,
+    OptimizationLevel,
+};
+
+use crate::{project::Project, utils::Result};
+
+/// Runs the `main` of a program in this process and returns its exit code. runtime.c is compiled
+/// into walter by build.rs, so no C compiler or linker is needed
+pub fn run(module: &Module, project: &Project, opt: OptimizationLevel) -> Result<i32> {
+    load_libs(project);
+
+    let engine = create_engine(module, opt)?;
+    let code = unsafe { call(&engine, "main")? };
+    io::stdout().flush()?;
+
+    Ok(code)
+}
+
+/// Creates a JIT for a module, the runtime functions it uses are mapped to the runtime in walter
+pub fn create_engine<'ctx>(
+    module: &Module<'ctx>,
+    opt: OptimizationLevel,
+) -> Result<ExecutionEngine<'ctx>> {
+    let engine = module
+        .create_jit_execution_engine(opt)
+        .map_err(|x| format!("Could not create the JIT: {}", x))?;
+
+    for (name, address) in runtime_functions() {
+        if let Some(function) = module.get_function(name) {
+            engine.add_global_mapping(&function, address);
+        }
+    }
+
+    Ok(engine)
+}
+
+/// Calls a function that takes nothing and returns an `i32`, like `main`. What the runtime
+/// printed is flushed before it returns
+///
+/// # Safety
+/// The function has to have that signature
+pub unsafe fn call(engine: &ExecutionEngine, name: &str) -> Result<i32> {
+    let function = engine
+        .get_function::<unsafe extern "C" fn() -> i32>(name)
+        .map_err(|x| format!("Could not find {}: {}", name, x))?;
+    let code = function.call();
+    fflush(ptr::null_mut());
+    Ok(code)
+}
+
+/// The last bullet that was shot, None if it is `wat`
+pub fn current_bullet() -> Option<String> {
+    let mut bullet = RawString {
+        length: 0,
+        data: ptr::null(),
+    };
+    unsafe {
+        rl_bullet(&mut bullet);
+        bullet.as_str().map(str::to_string)
+    }
+}
+
+/// Formats a number like `nums`
+pub fn format_number(number: f64) -> String {
+    let mut text = RawString {
+        length: 0,
+        data: ptr::null(),
+    };
+    unsafe {
+        rl_nums(&mut text, number);
+        let string = text.as_str().unwrap_or_default().to_string();
+        free(text.data as *mut c_void);
+        string
+    }
+}
+
+/// Layout of a string, same as `ValidType::get_llvm_type`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct RawString {
+    pub length: i64,
+    /// Null terminated, null for `wat`
+    pub data: *const c_char,
+}
+
+impl RawString {
+    /// None if it is `wat`
+    ///
+    /// # Safety
+    /// `data` has to point to `length` bytes
+    pub unsafe fn as_str<'a>(&self) -> Option<&'a str> {
+        if self.data.is_null() {
+            return None;
+        }
+        let bytes = slice::from_raw_parts(self.data as *const u8, self.length as usize);
+        Some(std::str::from_utf8(bytes).unwrap_or("<invalid UTF-8>"))
+    }
+}
+
+/// Layout of an array, same as `ValidType::get_llvm_type`
+#[repr(C)]
+pub struct RawArray {
+    pub length: i64,
+    pub elements: *const u8,
+}
+
+/// Loads the `libs` of `walter.yml` into the process, so outsourced functions can be found
+fn load_libs(project: &Project) {
+    let file_name = |lib: &str| {
+        if cfg!(windows) {
+            format!("{}.dll", lib)
+        } else if cfg!(target_os = "macos") {
+            format!("lib{}.dylib", lib)
+        } else {
+            format!("lib{}.so", lib)
+        }
+    };
+
+    let project_dir = Path::new(&project.path);
+    for lib in &project.config.libs {
+        let file_name = file_name(lib);
+        let loaded = project
+            .config
+            .lib_paths
+            .iter()
+            .map(|x| project_dir.join(x).join(&file_name))
+            .filter(|x| x.exists())
+            .chain([file_name.clone().into()])
+            // Returns true if the library could not be loaded
+            .any(|x| !load_library_permanently(x.to_str().unwrap()));
+
+        // Libraries like libm are already loaded by walter, only a static library is missing
+        if !loaded {
+            log::warn!(
+                "Could not load {}, only shared libraries can be used with {}",
+                file_name.bold(),
+                "--jit".bold()
+            );
+        }
+    }
+}
+
+/// Addresses of the runtime functions, by symbol
+fn runtime_functions() -> [(&'static str, usize); 23] {
+    [
+        ("rl_alloc", rl_alloc as *const () as usize),
+        ("rl_try_enter", rl_try_enter as *const () as usize),
+        ("rl_try_exit", rl_try_exit as *const () as usize),
+        ("rl_throw", rl_throw as *const () as usize),
+        ("rl_bullet", rl_bullet as *const () as usize),
+        ("setjmp", setjmp as *const () as usize),
+        ("rl_concat", rl_concat as *const () as usize),
+        ("rl_compare", rl_compare as *const () as usize),
+        ("rl_from_c", rl_from_c as *const () as usize),
+        ("rl_length", rl_length as *const () as usize),
+        ("rl_substring", rl_substring as *const () as usize),
+        ("rl_split", rl_split as *const () as usize),
+        ("rl_contains", rl_contains as *const () as usize),
+        (
+            "rl_coitusinterruptus",
+            rl_coitusinterruptus as *const () as usize,
+        ),
+        ("rl_pulloutnt", rl_pulloutnt as *const () as usize),
+        ("rl_exit", rl_exit as *const () as usize),
+        ("rl_zzz", rl_zzz as *const () as usize),
+        ("rl_nums", rl_nums as *const () as usize),
+        ("rl_unnums", rl_unnums as *const () as usize),
+        ("rl_trace_number", rl_trace_number as *const () as usize),
+        ("rl_trace_boolean", rl_trace_boolean as *const () as usize),
+        ("rl_trace_string", rl_trace_string as *const () as usize),
+        ("rl_trace_array", rl_trace_array as *const () as usize),
+    ]
+}
+
+// runtime.c, only walter calls these from Rust. Bullets jump between frames of the program and
+// the runtime, never through Rust
+extern "C" {
+    fn free(ptr: *mut c_void);
+    fn fflush(stream: *mut c_void) -> c_int;
+    // `setjmp` is a macro of `_setjmp` on Windows
+    #[cfg_attr(windows, link_name = "_setjmp")]
+    fn setjmp(buffer: *mut c_void) -> c_int;
+
+    fn rl_alloc(size: i64) -> *mut c_void;
+    fn rl_try_enter() -> *mut c_void;
+    fn rl_try_exit();
+    fn rl_throw(bullet: *const RawString);
+    fn rl_bullet(out: *mut RawString);
+    fn rl_concat(out: *mut RawString, left: *const RawString, right: *const RawString);
+    fn rl_compare(left: *const RawString, right: *const RawString) -> i32;
+    fn rl_from_c(out: *mut RawString, text: *const c_char);
+    fn rl_length(text: *const RawString) -> f64;
+    fn rl_substring(out: *mut RawString, text: *const RawString, start: f64, count: f64);
+    fn rl_split(out: *mut RawArray, text: *const RawString, separator: *const RawString);
+    fn rl_contains(text: *const RawString, part: *const RawString) -> bool;
+    fn rl_coitusinterruptus(text: *const RawString);
+    fn rl_pulloutnt(out: *mut RawString);
+    fn rl_exit(code: f64);
+    fn rl_zzz(time_ms: f64);
+    fn rl_nums(out: *mut RawString, number: f64);
+    fn rl_unnums(text: *const RawString) -> f64;
+    fn rl_trace_number(location: *const c_char, name: *const c_char, value: f64);
+    fn rl_trace_boolean(location: *const c_char, name: *const c_char, value: bool);
+    fn rl_trace_string(location: *const c_char, name: *const c_char, value: *const RawString);
+    fn rl_trace_array(location: *const c_char, name: *const c_char, length: i64);
+}
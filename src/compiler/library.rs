@@ -0,0 +1,356 @@
+use std::{fmt::Write, fs, path::Path};
+
+use colored::Colorize;
+use inkwell::values::BasicMetadataValueEnum;
+
+use crate::{
+    bug,
+    parser::FunctionMod,
+    semantic::{Program, Signature, TypedModule, ValidType, MAIN_MODULE},
+    utils::Result,
+};
+
+use super::{declare_extern, declare_function, from_c_string, to_c_string, Compiler};
+
+/// Start of the symbols of the runtime and the standard library, ex. `rl_alloc`
+const RUNTIME_PREFIX: &str = "rl_";
+
+/// Keywords of C and C++ and the macros of `stdbool.h`, parameters named like them are left
+/// unnamed in the header
+const C_KEYWORDS: &[&str] = &[
+    "_Alignas",
+    "_Alignof",
+    "_Atomic",
+    "_Bool",
+    "_Complex",
+    "_Generic",
+    "_Imaginary",
+    "_Noreturn",
+    "_Static_assert",
+    "_Thread_local",
+    "alignas",
+    "alignof",
+    "and",
+    "and_eq",
+    "asm",
+    "auto",
+    "bitand",
+    "bitor",
+    "bool",
+    "break",
+    "case",
+    "catch",
+    "char",
+    "char16_t",
+    "char32_t",
+    "char8_t",
+    "class",
+    "co_await",
+    "co_return",
+    "co_yield",
+    "compl",
+    "concept",
+    "const",
+    "const_cast",
+    "consteval",
+    "constexpr",
+    "constinit",
+    "continue",
+    "decltype",
+    "default",
+    "delete",
+    "do",
+    "double",
+    "dynamic_cast",
+    "else",
+    "enum",
+    "explicit",
+    "export",
+    "extern",
+    "false",
+    "float",
+    "for",
+    "friend",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "mutable",
+    "namespace",
+    "new",
+    "noexcept",
+    "not",
+    "not_eq",
+    "nullptr",
+    "operator",
+    "or",
+    "or_eq",
+    "private",
+    "protected",
+    "public",
+    "register",
+    "reinterpret_cast",
+    "requires",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "static_assert",
+    "static_cast",
+    "struct",
+    "switch",
+    "template",
+    "this",
+    "thread_local",
+    "throw",
+    "true",
+    "try",
+    "typedef",
+    "typeid",
+    "typename",
+    "typeof",
+    "typeof_unqual",
+    "union",
+    "unsigned",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+    "wchar_t",
+    "while",
+    "xor",
+    "xor_eq",
+];
+
+/// A `bar` function that is callable from C
+pub struct Export {
+    /// Stable C name, see `export_symbol`
+    pub name: String,
+    /// Module of the function
+    pub module: String,
+    /// Name of the function in its module
+    pub function: String,
+    /// Symbol of the function that is called
+    pub symbol: String,
+    /// Symbol of the entry of the module of the function, it runs before the function
+    pub entry: String,
+    pub param_names: Vec<String>,
+    pub signature: Signature,
+}
+
+/// Replaces `main` as the entry of the main module of a library
+pub fn init_symbol(project_name: &str) -> String {
+    format!("{}_init", c_identifier(project_name))
+}
+
+/// `<project>_<function>` in the main module and `<project>_<module>_<function>` in others,
+/// ex. `mylib_utils_add` for `add` in `utils.rl` of `mylib`
+pub fn export_symbol(project_name: &str, module: &str, function: &str) -> String {
+    if module == MAIN_MODULE {
+        format!("{}_{}", c_identifier(project_name), function)
+    } else {
+        format!(
+            "{}_{}_{}",
+            c_identifier(project_name),
+            c_identifier(module),
+            function
+        )
+    }
+}
+
+/// Replaces every character that can't be in a C identifier with `_`
+fn c_identifier(name: &str) -> String {
+    name.chars()
+        .map(|x| if x.is_ascii_alphanumeric() { x } else { '_' })
+        .collect()
+}
+
+/// Every `bar` function of a module, except functions using arrays which have no C type
+fn module_exports(project_name: &str, module: &TypedModule) -> Vec<Export> {
+    module
+        .functions
+        .iter()
+        .filter(|x| x.modifiers.iter().any(|x| matches!(x, FunctionMod::Public)))
+        .filter(|function| {
+            let signature = &function.signature;
+            let has_array = signature
+                .params
+                .iter()
+                .chain(&signature.ret)
+                .any(|x| matches!(x, ValidType::Array(_)));
+            if has_array {
+                log::warn!(
+                    "{} is not exported, arrays can't be passed to or returned from C",
+                    function.name
+                );
+            }
+            !has_array
+        })
+        .filter(|function| {
+            let name = export_symbol(project_name, &module.name, &function.name);
+            let is_init = name == init_symbol(project_name);
+            if is_init {
+                log::warn!(
+                    "{} is not exported, {} is the init function of the library",
+                    function.name,
+                    name
+                );
+            }
+            !is_init
+        })
+        .map(|function| Export {
+            name: export_symbol(project_name, &module.name, &function.name),
+            module: module.name.clone(),
+            function: function.name.clone(),
+            symbol: function.symbol.clone(),
+            entry: module.entry.clone(),
+            param_names: function
+                .params
+                .iter()
+                .map(|x| function.locals[x.0].name.clone())
+                .collect(),
+            signature: function.signature.clone(),
+        })
+        .collect()
+}
+
+/// Exports of every module, the entries of the modules have to be final. Errors if two exports
+/// have the same C name, or if the names can be names of the runtime
+pub fn exports(project_name: &str, program: &Program) -> Result<Vec<Export>> {
+    // Every C name starts like the init function
+    let init = init_symbol(project_name);
+    if init.starts_with(RUNTIME_PREFIX) {
+        return Err(format!(
+            "A library can't be named {}, its C names would start with {} like the functions of \
+             the runtime",
+            project_name.bold(),
+            RUNTIME_PREFIX.bold()
+        )
+        .into());
+    }
+
+    let exports = program
+        .modules
+        .iter()
+        .flat_map(|x| module_exports(project_name, x))
+        .collect::<Vec<_>>();
+    for (i, export) in exports.iter().enumerate() {
+        if let Some(other) = exports[..i].iter().find(|x| x.name == export.name) {
+            return Err(format!(
+                "{} of {} and {} of {} are both exported as {}, rename one of them",
+                other.function.bold(),
+                other.module.bold(),
+                export.function.bold(),
+                export.module.bold(),
+                export.name.bold()
+            )
+            .into());
+        }
+    }
+    Ok(exports)
+}
+
+/// Defines the C functions of the exports of a module. They run the entry of the module, which
+/// only runs once, and then call the function
+pub fn compile_exports<'a>(compiler: &Compiler<'a>, module: &TypedModule, exports: &[Export]) {
+    // The wrappers have no debug info
+    compiler.builder.unset_current_debug_location();
+
+    for export in exports.iter().filter(|x| x.module == module.name) {
+        let wrapper = declare_extern(compiler, &export.name, &export.signature);
+        let block = compiler.context.append_basic_block(wrapper, "");
+        compiler.builder.position_at_end(block);
+
+        let entry = compiler
+            .module
+            .get_function(&export.entry)
+            .unwrap_or_else(|| bug!("UNDECLARED_ENTRY({})", export.entry));
+        compiler.builder.build_call(entry, &[], "");
+
+        let function = declare_function(compiler, &export.symbol, &export.signature);
+        // C strings are converted to strings and back
+        let args = wrapper
+            .get_param_iter()
+            .zip(&export.signature.params)
+            .map(|(x, r#type)| match r#type {
+                ValidType::String => from_c_string(compiler, x).into(),
+                _ => x.into(),
+            })
+            .collect::<Vec<BasicMetadataValueEnum>>();
+        let value = compiler
+            .builder
+            .build_call(function, &args, "return")
+            .try_as_basic_value()
+            .left()
+            .map(|x| match export.signature.ret {
+                Some(ValidType::String) => to_c_string(compiler, x),
+                _ => x,
+            });
+
+        match value {
+            Some(x) => compiler.builder.build_return(Some(&x)),
+            None => compiler.builder.build_return(None),
+        };
+    }
+}
+
+/// Writes a C header declaring the init function and every export
+pub fn write_header(project_name: &str, exports: &[Export], path: &Path) -> Result<()> {
+    let guard = format!("{}_H", c_identifier(project_name).to_uppercase());
+    let mut header = String::new();
+
+    writeln!(header, "/* Generated by walter */")?;
+    writeln!(header, "#ifndef {}", guard)?;
+    writeln!(header, "#define {}\n", guard)?;
+    writeln!(header, "#include <stdbool.h>\n")?;
+    writeln!(header, "#ifdef __cplusplus\nextern \"C\" {{\n#endif\n")?;
+
+    writeln!(
+        header,
+        "/*\n * A bullet that is not caught in RedditLang prints \"Uncaught bullet: ...\" and exits\n \
+         * the whole process with code 1. Catch bullets with test-wall in the exported functions\n \
+         * if the host has to keep running.\n */\n"
+    )?;
+    writeln!(
+        header,
+        "/* Runs src/main.rl once, exports of src/main.rl run it before themselves */"
+    )?;
+    writeln!(header, "int {}(void);\n", init_symbol(project_name))?;
+
+    for export in exports {
+        let params = if export.param_names.is_empty() {
+            "void".to_string()
+        } else {
+            export
+                .param_names
+                .iter()
+                .zip(&export.signature.params)
+                .map(|(name, r#type)| match C_KEYWORDS.contains(&name.as_str()) {
+                    true => c_type(r#type).to_string(),
+                    false => format!("{} {}", c_type(r#type), name),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let ret = export.signature.ret.as_ref().map_or("void", c_type);
+        writeln!(header, "{} {}({});", ret, export.name, params)?;
+    }
+
+    writeln!(header, "\n#ifdef __cplusplus\n}}\n#endif\n")?;
+    writeln!(header, "#endif")?;
+
+    fs::write(path, header)?;
+    Ok(())
+}
+
+fn c_type(r#type: &ValidType) -> &'static str {
+    match r#type {
+        ValidType::Number => "double",
+        ValidType::Boolean => "bool",
+        ValidType::String => "const char *",
+        _ => bug!("NO_C_TYPE({:?})", r#type),
+    }
+}
@@ -1,50 +1,185 @@
 use std::{
     fs,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::{Command, ExitStatus},
 };
 
-use inkwell::{targets::TargetTriple, AddressSpace};
-use log::info;
+use colored::Colorize;
+use inkwell::targets::{TargetMachine, TargetTriple};
+use log::{info, warn};
 
-use crate::{compiler::Compiler, git::update, project::Project};
+use crate::{
+    compiler::{
+        runtime::{write_js_runtime, write_runtime},
+        target::BuildTarget,
+    },
+    git::checkout_rev,
+    project::{OptLevel, Project},
+    utils::Result,
+};
 
 const STDLIB_URL: &str = "https://github.com/elijah629/redditlang-std";
+/// The tag of the standard library released with this version of walter
+const STDLIB_DEFAULT_REV: &str = concat!("v", env!("CARGO_PKG_VERSION"));
+
+/// Builds the external standard library configured in `walter.yml` and returns a path to it.
+/// Offline builds only use the cached copy or the local path
+pub fn build_libstd(project: &Project, offline: bool) -> Result<PathBuf> {
+    let config = project.config.std.as_ref();
+    if let Some(path) = config.and_then(|x| x.path.as_ref()) {
+        return build_local_libstd(&Path::new(&project.path).join(path), offline);
+    }
+    let rev = config
+        .and_then(|x| x.rev.as_deref())
+        .unwrap_or(STDLIB_DEFAULT_REV);
 
-/// Builds libstd and returns a path to it
-pub fn build_libstd() -> Result<PathBuf, Box<dyn std::error::Error>> {
-    let walter_dir = dirs::home_dir().unwrap().join(".walter");
+    let walter_dir = dirs::home_dir()
+        .ok_or("Could not find the home directory")?
+        .join(".walter");
     let std_dir = walter_dir.join("stdlib");
+    let cached = std_dir.join("libstd.a");
+    // Rev and commit the cached libstd.a was built from
+    let cached_rev_path = std_dir.join("libstd.a.rev");
+    let cached_rev = fs::read_to_string(&cached_rev_path).ok();
+    let cached_rev = cached_rev.as_deref().and_then(|x| x.split_once('\n'));
+
+    if offline {
+        if cached.try_exists()? {
+            check_cached_rev(cached_rev, rev)
+                .map_err(|x| format!("{}, build once without {}", x, "--offline".bold()))?;
+            return Ok(cached);
+        }
+        return Err(format!(
+            "No cached libstd at {}, build once without {} or set {} in {}",
+            cached.display(),
+            "--offline".bold(),
+            "std.path".bold(),
+            "walter.yml".bold()
+        )
+        .into());
+    }
 
     fs::create_dir_all(&walter_dir)?;
 
-    // Ensure libstd is up to date, should just not do this. ie check for new commits
-    let up_to_date = update(STDLIB_URL, &std_dir, "main").expect("Failed to update libstd repo");
+    let commit = match checkout_rev(STDLIB_URL, &std_dir, rev) {
+        Ok(x) => x.to_string(),
+        Err(x) if cached.try_exists()? => {
+            check_cached_rev(cached_rev, rev)
+                .map_err(|error| format!("Could not download libstd: {}\n{}", x, error))?;
+            warn!("Could not update libstd, using the cached copy: {}", x);
+            return Ok(cached);
+        }
+        Err(x) => return Err(format!("Could not download libstd: {}", x).into()),
+    };
 
-    if up_to_date {
-        return Ok(std_dir.join("libstd.a"));
+    if cached.try_exists()? && cached_rev.is_some_and(|(_, x)| x == commit) {
+        return Ok(cached);
     }
 
-    info!("Updating libstd");
+    info!("Building libstd {}", rev.bold());
 
-    Command::new("cargo")
-        .arg("build")
-        .arg("--release")
-        .current_dir(&std_dir)
-        .output()?;
+    cargo_build(&std_dir, false)?;
 
     // TODO: Windows
-    fs::rename(
-        &std_dir.join("target/release/libstd.a"),
-        &std_dir.join("libstd.a"),
-    )?;
+    fs::rename(std_dir.join("target/release/libstd.a"), &cached)?;
+    fs::write(&cached_rev_path, format!("{}\n{}", rev, commit))?;
 
     Command::new("cargo")
         .arg("clean")
         .current_dir(&std_dir)
         .output()?;
 
-    Ok(std_dir.join("libstd.a"))
+    Ok(cached)
+}
+
+/// Errors if the cached libstd was not built from `rev`, it is used when `rev` can't be fetched
+fn check_cached_rev(cached_rev: Option<(&str, &str)>, rev: &str) -> Result<()> {
+    match cached_rev {
+        Some((cached, commit)) if cached == rev || commit == rev => Ok(()),
+        Some((cached, commit)) => Err(format!(
+            "The cached libstd is {} ({}), not {}",
+            cached.bold(),
+            commit,
+            rev.bold()
+        )
+        .into()),
+        None => Err(format!(
+            "The cached libstd is an unknown revision, not {}",
+            rev.bold()
+        )
+        .into()),
+    }
+}
+
+/// `path` is either a built libstd or a checkout of it
+fn build_local_libstd(path: &Path, offline: bool) -> Result<PathBuf> {
+    if path.is_file() {
+        return Ok(path.to_path_buf());
+    }
+    if !path.is_dir() {
+        return Err(format!("libstd path {} does not exist", path.display()).into());
+    }
+
+    info!("Building libstd at {}", path.display().to_string().bold());
+
+    cargo_build(path, offline)?;
+    Ok(path.join("target/release/libstd.a"))
+}
+
+fn cargo_build(dir: &Path, offline: bool) -> Result<()> {
+    let mut command = Command::new("cargo");
+    command.arg("build").arg("--release").current_dir(dir);
+    if offline {
+        command.arg("--offline");
+    }
+
+    let output = command.output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Failed to build libstd\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        )
+        .into())
+    }
+}
+
+/// C compiler for the target, it compiles the runtime and links. Cross compilers are found by
+/// the prefix of the triple, ex. `aarch64-linux-gnu-gcc`
+fn c_compiler(target_triple: &TargetTriple, build_dir: &PathBuf, opt_level: OptLevel) -> cc::Build {
+    let target_str = target_triple.as_str().to_str().unwrap();
+    let host_triple = TargetMachine::get_default_triple();
+
+    let mut build = cc::Build::new();
+    build
+        .target(target_str)
+        .out_dir(build_dir)
+        .opt_level_str(opt_level.as_str())
+        .host(host_triple.as_str().to_str().unwrap())
+        .cargo_metadata(false);
+    build
+}
+
+/// Adds `-L` and `-l` for the C libraries of `walter.yml`, they come after the objects that use them
+fn add_libs(command: &mut Command, project: &Project) {
+    let project_dir = Path::new(&project.path);
+    for path in &project.config.lib_paths {
+        command.arg(format!("-L{}", project_dir.join(path).display()));
+    }
+    for lib in &project.config.libs {
+        command.arg(format!("-l{}", lib));
+    }
+}
+
+fn run(mut command: Command) -> Result<()> {
+    let output = command.output()?;
+
+    if ExitStatus::success(&output.status) {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).into_owned().into())
+    }
 }
 
 pub fn link(
@@ -52,30 +187,24 @@ pub fn link(
     target_triple: &TargetTriple,
     build_dir: &PathBuf,
     object_path: &PathBuf,
-    std_path: &PathBuf,
-    release: bool,
-    no_std: bool,
-    strip: bool
-) -> Result<PathBuf, Box<dyn std::error::Error>> {
-    let target_str = target_triple.as_str().to_str().unwrap();
-
-    let compiler = cc::Build::new()
-        .target(&target_str)
-        .out_dir(&build_dir)
-        .opt_level(if release { 3 } else { 0 })
-        .host(&target_str)
-        .cargo_metadata(false)
-        .get_compiler();
+    std_path: Option<&PathBuf>,
+    opt_level: OptLevel,
+    strip: bool,
+) -> Result<PathBuf> {
+    let compiler = c_compiler(target_triple, build_dir, opt_level).get_compiler();
 
     let output_file = build_dir.join(&project.config.name);
 
     let mut command = compiler.to_command();
-    command.arg(&object_path);
+    command.arg(object_path);
+    command.arg(write_runtime(build_dir)?);
 
-    if !no_std {
-        command.arg(&std_path);
+    if let Some(std_path) = std_path {
+        command.arg(std_path);
     }
 
+    add_libs(&mut command, project);
+
     if strip {
         command.arg("-s");
     }
@@ -83,36 +212,128 @@ pub fn link(
     command.arg("-o");
     command.arg(&output_file);
 
-    let output = command.output().unwrap();
+    run(command)?;
+    Ok(output_file)
+}
+
+/// Links a `.wasm` module with clang. `wasm32-wasi` modules include the runtime, others import
+/// it from the JavaScript runtime, which is written next to the module
+pub fn link_wasm(
+    project: &Project,
+    target: &BuildTarget,
+    build_dir: &PathBuf,
+    object_path: &PathBuf,
+    opt_level: OptLevel,
+    strip: bool,
+) -> Result<PathBuf> {
+    let compiler = c_compiler(&target.triple, build_dir, opt_level).get_compiler();
+
+    let output_file = build_dir.join(format!("{}.wasm", project.config.name));
 
-    if ExitStatus::success(&output.status) {
-        Ok(output_file)
+    let mut command = compiler.to_command();
+    command.arg(object_path);
+
+    if target.is_wasi() {
+        command.arg(write_runtime(build_dir)?);
     } else {
-        Err(String::from_utf8(output.stderr).unwrap().into())
+        write_js_runtime(build_dir)?;
+        command.args([
+            "-nostdlib",
+            "-Wl,--no-entry",
+            "-Wl,--export=main",
+            "-Wl,--export=__heap_base",
+            "-Wl,--allow-undefined",
+        ]);
     }
+
+    add_libs(&mut command, project);
+
+    if strip {
+        command.arg("-Wl,--strip-all");
+    }
+
+    command.arg("-o");
+    command.arg(&output_file);
+
+    run(command)?;
+    Ok(output_file)
 }
 
-pub fn define_libstd(compiler: &Compiler) {
-    let println_type = compiler.context.void_type().fn_type(
-        &[compiler
-            .context
-            .i8_type()
-            .ptr_type(AddressSpace::default())
-            .into()],
-        false,
-    );
-
-    let nums_type = compiler
-        .context
-        .i8_type()
-        .ptr_type(AddressSpace::default())
-        .fn_type(&[compiler.context.f64_type().into()], false);
-
-    let exit_type = compiler.context.void_type().fn_type(&[compiler.context.f64_type().into()], false);
-    
-    compiler
-        .module
-        .add_function("coitusinterruptus", println_type, None);
-    compiler.module.add_function("nums", nums_type, None);
-    compiler.module.add_function("exit", exit_type, None);
+/// Builds `lib<name>.a` and `lib<name>.so` (`.dylib` on Apple targets) from the object of a
+/// library project, both include the runtime
+pub fn link_library(
+    project: &Project,
+    target_triple: &TargetTriple,
+    build_dir: &PathBuf,
+    object_path: &PathBuf,
+    std_path: Option<&PathBuf>,
+    opt_level: OptLevel,
+    strip: bool,
+) -> Result<Vec<PathBuf>> {
+    let mut build = c_compiler(target_triple, build_dir, opt_level);
+    build.pic(true);
+    let compiler = build.get_compiler();
+
+    let runtime_object = build_dir.join("runtime.o");
+    let mut command = compiler.to_command();
+    command
+        .arg("-c")
+        .arg(write_runtime(build_dir)?)
+        .arg("-o")
+        .arg(&runtime_object);
+    run(command)?;
+
+    let name = &project.config.name;
+    let static_lib = build_dir.join(format!("lib{}.a", name));
+    // `ar` appends to an existing archive
+    if static_lib.try_exists()? {
+        fs::remove_file(&static_lib)?;
+    }
+
+    let mut command = build.get_archiver();
+    command
+        .arg("crs")
+        .arg(&static_lib)
+        .arg(object_path)
+        .arg(&runtime_object);
+    run(command)?;
+
+    if std_path.is_some() {
+        warn!(
+            "libstd is not included in {}, link it after the library",
+            static_lib.file_name().unwrap().to_str().unwrap().bold()
+        );
+    }
+
+    let target_str = target_triple.as_str().to_str().unwrap();
+    let shared_lib = build_dir.join(format!(
+        "lib{}.{}",
+        name,
+        if target_str.contains("apple") {
+            "dylib"
+        } else {
+            "so"
+        }
+    ));
+
+    let mut command = compiler.to_command();
+    command.arg("-shared");
+    command.arg(object_path);
+    command.arg(&runtime_object);
+
+    if let Some(std_path) = std_path {
+        command.arg(std_path);
+    }
+
+    add_libs(&mut command, project);
+
+    if strip {
+        command.arg("-s");
+    }
+
+    command.arg("-o");
+    command.arg(&shared_lib);
+
+    run(command)?;
+    Ok(vec![static_lib, shared_lib])
 }
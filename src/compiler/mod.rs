@@ -1,89 +1,510 @@
-use std::collections::HashMap;
-
-use self::compile_node::{Compile, ValidType};
+use self::{compile_node::Compile, debug_info::DebugInfo};
 use crate::{
     bug,
-    parser::{Node, Tree},
+    parser::{FunctionMod, Span},
+    semantic::{
+        Local, LocalId, Signature, TypedFunction, TypedModule, TypedNode, TypedTree, ValidType,
+        VariableRef, MAIN_MODULE,
+    },
     utils::Result,
 };
 use inkwell::{
+    attributes::{Attribute, AttributeLoc},
     basic_block::BasicBlock,
     builder::Builder,
     context::Context,
+    debug_info::DIScope,
     module::Module,
-    values::{FunctionValue, PointerValue},
+    types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum},
+    values::{BasicValueEnum, FunctionValue, PointerValue},
+    AddressSpace,
 };
 
 pub mod compile_node;
+pub mod debug_info;
+pub mod jit;
+pub mod library;
 pub mod linking;
+pub mod optimize;
+pub mod runtime;
+pub mod target;
 
 pub struct Compiler<'ctx> {
     pub context: &'ctx Context,
     pub builder: &'ctx Builder<'ctx>,
     pub module: Module<'ctx>,
-}
-
-//#[derive(Clone)]
-pub struct ScopeVariable<'a> {
-    pub ptr: PointerValue<'a>,
-    pub r#type: ValidType,
-}
-
-//#[derive(Clone)]
-pub struct Scope<'a> {
-    pub variables: HashMap<String, ScopeVariable<'a>>,
+    /// If the project is a library, the entry of the main module is then ran by its exports
+    pub library: bool,
+    /// None if the build has no debug info
+    pub debug_info: Option<DebugInfo<'ctx>>,
+    /// If `debug` functions print their variables, only debug builds trace them
+    pub trace: bool,
 }
 
 pub struct LoopMetadata<'a> {
     exit_block: BasicBlock<'a>,
     loop_block: BasicBlock<'a>,
+    /// `CompileMetadata::tries` outside of the loop
+    tries: usize,
 }
 
 pub struct CompileMetadata<'a> {
     pub r#loop: Option<LoopMetadata<'a>>,
-    pub function_scope: Scope<'a>,
+    /// Allocas of the locals of the current function, indexed by `LocalId`
+    pub locals: Vec<PointerValue<'a>>,
+    /// Locals of the current function, indexed by `LocalId`. The debug info declares them where
+    /// they are declared in the source, in the scope of their block
+    pub declarations: Vec<Local>,
+    /// Locals assigned in a `test`, indexed by `LocalId`. See `assigned_in_tries`
+    pub volatile: Vec<bool>,
     pub fn_value: FunctionValue<'a>,
+    /// If the current function is the entry of a module, which returns an exit code
+    pub entry: bool,
+    /// Number of `try` blocks around the current statement, they are exited before `spez`
+    pub tries: usize,
+    /// Debug info scope of the current function
+    pub scope: Option<DIScope<'a>>,
+    /// Set if the current function is a traced `debug` function
+    pub trace: Option<Trace>,
+    /// Span of the current statement
+    pub span: Span,
 }
 
-pub fn compile<'a>(
+/// What a `debug` function prints when one of its variables changes
+pub struct Trace {
+    /// File name of the module, printed with the line of the statement
+    pub file: String,
+    /// Names of the locals of the function, indexed by `LocalId`
+    pub locals: Vec<String>,
+}
+
+/// Compiles the globals, functions and top level statements of a module
+pub fn compile_module<'a>(compiler: &Compiler<'a>, module: &TypedModule) -> Result<()> {
+    for global in &module.globals {
+        let r#type = global.r#type.get_llvm_type(compiler);
+        let value = compiler.module.add_global(r#type, None, &global.symbol);
+        value.set_initializer(&r#type.const_zero());
+    }
+
+    // Declared first so functions can call functions defined after them
+    for function in &module.functions {
+        declare_function(compiler, &function.symbol, &function.signature);
+    }
+
+    for function in &module.functions {
+        compile_function(compiler, module, function)?;
+    }
+
+    compile_entry(compiler, module)
+}
+
+fn compile_function<'a>(
     compiler: &Compiler<'a>,
-    tree: &Tree,
-    compile_meta: &mut CompileMetadata<'a>,
+    module: &TypedModule,
+    function: &TypedFunction,
 ) -> Result<()> {
-    for node in tree {
-        // these cannot be compiled
-        if !matches!(node, Node::EOI | Node::Import(..)) {
-            compile_one(&compiler, &node, compile_meta)?;
-        }
+    let fn_value = compiler
+        .module
+        .get_function(&function.symbol)
+        .unwrap_or_else(|| bug!("UNDECLARED_FUNCTION({})", function.symbol));
+
+    let entry_basic_block = compiler.context.append_basic_block(fn_value, "");
+    compiler.builder.position_at_end(entry_basic_block);
+
+    let scope = compiler.debug_info.as_ref().map(|debug_info| {
+        let scope = debug_info.function(
+            fn_value,
+            &function.name,
+            Some(&function.signature),
+            function.span,
+        );
+        debug_info.set_location(compiler, function.span, scope);
+        scope
+    });
+
+    let locals = allocate_locals(compiler, &function.locals);
+    for (param, value) in function.params.iter().zip(fn_value.get_param_iter()) {
+        compiler.builder.build_store(locals[param.0], value);
     }
+    if let Some(scope) = scope {
+        declare_params(compiler, &locals, &function.locals, &function.params, scope);
+    }
+
+    let is_debug = function
+        .modifiers
+        .iter()
+        .any(|x| matches!(x, FunctionMod::Debug));
+    let trace = (compiler.trace && is_debug).then(|| Trace {
+        file: module
+            .path
+            .file_name()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        locals: function.locals.iter().map(|x| x.name.clone()).collect(),
+    });
+
+    compile(
+        compiler,
+        &function.body,
+        &mut CompileMetadata {
+            r#loop: None,
+            locals,
+            declarations: function.locals.clone(),
+            volatile: assigned_in_tries(&function.body, function.locals.len()),
+            fn_value,
+            entry: false,
+            tries: 0,
+            scope,
+            trace,
+            span: function.span,
+        },
+    )?;
+
+    // Falling off the end of a function returns `wat`
+    if !is_terminated(compiler) {
+        match &function.signature.ret {
+            Some(x) => compiler
+                .builder
+                .build_return(Some(&x.get_llvm_type(compiler).const_zero())),
+            None => compiler.builder.build_return(None),
+        };
+    }
+
     Ok(())
 }
 
-pub fn compile_one<'a>(
+fn compile_entry<'a>(compiler: &Compiler<'a>, module: &TypedModule) -> Result<()> {
+    let i32_type = compiler.context.i32_type();
+    let main_type = i32_type.fn_type(&[], false);
+    let main_fn = compiler.module.add_function(&module.entry, main_type, None);
+
+    let entry_basic_block = compiler.context.append_basic_block(main_fn, "");
+    compiler.builder.position_at_end(entry_basic_block);
+
+    // The top level statements start at the top of the file
+    let span = Span {
+        line: 1,
+        col: 1,
+        ..Default::default()
+    };
+    let scope = compiler.debug_info.as_ref().map(|debug_info| {
+        let scope = debug_info.function(main_fn, &module.entry, None, span);
+        debug_info.set_location(compiler, span, scope);
+        scope
+    });
+
+    let locals = allocate_locals(compiler, &module.locals);
+
+    // Every import runs the entry of the imported module, but it should only run once
+    if module.name != MAIN_MODULE || compiler.library {
+        let bool_type = compiler.context.bool_type();
+        let initialized =
+            compiler
+                .module
+                .add_global(bool_type, None, &format!("{}.initialized", module.name));
+        initialized.set_initializer(&bool_type.const_zero());
+
+        let init_block = compiler.context.append_basic_block(main_fn, "init");
+        let done_block = compiler.context.append_basic_block(main_fn, "initialized");
+
+        let is_initialized = compiler
+            .builder
+            .build_load(bool_type, initialized.as_pointer_value(), "initialized")
+            .into_int_value();
+        compiler
+            .builder
+            .build_conditional_branch(is_initialized, done_block, init_block);
+
+        compiler.builder.position_at_end(done_block);
+        compiler.builder.build_return(Some(&i32_type.const_zero()));
+
+        compiler.builder.position_at_end(init_block);
+        compiler
+            .builder
+            .build_store(initialized.as_pointer_value(), bool_type.const_all_ones());
+    }
+
+    compile(
+        compiler,
+        &module.body,
+        &mut CompileMetadata {
+            r#loop: None,
+            locals,
+            declarations: module.locals.clone(),
+            volatile: assigned_in_tries(&module.body, module.locals.len()),
+            fn_value: main_fn,
+            entry: true,
+            tries: 0,
+            scope,
+            trace: None,
+            span,
+        },
+    )?;
+
+    // Add return
+    if !is_terminated(compiler) {
+        compiler.builder.build_return(Some(&i32_type.const_zero()));
+    }
+
+    Ok(())
+}
+
+/// Gets a function from the current module, declaring it if it is defined in another module
+pub fn declare_function<'a>(
     compiler: &Compiler<'a>,
-    node: &Node,
-    compile_meta: &mut CompileMetadata<'a>,
-) -> Result<()> {
-    let impl_compile: &dyn Compile<'a> = match node {
-        Node::EOI => unreachable!(), // EOI is skipped above
-        Node::Expr(_) => bug!("Expected statement, got an expression, COMPILE_EXPRESSION"),
+    symbol: &str,
+    signature: &Signature,
+) -> FunctionValue<'a> {
+    if let Some(function) = compiler.module.get_function(symbol) {
+        return function;
+    }
 
-        Node::Import(_) => unreachable!(), // import is a compiler directive
+    let params = signature
+        .params
+        .iter()
+        .map(|x| x.get_llvm_type(compiler).into())
+        .collect::<Vec<BasicMetadataTypeEnum>>();
+    let fn_type = match &signature.ret {
+        Some(x) => x.get_llvm_type(compiler).fn_type(&params, false),
+        None => compiler.context.void_type().fn_type(&params, false),
+    };
 
-        Node::Variable(x) => x,
-        Node::Assignment(x) => x,
+    compiler.module.add_function(symbol, fn_type, None)
+}
 
-        Node::Loop(x) => x,
-        Node::Break(x) => x,
+/// Declares a C function, booleans are passed as a C `bool` and strings as a `const char *`
+pub fn declare_extern<'a>(
+    compiler: &Compiler<'a>,
+    symbol: &str,
+    signature: &Signature,
+) -> FunctionValue<'a> {
+    if let Some(function) = compiler.module.get_function(symbol) {
+        return function;
+    }
 
-        Node::Function(_) => todo!(),
-        Node::Call(call) => todo!(),
-        Node::Throw(_) => todo!(),
-        Node::TryCatch(_) => todo!(),
-        Node::If(r#if) => todo!(),
-        Node::Class(_) => todo!(),
-        Node::Return(_) => todo!(),
+    let params = signature
+        .params
+        .iter()
+        .map(|x| extern_type(compiler, x).into())
+        .collect::<Vec<BasicMetadataTypeEnum>>();
+    let fn_type = match &signature.ret {
+        Some(x) => extern_type(compiler, x).fn_type(&params, false),
+        None => compiler.context.void_type().fn_type(&params, false),
     };
+    let function = compiler.module.add_function(symbol, fn_type, None);
+    let zeroext = enum_attribute(compiler, "zeroext");
+    for (i, param) in signature.params.iter().enumerate() {
+        if *param == ValidType::Boolean {
+            function.add_attribute(AttributeLoc::Param(i as u32), zeroext);
+        }
+    }
+    if signature.ret == Some(ValidType::Boolean) {
+        function.add_attribute(AttributeLoc::Return, zeroext);
+    }
+
+    function
+}
 
-    impl_compile.compile(compiler, compile_meta)
+/// Type of a value passed to C
+pub fn extern_type<'a>(compiler: &Compiler<'a>, r#type: &ValidType) -> BasicTypeEnum<'a> {
+    match r#type {
+        ValidType::String => compiler
+            .context
+            .i8_type()
+            .ptr_type(AddressSpace::default())
+            .into(),
+        _ => r#type.get_llvm_type(compiler),
+    }
+}
+
+/// The null terminated data of a string, null for `wat`
+pub fn to_c_string<'a>(compiler: &Compiler<'a>, value: BasicValueEnum<'a>) -> BasicValueEnum<'a> {
+    compiler
+        .builder
+        .build_extract_value(value.into_struct_value(), 1, "data")
+        .unwrap_or_else(|| bug!("STRING_WITHOUT_DATA"))
+}
+
+/// A string of a null terminated C string, null is `wat`
+pub fn from_c_string<'a>(compiler: &Compiler<'a>, value: BasicValueEnum<'a>) -> BasicValueEnum<'a> {
+    runtime::call_runtime_out(compiler, "rl_from_c", &ValidType::String, &[value.into()])
+}
+
+pub fn enum_attribute(compiler: &Compiler, name: &str) -> Attribute {
+    compiler
+        .context
+        .create_enum_attribute(Attribute::get_named_enum_kind_id(name), 0)
+}
+
+/// Which locals are assigned in a `test`, indexed by `LocalId`. A bullet `longjmp`s back to the
+/// `setjmp` of the `test`, which restores the registers it saved, so a local kept in a register
+/// would lose what the `test` assigned. Their loads and stores are volatile, which keeps them in
+/// memory
+fn assigned_in_tries(body: &TypedTree, locals: usize) -> Vec<bool> {
+    fn visit(tree: &TypedTree, in_try: bool, assigned: &mut [bool]) {
+        for node in tree {
+            match &node.node {
+                TypedNode::Variable(VariableRef::Local(id), _)
+                | TypedNode::Assignment(VariableRef::Local(id), _) => assigned[id.0] |= in_try,
+                TypedNode::Loop(body) => visit(body, in_try, assigned),
+                TypedNode::If(cases, r#else) => {
+                    for (_, body) in cases {
+                        visit(body, in_try, assigned);
+                    }
+                    if let Some(body) = r#else {
+                        visit(body, in_try, assigned);
+                    }
+                }
+                TypedNode::TryCatch(r#try, _, catch) => {
+                    visit(r#try, true, assigned);
+                    visit(catch, in_try, assigned);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut assigned = vec![false; locals];
+    visit(body, false, &mut assigned);
+    assigned
+}
+
+/// Allocates at the start of the current function, so allocas in loops don't grow the stack
+pub fn entry_alloca<'a>(
+    compiler: &Compiler<'a>,
+    r#type: BasicTypeEnum<'a>,
+    name: &str,
+) -> PointerValue<'a> {
+    let entry = compiler
+        .builder
+        .get_insert_block()
+        .and_then(|x| x.get_parent())
+        .and_then(|x| x.get_first_basic_block())
+        .unwrap_or_else(|| bug!("NO_INSERT_BLOCK"));
+
+    let builder = compiler.context.create_builder();
+    match entry.get_first_instruction() {
+        Some(x) => builder.position_before(&x),
+        None => builder.position_at_end(entry),
+    }
+    builder.build_alloca(r#type, name)
+}
+
+/// Allocates every local at the start of the function
+fn allocate_locals<'a>(compiler: &Compiler<'a>, locals: &[Local]) -> Vec<PointerValue<'a>> {
+    locals
+        .iter()
+        .map(|x| {
+            compiler
+                .builder
+                .build_alloca(x.r#type.get_llvm_type(compiler), &x.name)
+        })
+        .collect()
+}
+
+/// Describes the allocas of the parameters of a function in its debug info, the other locals
+/// are described by `declare_local`
+fn declare_params<'a>(
+    compiler: &Compiler<'a>,
+    allocas: &[PointerValue<'a>],
+    locals: &[Local],
+    params: &[LocalId],
+    scope: DIScope<'a>,
+) {
+    let Some(debug_info) = &compiler.debug_info else {
+        return;
+    };
+    let block = compiler
+        .builder
+        .get_insert_block()
+        .unwrap_or_else(|| bug!("NO_INSERT_BLOCK"));
+
+    for (i, param) in params.iter().enumerate() {
+        let arg_no = Some(i as u32 + 1);
+        let local = &locals[param.0];
+        debug_info.declare_local(compiler, allocas[param.0], local, arg_no, scope, block);
+    }
+}
+
+/// Describes the alloca of a local in the debug info, where it is declared
+pub fn declare_local<'a>(compiler: &Compiler<'a>, compile_meta: &CompileMetadata<'a>, id: LocalId) {
+    let (Some(debug_info), Some(scope)) = (&compiler.debug_info, compile_meta.scope) else {
+        return;
+    };
+    let block = compiler
+        .builder
+        .get_insert_block()
+        .unwrap_or_else(|| bug!("NO_INSERT_BLOCK"));
+
+    let local = &compile_meta.declarations[id.0];
+    debug_info.declare_local(
+        compiler,
+        compile_meta.locals[id.0],
+        local,
+        None,
+        scope,
+        block,
+    );
+}
+
+/// Opens a lexical block that starts at `span` in the debug info, locals declared in a nested
+/// block are only visible in it. Returns the scope to restore after the block
+pub fn enter_block<'a>(
+    compiler: &Compiler<'a>,
+    compile_meta: &mut CompileMetadata<'a>,
+    span: Span,
+) -> Option<DIScope<'a>> {
+    let outer_scope = compile_meta.scope;
+    if let (Some(debug_info), Some(scope)) = (&compiler.debug_info, outer_scope) {
+        compile_meta.scope = Some(debug_info.lexical_block(scope, span));
+    }
+    outer_scope
+}
+
+/// Compiles the statements of a nested block in a lexical block
+pub fn compile_block<'a>(
+    compiler: &Compiler<'a>,
+    tree: &TypedTree,
+    compile_meta: &mut CompileMetadata<'a>,
+) -> Result<()> {
+    let Some(first) = tree.first() else {
+        return Ok(());
+    };
+    let outer_scope = enter_block(compiler, compile_meta, first.span);
+    compile(compiler, tree, compile_meta)?;
+    compile_meta.scope = outer_scope;
+    Ok(())
+}
+
+/// If the current block already ends with a branch or return
+pub fn is_terminated(compiler: &Compiler) -> bool {
+    compiler
+        .builder
+        .get_insert_block()
+        .and_then(|x| x.get_terminator())
+        .is_some()
+}
+
+pub fn compile<'a>(
+    compiler: &Compiler<'a>,
+    tree: &TypedTree,
+    compile_meta: &mut CompileMetadata<'a>,
+) -> Result<()> {
+    for node in tree {
+        // Code after a `sthu` or `spez` is never ran, but it still needs a block
+        if is_terminated(compiler) {
+            let block = compiler
+                .context
+                .append_basic_block(compile_meta.fn_value, "unreachable");
+            compiler.builder.position_at_end(block);
+        }
+
+        compile_meta.span = node.span;
+        if let (Some(debug_info), Some(scope)) = (&compiler.debug_info, compile_meta.scope) {
+            debug_info.set_location(compiler, node.span, scope);
+        }
+
+        node.node.compile(compiler, compile_meta)?;
+    }
+    Ok(())
 }
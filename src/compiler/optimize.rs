@@ -0,0 +1,30 @@
+use inkwell::{
+    module::Module, passes::PassBuilderOptions, targets::TargetMachine, OptimizationLevel,
+};
+
+use crate::{bug, project::OptLevel, utils::Result};
+
+/// Optimization level of the code generator, which runs after the pass pipeline
+pub fn codegen_level(level: OptLevel) -> OptimizationLevel {
+    match level {
+        OptLevel::O0 => OptimizationLevel::None,
+        OptLevel::O1 => OptimizationLevel::Less,
+        OptLevel::O2 | OptLevel::Os => OptimizationLevel::Default,
+        OptLevel::O3 => OptimizationLevel::Aggressive,
+    }
+}
+
+/// Runs the LLVM pass pipeline of the level (mem2reg, inlining, GVN...) over the whole program
+/// and verifies the result
+pub fn optimize(module: &Module, target_machine: &TargetMachine, level: OptLevel) -> Result<()> {
+    let pipeline = format!("default<O{}>", level.as_str());
+    module
+        .run_passes(&pipeline, target_machine, PassBuilderOptions::create())
+        .map_err(|x| format!("Could not optimize the program: {}", x))?;
+
+    if let Err(x) = module.verify() {
+        bug!("INVALID_OPTIMIZED_MODULE({})", x.to_string());
+    }
+
+    Ok(())
+}
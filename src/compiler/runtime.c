@@ -0,0 +1,437 @@
+/*
+ * The RedditLang runtime, walter compiles this file and links it into every program. build.rs
+ * also compiles it into walter, which runs programs with it in the JIT.
+ *
+ * Standard library functions are prefixed with `rl_`, so they can't collide with libc.
+ * Strings are a length in bytes and null terminated UTF-8 data, `wat` strings have null data.
+ * Strings and arrays are passed by pointer, and returned through a pointer before the arguments.
+ *
+ * On wasm32-wasi there is no setjmp, so bullets can't be caught and always stop the program.
+ */
+
+#ifdef __wasm__
+typedef int jmp_buf[1];
+
+/* Only ever returns once, `rl_throw` never jumps back */
+int setjmp(void *buf) {
+    (void)buf;
+    return 0;
+}
+#else
+#include <setjmp.h>
+#endif
+
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#ifdef _WIN32
+#include <windows.h>
+#else
+#include <time.h>
+#endif
+
+struct rl_string {
+    int64_t length;
+    const char *data;
+};
+
+struct rl_array {
+    int64_t length;
+    void *elements;
+};
+
+/* Memory */
+
+static void out_of_memory(void) {
+    fputs("Out of memory\n", stderr);
+    exit(1);
+}
+
+void *rl_alloc(int64_t size) {
+    void *ptr = malloc(size > 0 ? (size_t)size : 1);
+    if (ptr == NULL) {
+        out_of_memory();
+    }
+    return ptr;
+}
+
+/* Copies `length` bytes into a new null terminated string */
+static struct rl_string rl_string_new(const char *data, size_t length) {
+    char *copy = rl_alloc((int64_t)length + 1);
+    memcpy(copy, data, length);
+    copy[length] = '\0';
+    struct rl_string text = {(int64_t)length, copy};
+    return text;
+}
+
+/* A string of a null terminated string, which is kept */
+static struct rl_string rl_string_of(const char *data) {
+    struct rl_string text = {(int64_t)strlen(data), data};
+    return text;
+}
+
+/* Bullets */
+
+/* A `test`, bullets shot inside of it jump back to it */
+struct rl_frame {
+    jmp_buf buf;
+    struct rl_frame *prev;
+};
+
+static struct rl_frame *rl_frames = NULL;
+static struct rl_string rl_current_bullet = {0, NULL};
+
+/* Called before `setjmp`, the returned buffer is passed to it */
+void *rl_try_enter(void) {
+    struct rl_frame *frame = rl_alloc(sizeof(struct rl_frame));
+    frame->prev = rl_frames;
+    rl_frames = frame;
+    return frame->buf;
+}
+
+/* Called when a `test` is left without a bullet */
+void rl_try_exit(void) {
+    struct rl_frame *frame = rl_frames;
+    if (frame != NULL) {
+        rl_frames = frame->prev;
+        free(frame);
+    }
+}
+
+void rl_throw(const struct rl_string *bullet) {
+    struct rl_frame *frame = rl_frames;
+#ifdef __wasm__
+    frame = NULL;
+#endif
+    if (frame == NULL) {
+        fflush(stdout);
+        fputs("Uncaught bullet: ", stderr);
+        if (bullet->data == NULL) {
+            fputs("wat", stderr);
+        } else {
+            fwrite(bullet->data, 1, (size_t)bullet->length, stderr);
+        }
+        fputc('\n', stderr);
+        exit(1);
+    }
+
+    jmp_buf target;
+    memcpy(target, frame->buf, sizeof(jmp_buf));
+    rl_frames = frame->prev;
+    free(frame);
+
+    rl_current_bullet = *bullet;
+#ifndef __wasm__
+    longjmp(target, 1);
+#endif
+}
+
+static void rl_throw_text(const char *text) {
+    struct rl_string bullet = rl_string_of(text);
+    rl_throw(&bullet);
+}
+
+/* The bullet caught by the current `wall` */
+void rl_bullet(struct rl_string *out) {
+    *out = rl_current_bullet;
+}
+
+/* I/O */
+
+void rl_coitusinterruptus(const struct rl_string *text) {
+    if (text->data == NULL) {
+        puts("wat");
+    } else {
+        fwrite(text->data, 1, (size_t)text->length, stdout);
+        putchar('\n');
+    }
+}
+
+void rl_pulloutnt(struct rl_string *out) {
+    fflush(stdout);
+
+    size_t capacity = 64;
+    size_t length = 0;
+    char *line = rl_alloc(capacity);
+
+    int c;
+    while ((c = getchar()) != EOF && c != '\n') {
+        if (length + 1 == capacity) {
+            capacity *= 2;
+            line = realloc(line, capacity);
+            if (line == NULL) {
+                out_of_memory();
+            }
+        }
+        line[length++] = (char)c;
+    }
+
+    if (length > 0 && line[length - 1] == '\r') {
+        length--;
+    }
+    line[length] = '\0';
+    out->length = (int64_t)length;
+    out->data = line;
+}
+
+/* System */
+
+void rl_exit(double code) {
+    fflush(stdout);
+    exit((int)code);
+}
+
+/* Time */
+
+void rl_zzz(double time_ms) {
+    if (time_ms <= 0) {
+        return;
+    }
+#ifdef _WIN32
+    Sleep((DWORD)time_ms);
+#else
+    struct timespec duration;
+    duration.tv_sec = (time_t)(time_ms / 1000);
+    duration.tv_nsec = (long)((time_ms - (double)duration.tv_sec * 1000) * 1000000);
+    nanosleep(&duration, NULL);
+#endif
+}
+
+/* Strings */
+
+/* A new string with both strings, `wat` is joined as `wat` */
+void rl_concat(struct rl_string *out, const struct rl_string *left,
+               const struct rl_string *right) {
+    struct rl_string wat = rl_string_of("wat");
+    left = left->data == NULL ? &wat : left;
+    right = right->data == NULL ? &wat : right;
+
+    size_t left_length = (size_t)left->length;
+    size_t right_length = (size_t)right->length;
+    char *text = rl_alloc((int64_t)(left_length + right_length + 1));
+    memcpy(text, left->data, left_length);
+    memcpy(text + left_length, right->data, right_length);
+    text[left_length + right_length] = '\0';
+    out->length = (int64_t)(left_length + right_length);
+    out->data = text;
+}
+
+/* Negative if `left` is before `right`, by their bytes, `wat` is before every other string */
+int32_t rl_compare(const struct rl_string *left, const struct rl_string *right) {
+    if (left->data == NULL || right->data == NULL) {
+        return (left->data != NULL) - (right->data != NULL);
+    }
+
+    size_t length = (size_t)(left->length < right->length ? left->length : right->length);
+    int ordering = memcmp(left->data, right->data, length);
+    if (ordering != 0) {
+        return ordering;
+    }
+    return (left->length > right->length) - (left->length < right->length);
+}
+
+/* A string of a C string, null is `wat` */
+void rl_from_c(struct rl_string *out, const char *text) {
+    if (text == NULL) {
+        out->length = 0;
+        out->data = NULL;
+    } else {
+        *out = rl_string_of(text);
+    }
+}
+
+/* Number of characters, `wat` is empty for the string functions */
+static int64_t rl_count(const char *data, int64_t length) {
+    int64_t count = 0;
+    for (int64_t i = 0; i < length; i++) {
+        /* Continuation bytes of UTF-8 are 10xxxxxx */
+        if ((data[i] & 0xC0) != 0x80) {
+            count++;
+        }
+    }
+    return count;
+}
+
+/* Byte offset of the character at `index` */
+static int64_t rl_offset(const char *data, int64_t length, int64_t index) {
+    int64_t offset = 0;
+    while (index > 0 && offset < length) {
+        offset++;
+        while (offset < length && (data[offset] & 0xC0) == 0x80) {
+            offset++;
+        }
+        index--;
+    }
+    return offset;
+}
+
+double rl_length(const struct rl_string *text) {
+    return (double)rl_count(text->data, text->length);
+}
+
+static char *rl_format_number(double number);
+
+/* `count` characters from the character at `start`, the first character is at 1 */
+void rl_substring(struct rl_string *out, const struct rl_string *text, double start,
+                  double count) {
+    int64_t length = rl_count(text->data, text->length);
+    /* Checked before casting, so huge numbers, infinities and NaN are out of bounds too */
+    bool in_range = start >= 1 && start < (double)length + 2 && count > -1 &&
+                    count < (double)length + 1;
+    int64_t skip = in_range ? (int64_t)start - 1 : 0;
+    int64_t take = in_range ? (int64_t)count : 0;
+    if (!in_range || skip + take > length) {
+        char *count_text = rl_format_number(count);
+        char *start_text = rl_format_number(start);
+        const char *format =
+            "Substring of %s characters at %s is out of bounds of a string with %lld characters";
+        size_t size = strlen(format) + strlen(count_text) + strlen(start_text) + 20;
+        char *bullet = rl_alloc((int64_t)size);
+        snprintf(bullet, size, format, count_text, start_text, (long long)length);
+        free(count_text);
+        free(start_text);
+        rl_throw_text(bullet);
+    }
+
+    int64_t from = rl_offset(text->data, text->length, skip);
+    int64_t to = from + rl_offset(text->data + from, text->length - from, take);
+    *out = rl_string_new(text->data + from, (size_t)(to - from));
+}
+
+/* Position of `part` in `text` from the byte at `from`, -1 if it isn't in it */
+static int64_t rl_find(const struct rl_string *text, const struct rl_string *part,
+                       int64_t from) {
+    for (int64_t i = from; i + part->length <= text->length; i++) {
+        if (memcmp(text->data + i, part->data, (size_t)part->length) == 0) {
+            return i;
+        }
+    }
+    return -1;
+}
+
+void rl_split(struct rl_array *out, const struct rl_string *text,
+              const struct rl_string *separator) {
+    if (separator->length == 0) {
+        rl_throw_text("Cannot split with an empty separator");
+    }
+
+    int64_t count = 1;
+    for (int64_t i = rl_find(text, separator, 0); i != -1;
+         i = rl_find(text, separator, i + separator->length)) {
+        count++;
+    }
+
+    struct rl_string *parts = rl_alloc(count * (int64_t)sizeof(struct rl_string));
+    int64_t start = 0;
+    for (int64_t i = 0; i < count; i++) {
+        int64_t end = rl_find(text, separator, start);
+        if (end == -1) {
+            end = text->length;
+        }
+        parts[i] = rl_string_new(text->data == NULL ? "" : text->data + start,
+                                 (size_t)(end - start));
+        start = end + separator->length;
+    }
+    out->length = count;
+    out->elements = parts;
+}
+
+bool rl_contains(const struct rl_string *text, const struct rl_string *part) {
+    return rl_find(text, part, 0) != -1;
+}
+
+/* Conversion */
+
+/* Shortest representation that converts back to the same number, `3` instead of `3.000000` */
+static char *rl_format_number(double number) {
+    char *text = rl_alloc(32);
+    for (int precision = 1; precision <= 17; precision++) {
+        snprintf(text, 32, "%.*g", precision, number);
+        if (strtod(text, NULL) == number) {
+            break;
+        }
+    }
+
+    /* The shortest precision can be too short for the integer digits, ex. 2e+01 for 20 */
+    char *exponent = strchr(text, 'e');
+    if (exponent != NULL) {
+        int digits = atoi(exponent + 1) + 1;
+        if (digits > 0 && digits <= 17) {
+            snprintf(text, 32, "%.*g", digits, number);
+        }
+    }
+    return text;
+}
+
+void rl_nums(struct rl_string *out, double number) {
+    *out = rl_string_of(rl_format_number(number));
+}
+
+/* `strtod` also reads hexadecimal numbers and `nan(...)`, the interpreter reads neither */
+static bool rl_is_decimal(const char *text) {
+    while (*text == ' ' || (*text >= '\t' && *text <= '\r')) {
+        text++;
+    }
+    if (*text == '+' || *text == '-') {
+        text++;
+    }
+    if (text[0] == '0' && (text[1] == 'x' || text[1] == 'X')) {
+        return false;
+    }
+    return strchr(text, '(') == NULL;
+}
+
+double rl_unnums(const struct rl_string *text) {
+    if (text->length == 0) {
+        rl_throw_text("Cannot convert an empty string to a Number");
+    }
+
+    char *end;
+    double number = strtod(text->data, &end);
+    if (end != text->data + text->length || !rl_is_decimal(text->data)) {
+        const char *format = "Cannot convert \"%s\" to a Number";
+        size_t size = strlen(format) + (size_t)text->length;
+        char *bullet = rl_alloc((int64_t)size);
+        snprintf(bullet, size, format, text->data);
+        rl_throw_text(bullet);
+    }
+    return number;
+}
+
+/* Tracing, variables of `debug` functions are printed when they change */
+
+static void rl_trace(const char *location, const char *name) {
+    fflush(stdout);
+    fprintf(stderr, "[%s] %s = ", location, name);
+}
+
+void rl_trace_number(const char *location, const char *name, double value) {
+    rl_trace(location, name);
+    char *text = rl_format_number(value);
+    fprintf(stderr, "%s\n", text);
+    free(text);
+}
+
+void rl_trace_boolean(const char *location, const char *name, bool value) {
+    rl_trace(location, name);
+    fputs(value ? "Yup\n" : "Nope\n", stderr);
+}
+
+void rl_trace_string(const char *location, const char *name, const struct rl_string *value) {
+    rl_trace(location, name);
+    if (value->data == NULL) {
+        fputs("wat\n", stderr);
+    } else {
+        fputc('"', stderr);
+        fwrite(value->data, 1, (size_t)value->length, stderr);
+        fputs("\"\n", stderr);
+    }
+}
+
+void rl_trace_array(const char *location, const char *name, int64_t length) {
+    rl_trace(location, name);
+    fprintf(stderr, "[%lld elements]\n", (long long)length);
+}
@@ -0,0 +1,258 @@
+/*
+ * The RedditLang runtime for wasm32-unknown-unknown, walter writes it next to the module.
+ *
+ * The module imports the runtime and the standard library from `env`, this file provides them.
+ * Strings are `{ length: i64, data: i32 }`, with null terminated UTF-8 data, `wat` strings have
+ * null data. Strings and arrays are passed by pointer, and returned through a pointer before the
+ * arguments.
+ * There is no setjmp, so bullets can't be caught and always stop the program.
+ *
+ *     import { run } from "./runtime.js";
+ *
+ *     const bytes = await (await fetch("program.wasm")).arrayBuffer();
+ *     const code = await run(bytes, { print: (text) => console.log(text) });
+ */
+
+/* Shot by `exit`, unwinds the module */
+class Exit extends Error {
+    constructor(code) {
+        super(`Exited with ${code}`);
+        this.code = code;
+    }
+}
+
+export class Bullet extends Error {}
+
+/* Shortest representation that converts back to the same number, like the C runtime */
+function nums(number) {
+    if (Number.isNaN(number)) {
+        return "nan";
+    }
+    if (!Number.isFinite(number)) {
+        return number > 0 ? "inf" : "-inf";
+    }
+    // `toExponential` and `toFixed` drop the sign of -0
+    if (number < 0 || Object.is(number, -0)) {
+        return `-${nums(-number)}`;
+    }
+
+    let text = formatG(number, 17);
+    for (let precision = 1; precision <= 17; precision++) {
+        const shortest = formatG(number, precision);
+        if (Number(shortest) === number) {
+            text = shortest;
+            break;
+        }
+    }
+
+    // The shortest precision can be too short for the integer digits, ex. 2e+01 for 20
+    const exponent = text.split("e")[1];
+    const digits = exponent === undefined ? 0 : Number(exponent) + 1;
+    return digits > 0 && digits <= 17 ? formatG(number, digits) : text;
+}
+
+/* `%.<precision>g` of C, for positive numbers */
+function formatG(number, precision) {
+    // The exponent after rounding to `precision` digits decides the notation
+    const [mantissa, exponent] = number.toExponential(precision - 1).split("e");
+    const power = Number(exponent);
+    if (power < -4 || power >= precision) {
+        const sign = power < 0 ? "-" : "+";
+        return `${trimZeros(mantissa)}e${sign}${String(Math.abs(power)).padStart(2, "0")}`;
+    }
+    return trimZeros(number.toFixed(precision - 1 - power));
+}
+
+/* `%g` removes trailing zeros of the fraction */
+function trimZeros(text) {
+    return text.includes(".") ? text.replace(/0+$/, "").replace(/\.$/, "") : text;
+}
+
+/*
+ * Runs a module and returns its exit code.
+ * `print` gets every line printed by `coitusinterruptus`, `read` returns a line for `pulloutnt`.
+ */
+export async function run(bytes, { print = console.log, read = () => prompt("") ?? "" } = {}) {
+    const encoder = new TextEncoder();
+    const decoder = new TextDecoder();
+
+    let memory;
+    // Nothing is freed, the heap only grows
+    let heap;
+
+    const alloc = (size) => {
+        const ptr = (heap + 7) & ~7;
+        heap = ptr + Math.max(size, 1);
+
+        const missing = heap - memory.buffer.byteLength;
+        if (missing > 0) {
+            memory.grow(Math.ceil(missing / 65536));
+        }
+        return ptr;
+    };
+
+    const readCString = (ptr) => {
+        if (ptr === 0) {
+            return null;
+        }
+        const bytes = new Uint8Array(memory.buffer);
+        let end = ptr;
+        while (bytes[end] !== 0) {
+            end++;
+        }
+        return decoder.decode(bytes.subarray(ptr, end));
+    };
+
+    // Bytes of a string, null for `wat`
+    const readBytes = (ptr) => {
+        const view = new DataView(memory.buffer);
+        const length = Number(view.getBigInt64(ptr, true));
+        const data = view.getUint32(ptr + 8, true);
+        return data === 0 ? null : new Uint8Array(memory.buffer, data, length);
+    };
+
+    const readString = (ptr) => {
+        const bytes = readBytes(ptr);
+        return bytes === null ? null : decoder.decode(bytes);
+    };
+
+    const writeString = (out, text) => {
+        const encoded = encoder.encode(text);
+        const data = alloc(encoded.length + 1);
+        const bytes = new Uint8Array(memory.buffer, data, encoded.length + 1);
+        bytes.set(encoded);
+        bytes[encoded.length] = 0;
+
+        const view = new DataView(memory.buffer);
+        view.setBigInt64(out, BigInt(encoded.length), true);
+        view.setUint32(out + 8, data, true);
+    };
+
+    const writeWat = (out) => {
+        const view = new DataView(memory.buffer);
+        view.setBigInt64(out, 0n, true);
+        view.setUint32(out + 8, 0, true);
+    };
+
+    // Arrays are `{ length: i64, elements: i32 }` too, a string takes 16 bytes
+    const writeStrings = (out, texts) => {
+        const elements = alloc(texts.length * 16);
+        texts.forEach((text, i) => writeString(elements + i * 16, text));
+
+        const view = new DataView(memory.buffer);
+        view.setBigInt64(out, BigInt(texts.length), true);
+        view.setUint32(out + 8, elements, true);
+    };
+
+    // `wat` is empty for the string functions, characters are counted by code point
+    const characters = (ptr) => [...(readString(ptr) ?? "")];
+
+    const shoot = (bullet) => {
+        throw new Bullet(bullet);
+    };
+
+    // Variables of `debug` functions are printed when they change
+    const trace = (location, name, value) =>
+        console.error(`[${readCString(location)}] ${readCString(name)} = ${value}`);
+
+    const env = {
+        // Sizes are i64, which are BigInts in JavaScript
+        rl_alloc: (size) => alloc(Number(size)),
+        rl_try_enter: () => 0,
+        rl_try_exit: () => {},
+        setjmp: () => 0,
+        rl_bullet: (out) => writeWat(out),
+        rl_throw: (ptr) => shoot(readString(ptr) ?? "wat"),
+
+        rl_concat: (out, left, right) =>
+            writeString(out, (readString(left) ?? "wat") + (readString(right) ?? "wat")),
+        // By bytes like the C runtime, UTF-16 code units aren't in the same order
+        rl_compare: (left, right) => {
+            const lhs = readBytes(left);
+            const rhs = readBytes(right);
+            if (lhs === null || rhs === null) {
+                return (lhs !== null) - (rhs !== null);
+            }
+            for (let i = 0; i < Math.min(lhs.length, rhs.length); i++) {
+                if (lhs[i] !== rhs[i]) {
+                    return lhs[i] - rhs[i];
+                }
+            }
+            return lhs.length - rhs.length;
+        },
+        rl_from_c: (out, ptr) => {
+            const text = readCString(ptr);
+            if (text === null) {
+                writeWat(out);
+            } else {
+                writeString(out, text);
+            }
+        },
+        rl_length: (ptr) => characters(ptr).length,
+        rl_substring: (out, ptr, start, count) => {
+            const text = characters(ptr);
+            const [skip, take] = [Math.trunc(start) - 1, Math.trunc(count)];
+            // Negated so NaN is out of bounds too
+            if (!(skip >= 0 && take >= 0 && skip + take <= text.length)) {
+                shoot(
+                    `Substring of ${nums(count)} characters at ${nums(start)} is out of bounds ` +
+                        `of a string with ${text.length} characters`,
+                );
+            }
+            writeString(out, text.slice(skip, skip + take).join(""));
+        },
+        rl_split: (out, ptr, separator) => {
+            const by = readString(separator) ?? "";
+            if (by === "") {
+                shoot("Cannot split with an empty separator");
+            }
+            writeStrings(out, (readString(ptr) ?? "").split(by));
+        },
+        rl_contains: (ptr, part) => (readString(ptr) ?? "").includes(readString(part) ?? ""),
+
+        rl_coitusinterruptus: (ptr) => print(readString(ptr) ?? "wat"),
+        rl_pulloutnt: (out) => writeString(out, read().replace(/\r$/, "")),
+        rl_exit: (code) => {
+            throw new Exit(Math.trunc(code));
+        },
+        // The browser can't block, sleeping does nothing
+        rl_zzz: () => {},
+        rl_nums: (out, number) => writeString(out, nums(number)),
+        rl_unnums: (ptr) => {
+            const text = readString(ptr);
+            if (text === null || text === "") {
+                shoot("Cannot convert an empty string to a Number");
+            }
+            const number = Number(text);
+            if (Number.isNaN(number) && text.toLowerCase() !== "nan") {
+                shoot(`Cannot convert "${text}" to a Number`);
+            }
+            return number;
+        },
+
+        rl_trace_number: (location, name, value) => trace(location, name, nums(value)),
+        rl_trace_boolean: (location, name, value) => trace(location, name, value ? "Yup" : "Nope"),
+        rl_trace_string: (location, name, ptr) => {
+            const text = readString(ptr);
+            trace(location, name, text === null ? "wat" : `"${text}"`);
+        },
+        rl_trace_array: (location, name, length) => trace(location, name, `[${length} elements]`),
+    };
+
+    const { instance } = await WebAssembly.instantiate(bytes, { env });
+    memory = instance.exports.memory;
+    heap = instance.exports.__heap_base.value;
+
+    try {
+        return instance.exports.main();
+    } catch (error) {
+        if (error instanceof Exit) {
+            return error.code;
+        }
+        if (error instanceof Bullet) {
+            console.error(`Uncaught bullet: ${error.message}`);
+            return 1;
+        }
+        throw error;
+    }
+}
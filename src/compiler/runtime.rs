@@ -0,0 +1,241 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use inkwell::{
+    attributes::AttributeLoc,
+    types::{BasicMetadataTypeEnum, BasicType},
+    values::{BasicMetadataValueEnum, BasicValueEnum, FunctionValue, PointerValue},
+    AddressSpace,
+};
+
+use crate::{
+    bug,
+    compiler::{entry_alloca, enum_attribute, Compiler},
+    semantic::{Signature, ValidType},
+    utils::Result,
+};
+
+/// Source of the runtime, it is compiled with every program when linking
+const RUNTIME_SOURCE: &str = include_str!("runtime.c");
+/// Runtime of wasm32-unknown-unknown modules, which import it from JavaScript
+const JS_RUNTIME_SOURCE: &str = include_str!("runtime.js");
+
+/// Symbol of a standard library function in the runtime
+pub fn std_symbol(name: &str) -> String {
+    format!("rl_{}", name)
+}
+
+/// Writes the runtime into the build directory and returns its path
+pub fn write_runtime(build_dir: &Path) -> Result<PathBuf> {
+    let path = build_dir.join("runtime.c");
+    fs::write(&path, RUNTIME_SOURCE)?;
+    Ok(path)
+}
+
+/// Writes the JavaScript runtime into the build directory and returns its path
+pub fn write_js_runtime(build_dir: &Path) -> Result<PathBuf> {
+    let path = build_dir.join("runtime.js");
+    fs::write(&path, JS_RUNTIME_SOURCE)?;
+    Ok(path)
+}
+
+/// Declares the runtime functions used by the generated code, standard library functions are
+/// declared when they are called
+pub fn define_runtime(compiler: &Compiler) {
+    let context = compiler.context;
+    let ptr_type = context.i8_type().ptr_type(AddressSpace::default());
+
+    compiler.module.add_function(
+        "rl_alloc",
+        ptr_type.fn_type(&[context.i64_type().into()], false),
+        None,
+    );
+    compiler
+        .module
+        .add_function("rl_try_enter", ptr_type.fn_type(&[], false), None);
+    compiler
+        .module
+        .add_function("rl_try_exit", context.void_type().fn_type(&[], false), None);
+    let void_type = context.void_type();
+
+    // Strings are passed by pointer, and returned through the first pointer
+    compiler.module.add_function(
+        "rl_bullet",
+        void_type.fn_type(&[ptr_type.into()], false),
+        None,
+    );
+    compiler.module.add_function(
+        "rl_concat",
+        void_type.fn_type(&[ptr_type.into(), ptr_type.into(), ptr_type.into()], false),
+        None,
+    );
+    compiler.module.add_function(
+        "rl_compare",
+        context
+            .i32_type()
+            .fn_type(&[ptr_type.into(), ptr_type.into()], false),
+        None,
+    );
+    compiler.module.add_function(
+        "rl_from_c",
+        void_type.fn_type(&[ptr_type.into(), ptr_type.into()], false),
+        None,
+    );
+
+    let throw = compiler.module.add_function(
+        "rl_throw",
+        context.void_type().fn_type(&[ptr_type.into()], false),
+        None,
+    );
+    throw.add_attribute(AttributeLoc::Function, enum_attribute(compiler, "noreturn"));
+
+    // Returns once when the `try` starts, and again when a bullet is thrown
+    let setjmp = compiler.module.add_function(
+        "setjmp",
+        context.i32_type().fn_type(&[ptr_type.into()], false),
+        None,
+    );
+    setjmp.add_attribute(
+        AttributeLoc::Function,
+        enum_attribute(compiler, "returns_twice"),
+    );
+
+    // Print the variables of `debug` functions, they take the location, name and value
+    let trace_values = [
+        ("rl_trace_number", context.f64_type().into()),
+        ("rl_trace_boolean", context.bool_type().into()),
+        ("rl_trace_string", ptr_type.into()),
+        ("rl_trace_array", context.i64_type().into()),
+    ];
+    for (name, value_type) in trace_values {
+        let trace = compiler.module.add_function(
+            name,
+            void_type.fn_type(&[ptr_type.into(), ptr_type.into(), value_type], false),
+            None,
+        );
+        if name == "rl_trace_boolean" {
+            trace.add_attribute(AttributeLoc::Param(2), enum_attribute(compiler, "zeroext"));
+        }
+    }
+}
+
+fn runtime_function<'a>(compiler: &Compiler<'a>, name: &str) -> FunctionValue<'a> {
+    compiler
+        .module
+        .get_function(name)
+        .unwrap_or_else(|| bug!("UNDECLARED_RUNTIME_FUNCTION({})", name))
+}
+
+/// If values of the type are passed to the runtime by pointer, and returned through a pointer
+fn by_pointer(r#type: &ValidType) -> bool {
+    matches!(r#type, ValidType::String | ValidType::Array(_))
+}
+
+/// Stores a string or array so it can be passed to the runtime
+pub fn to_pointer<'a>(compiler: &Compiler<'a>, value: BasicValueEnum<'a>) -> PointerValue<'a> {
+    let ptr = entry_alloca(compiler, value.get_type(), "arg");
+    compiler.builder.build_store(ptr, value);
+    compiler.builder.build_pointer_cast(
+        ptr,
+        compiler.context.i8_type().ptr_type(AddressSpace::default()),
+        "arg",
+    )
+}
+
+/// Calls a runtime function that returns a value of `r#type` through its first argument
+pub fn call_runtime_out<'a>(
+    compiler: &Compiler<'a>,
+    name: &str,
+    r#type: &ValidType,
+    args: &[BasicMetadataValueEnum<'a>],
+) -> BasicValueEnum<'a> {
+    let value_type = r#type.get_llvm_type(compiler);
+    let out = entry_alloca(compiler, value_type, name);
+    let out_arg = compiler.builder.build_pointer_cast(
+        out,
+        compiler.context.i8_type().ptr_type(AddressSpace::default()),
+        name,
+    );
+
+    let mut out_args = vec![out_arg.into()];
+    out_args.extend_from_slice(args);
+    call_runtime(compiler, name, &out_args);
+    compiler.builder.build_load(value_type, out, name)
+}
+
+/// Calls a standard library function of the runtime, declaring it on its first call
+pub fn call_std<'a>(
+    compiler: &Compiler<'a>,
+    name: &str,
+    signature: &Signature,
+    args: &[BasicValueEnum<'a>],
+) -> Option<BasicValueEnum<'a>> {
+    let symbol = std_symbol(name);
+    if compiler.module.get_function(&symbol).is_none() {
+        declare_std(compiler, &symbol, signature);
+    }
+
+    let args = args
+        .iter()
+        .zip(&signature.params)
+        .map(|(arg, r#type)| match by_pointer(r#type) {
+            true => to_pointer(compiler, *arg).into(),
+            false => (*arg).into(),
+        })
+        .collect::<Vec<BasicMetadataValueEnum>>();
+
+    match &signature.ret {
+        Some(x) if by_pointer(x) => Some(call_runtime_out(compiler, &symbol, x, &args)),
+        _ => call_runtime(compiler, &symbol, &args),
+    }
+}
+
+/// Booleans are a C `bool`, strings and arrays are pointers
+fn declare_std<'a>(compiler: &Compiler<'a>, symbol: &str, signature: &Signature) {
+    let ptr_type = compiler.context.i8_type().ptr_type(AddressSpace::default());
+    let lower = |x: &ValidType| match by_pointer(x) {
+        true => ptr_type.as_basic_type_enum(),
+        false => x.get_llvm_type(compiler),
+    };
+
+    let mut params = signature
+        .params
+        .iter()
+        .map(|x| lower(x).into())
+        .collect::<Vec<BasicMetadataTypeEnum>>();
+    let fn_type = match &signature.ret {
+        Some(x) if by_pointer(x) => {
+            params.insert(0, ptr_type.into());
+            compiler.context.void_type().fn_type(&params, false)
+        }
+        Some(x) => lower(x).fn_type(&params, false),
+        None => compiler.context.void_type().fn_type(&params, false),
+    };
+    let function = compiler.module.add_function(symbol, fn_type, None);
+
+    let zeroext = enum_attribute(compiler, "zeroext");
+    let offset = params.len() - signature.params.len();
+    for (i, param) in signature.params.iter().enumerate() {
+        if *param == ValidType::Boolean {
+            function.add_attribute(AttributeLoc::Param((i + offset) as u32), zeroext);
+        }
+    }
+    if signature.ret == Some(ValidType::Boolean) {
+        function.add_attribute(AttributeLoc::Return, zeroext);
+    }
+}
+
+/// Calls a function declared by `define_runtime`, returns its value if it has one
+pub fn call_runtime<'a>(
+    compiler: &Compiler<'a>,
+    name: &str,
+    args: &[BasicMetadataValueEnum<'a>],
+) -> Option<BasicValueEnum<'a>> {
+    compiler
+        .builder
+        .build_call(runtime_function(compiler, name), args, name)
+        .try_as_basic_value()
+        .left()
+}
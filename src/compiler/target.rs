@@ -0,0 +1,110 @@
+use crate::{project::TargetConfiguration, utils::Result};
+use colored::Colorize;
+use inkwell::{
+    targets::{CodeModel, InitializationConfig, RelocMode, Target, TargetMachine, TargetTriple},
+    OptimizationLevel,
+};
+
+/// The target a program is compiled for
+pub struct BuildTarget {
+    pub triple: TargetTriple,
+    pub cpu: String,
+    pub features: String,
+}
+
+impl BuildTarget {
+    /// Fills in the missing fields of a configuration. Without a CPU the oldest CPU of the
+    /// architecture is used, so binaries run everywhere
+    pub fn from_configuration(config: TargetConfiguration) -> Self {
+        let triple = match &config.triple {
+            Some(x) => TargetMachine::normalize_triple(&TargetTriple::create(x)),
+            None => TargetMachine::get_default_triple(),
+        };
+
+        let native = config.cpu.as_deref() == Some("native");
+        let cpu = match config.cpu {
+            Some(_) if native => TargetMachine::get_host_cpu_name().to_string(),
+            Some(x) => x,
+            None => default_cpu(&triple).to_string(),
+        };
+        let features = match config.features {
+            Some(x) => x,
+            None if native => TargetMachine::get_host_cpu_features().to_string(),
+            None => String::new(),
+        };
+
+        Self {
+            triple,
+            cpu,
+            features,
+        }
+    }
+
+    pub fn triple_str(&self) -> &str {
+        self.triple.as_str().to_str().unwrap()
+    }
+
+    /// If the target is `wasm32-wasi` or `wasm32-unknown-unknown`
+    pub fn is_wasm(&self) -> bool {
+        self.triple_str().starts_with("wasm32")
+    }
+
+    /// If the target is `wasm32-wasi`, which has a C library and runs in a wasm runtime
+    pub fn is_wasi(&self) -> bool {
+        self.is_wasm() && self.triple_str().contains("wasi")
+    }
+
+    /// If programs built for the target can run on this machine, the vendor does not matter
+    pub fn is_host(&self) -> bool {
+        fn without_vendor(triple: &TargetTriple) -> Vec<String> {
+            let triple = triple.as_str().to_str().unwrap();
+            triple
+                .split('-')
+                .enumerate()
+                .filter(|(i, _)| *i != 1)
+                .map(|(_, x)| x.to_string())
+                .collect()
+        }
+
+        without_vendor(&self.triple)
+            == without_vendor(&TargetMachine::normalize_triple(
+                &TargetMachine::get_default_triple(),
+            ))
+    }
+
+    pub fn create_target_machine(&self, opt: OptimizationLevel) -> Result<TargetMachine> {
+        Target::initialize_all(&InitializationConfig::default());
+
+        let target = Target::from_triple(&self.triple)
+            .map_err(|x| format!("Unsupported target {}: {}", self.triple_str().bold(), x))?;
+
+        // PIC is required for some bizzare reason, but wasm-ld only links static code
+        let reloc = if self.is_wasm() {
+            RelocMode::Static
+        } else {
+            RelocMode::PIC
+        };
+        let model = CodeModel::Default;
+
+        target
+            .create_target_machine(&self.triple, &self.cpu, &self.features, opt, reloc, model)
+            .ok_or_else(|| {
+                format!(
+                    "Could not create a target machine for {} with CPU {} and features {}",
+                    self.triple_str().bold(),
+                    self.cpu.bold(),
+                    self.features.bold()
+                )
+                .into()
+            })
+    }
+}
+
+/// The baseline CPU of the architecture of a triple
+fn default_cpu(triple: &TargetTriple) -> &'static str {
+    let triple = triple.as_str().to_str().unwrap();
+    match triple.split('-').next().unwrap_or_default() {
+        "x86_64" => "x86-64",
+        _ => "generic",
+    }
+}
@@ -0,0 +1,180 @@
+use std::{fs, path::Path};
+
+use crate::{
+    bug,
+    parser::{Declaration, FunctionMod, Node, Type, VariableMod},
+    project::SourceModule,
+    semantic::{Program, Signature, TypedModule, ValidType},
+    utils::Result,
+};
+
+/// A documented item of a module
+struct Item {
+    name: String,
+    /// Declaration as it would be written, with the inferred types
+    signature: String,
+    doc: Option<String>,
+}
+
+/// Writes an mdBook with a page for every module to `out_dir`, each page lists the `bar`
+/// functions and variables of the module with their types and doc comments
+pub fn write_book(
+    out_dir: &Path,
+    title: &str,
+    modules: &[SourceModule],
+    program: &Program,
+) -> Result<()> {
+    let src_dir = out_dir.join("src");
+    fs::create_dir_all(&src_dir)?;
+
+    fs::write(
+        out_dir.join("book.toml"),
+        format!(
+            "[book]\nlanguage = \"en\"\nsrc = \"src\"\ntitle = {:?}\n",
+            title
+        ),
+    )?;
+
+    let mut summary = String::from("# Summary\n\n");
+    for module in modules {
+        let typed = program
+            .modules
+            .iter()
+            .find(|x| x.name == module.name)
+            .unwrap_or_else(|| bug!("UNKNOWN_MODULE({})", module.name));
+        let file = format!("{}.md", module.name);
+
+        summary.push_str(&format!("- [{}](./{})\n", module.name, file));
+        fs::write(src_dir.join(file), page(module, typed))?;
+    }
+    fs::write(src_dir.join("SUMMARY.md"), summary)?;
+
+    Ok(())
+}
+
+/// Markdown page of a module
+fn page(module: &SourceModule, typed: &TypedModule) -> String {
+    let mut functions = vec![];
+    let mut variables = vec![];
+
+    for node in &module.tree {
+        match &node.node {
+            Node::Function(function)
+                if function
+                    .modifiers
+                    .iter()
+                    .any(|x| matches!(x, FunctionMod::Public)) =>
+            {
+                let name = &function.declaration.ident.0;
+                let signature = &typed
+                    .functions
+                    .iter()
+                    .find(|x| &x.name == name)
+                    .unwrap_or_else(|| bug!("UNKNOWN_FUNCTION({})", name))
+                    .signature;
+                functions.push(Item {
+                    name: name.clone(),
+                    signature: function_signature(
+                        "bar callmeonmycellphone",
+                        name,
+                        &function.args,
+                        signature,
+                    ),
+                    doc: function.doc.clone(),
+                });
+            }
+            Node::Extern(function)
+                if function
+                    .modifiers
+                    .iter()
+                    .any(|x| matches!(x, FunctionMod::Public)) =>
+            {
+                // Outsourced functions always have valid types after analysis
+                let name = &function.declaration.ident.0;
+                let valid = |r#type: &Type| {
+                    ValidType::try_from(r#type).unwrap_or_else(|x| bug!("INVALID_TYPE({})", x))
+                };
+                let signature = Signature {
+                    params: function
+                        .args
+                        .iter()
+                        .filter_map(|x| x.r#type.as_ref().map(valid))
+                        .collect(),
+                    ret: function.declaration.r#type.as_ref().map(valid),
+                };
+                functions.push(Item {
+                    name: name.clone(),
+                    signature: function_signature(
+                        "bar outsourced callmeonmycellphone",
+                        name,
+                        &function.args,
+                        &signature,
+                    ),
+                    doc: function.doc.clone(),
+                });
+            }
+            Node::Variable(variable)
+                if variable
+                    .modifiers
+                    .iter()
+                    .any(|x| matches!(x, VariableMod::Public)) =>
+            {
+                let name = &variable.declaration.ident.0;
+                let global = typed
+                    .globals
+                    .iter()
+                    .find(|x| x.public && &x.name == name)
+                    .unwrap_or_else(|| bug!("UNKNOWN_GLOBAL({})", name));
+                variables.push(Item {
+                    name: name.clone(),
+                    signature: format!("bar meth {} damn {}", name, global.r#type),
+                    doc: variable.doc.clone(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    let mut out = format!("# {}\n", module.name);
+    if functions.is_empty() && variables.is_empty() {
+        out.push_str("\nThis module has no public items.\n");
+    }
+    for (heading, items) in [("Functions", functions), ("Variables", variables)] {
+        if items.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("\n## {}\n", heading));
+        for item in items {
+            out.push_str(&format!(
+                "\n### {}\n\n```redditlang\n{}\n```\n",
+                item.name, item.signature
+            ));
+            if let Some(doc) = item.doc {
+                out.push_str(&format!("\n{}\n", doc));
+            }
+        }
+    }
+    out
+}
+
+/// `<keyword> name damn Number(a damn Number, b damn String,)`
+pub fn function_signature(
+    keyword: &str,
+    name: &str,
+    args: &[Declaration],
+    signature: &Signature,
+) -> String {
+    let mut out = format!("{} {}", keyword, name);
+    if let Some(ret) = &signature.ret {
+        out.push_str(&format!(" damn {}", ret));
+    }
+    out.push('(');
+    for (i, (arg, r#type)) in args.iter().zip(&signature.params).enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        out.push_str(&format!("{} damn {},", arg.ident.0, r#type));
+    }
+    out.push(')');
+    out
+}
@@ -1,7 +1,7 @@
-use crate::Rule;
+use crate::{project::SourceModule, semantic::Diagnostic, Rule};
 use colored::Colorize;
 use core::fmt;
-use pest::error::LineColLocation;
+use pest::error::{Error, ErrorVariant, LineColLocation};
 
 pub fn format_error(error: pest::error::Error<Rule>) -> String {
     let code = error.line();
@@ -18,13 +18,22 @@ pub fn format_error(error: pest::error::Error<Rule>) -> String {
     let colored_col = pos.1.to_string().blue().bold();
 
     let colored_error_position = format!("{}{}{}", colored_line, ":".blue().bold(), colored_col);
+    let colored_error_position = match error.path() {
+        Some(path) => format!(
+            "{}{}{}",
+            path.blue().bold(),
+            ":".blue().bold(),
+            colored_error_position
+        ),
+        None => colored_error_position,
+    };
 
     let colored_bar = "|".blue().bold();
     let colored_eq = "=".blue().bold();
 
     let colored_arrow = "-->".blue().bold();
     let colored_error_arrow = "^".red().bold();
-    return format!(
+    format!(
         r"
 {line_padding}{colored_arrow} {colored_error_position}
 {line_padding} {colored_bar}
@@ -33,7 +42,20 @@ pub fn format_error(error: pest::error::Error<Rule>) -> String {
 {line_padding} {colored_bar}
 {line_padding} {colored_eq} {message}
 "
+    )
+}
+
+pub fn format_diagnostic(diagnostic: &Diagnostic, module: &SourceModule) -> String {
+    let span = pest::Span::new(&module.source, diagnostic.span.start, diagnostic.span.end)
+        .unwrap_or_else(|| crate::bug!("INVALID_SPAN({:?})", diagnostic.span));
+    let error: Error<Rule> = Error::new_from_span(
+        ErrorVariant::CustomError {
+            message: diagnostic.message.clone(),
+        },
+        span,
     );
+
+    format_error(error.with_path(module.path.to_str().unwrap()))
 }
 
 pub fn syntax_error(syntax_error: pest::error::Error<Rule>) -> ! {
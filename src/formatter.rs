@@ -0,0 +1,534 @@
+use std::ops::Range;
+
+use pest::{
+    iterators::{Pair, Pairs},
+    Parser,
+};
+
+use crate::{
+    bug,
+    parser::{
+        cst::{self, Token},
+        operators,
+    },
+    RLParser, Rule,
+};
+
+/// Spaces per level of indentation
+const INDENT: &str = "    ";
+
+/// Pretty prints a source file: blocks are indented with 4 spaces, operators are surrounded by
+/// spaces, lists end with a comma and there is at most one blank line between statements.
+/// Comments are kept, a comment inside of a statement is moved above it. Operators written in ASCII
+/// are kept, or replaced by the Unicode operator with `unicode`
+pub fn format_source(source: &str, unicode: bool) -> Result<String, Box<pest::error::Error<Rule>>> {
+    let pairs = RLParser::parse(Rule::AsciiProgram, source)?;
+
+    let mut formatter = Formatter {
+        source,
+        comments: cst::from_pairs(source, pairs.clone())
+            .comments()
+            .into_iter()
+            .cloned()
+            .collect(),
+        next_comment: 0,
+        last_end: 0,
+        indent: 0,
+        unicode,
+        out: String::new(),
+    };
+    let statements = pairs.filter(|x| x.as_rule() == Rule::Statement);
+    formatter.statements(statements, source.len());
+
+    let mut out = formatter.out;
+    let trimmed = out.trim_end_matches('\n').len();
+    out.truncate(trimmed);
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    comments: Vec<Token>,
+    /// Index of the first comment that is not written yet
+    next_comment: usize,
+    /// End of the last statement or comment that was written
+    last_end: usize,
+    indent: usize,
+    /// Replaces ASCII operators by their Unicode operator
+    unicode: bool,
+    out: String,
+}
+
+impl<'a> Formatter<'a> {
+    /// Writes a list of statements, `end` is where the list ends in the source
+    fn statements<'b>(&mut self, statements: impl Iterator<Item = Pair<'b, Rule>>, end: usize) {
+        for statement in statements {
+            let start = statement.as_span().start();
+            let end = self.code_end(start, statement.as_span().end());
+            self.comments_before(start);
+
+            // Comments outside of the blocks of a statement, ex. in a `but` condition, can't stay
+            // there
+            let inner = statement.clone().into_inner().next().unwrap();
+            self.comments_outside(end, &blocks(inner.clone()));
+
+            self.blank_line(start);
+            self.line_start();
+            self.statement(inner);
+            self.last_end = end;
+            self.trailing_comment();
+            self.out.push('\n');
+        }
+
+        self.comments_before(end);
+    }
+
+    /// End of the code in a span, a span can end with comments after its last token
+    fn code_end(&self, start: usize, mut end: usize) -> usize {
+        loop {
+            end = start + self.source[start..end].trim_end().len();
+            match self.comments.iter().find(|x| x.range.end == end) {
+                Some(comment) if comment.range.start >= start => end = comment.range.start,
+                _ => return end,
+            }
+        }
+    }
+
+    /// Writes the comments that start before `position` on their own lines
+    fn comments_before(&mut self, position: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.range.start >= position {
+                break;
+            }
+            let comment = comment.clone();
+            self.next_comment += 1;
+            self.comment_line(&comment);
+        }
+    }
+
+    /// Writes the comments that start before `end` and are not in one of `blocks` on their own
+    /// lines, the others are written with their block
+    fn comments_outside(&mut self, end: usize, blocks: &[Range<usize>]) {
+        let mut i = self.next_comment;
+        while let Some(comment) = self.comments.get(i) {
+            if comment.range.start >= end {
+                break;
+            }
+            if blocks.iter().any(|x| x.contains(&comment.range.start)) {
+                i += 1;
+                continue;
+            }
+            let comment = self.comments.remove(i);
+            self.comment_line(&comment);
+        }
+    }
+
+    fn comment_line(&mut self, comment: &Token) {
+        self.blank_line(comment.range.start);
+        self.line_start();
+        self.out.push_str(&comment.text);
+        self.out.push('\n');
+        self.last_end = comment.range.end;
+    }
+
+    /// Writes a comment that is on the same line as the end of the last statement
+    fn trailing_comment(&mut self) {
+        let Some(comment) = self.comments.get(self.next_comment) else {
+            return;
+        };
+        let between = self.source.get(self.last_end..comment.range.start);
+        if between.is_none_or(|x| x.contains('\n')) {
+            return;
+        }
+
+        self.out.push(' ');
+        self.out.push_str(&comment.text);
+        self.last_end = comment.range.end;
+        self.next_comment += 1;
+    }
+
+    /// Keeps one blank line if there are any between the last statement and `position`, except at
+    /// the start of a block
+    fn blank_line(&mut self, position: usize) {
+        let between = &self.source[self.last_end.min(position)..position];
+        let at_block_start = self.out.is_empty() || self.out.ends_with("{\n");
+        if between.matches('\n').count() > 1 && !at_block_start {
+            self.out.push('\n');
+        }
+    }
+
+    fn line_start(&mut self) {
+        self.out.push_str(&INDENT.repeat(self.indent));
+    }
+
+    fn block(&mut self, block: Pair<Rule>) {
+        let span = block.as_span();
+        let statements = block
+            .into_inner()
+            .filter(|x| x.as_rule() == Rule::Statement);
+        let has_comments = self
+            .comments
+            .get(self.next_comment)
+            .is_some_and(|x| x.range.start < span.end());
+
+        let mut statements = statements.peekable();
+        if statements.peek().is_none() && !has_comments {
+            self.out.push_str("{}");
+            return;
+        }
+
+        self.out.push_str("{\n");
+        self.indent += 1;
+        self.last_end = span.start() + 1;
+        // The closing brace is not part of the list
+        self.statements(statements, span.end() - 1);
+        self.indent -= 1;
+        self.line_start();
+        self.out.push('}');
+        self.last_end = span.end();
+    }
+
+    fn statement(&mut self, pair: Pair<Rule>) {
+        let rule = pair.as_rule();
+        let text = pair.as_str();
+        let mut inner = pair.into_inner();
+
+        match rule {
+            Rule::Loop => {
+                self.out.push_str("repeatdatshid ");
+                self.block(inner.next().unwrap());
+            }
+            Rule::Break => self.out.push_str("sthu"),
+            Rule::Function => {
+                self.modifiers(inner.next().unwrap());
+                self.out.push_str("callmeonmycellphone ");
+                self.declaration(inner.next().unwrap());
+                self.args(inner.next().unwrap());
+                self.out.push(' ');
+                self.block(inner.next().unwrap());
+            }
+            Rule::Extern => {
+                self.modifiers(inner.next().unwrap());
+                self.out.push_str("outsourced callmeonmycellphone ");
+                self.declaration(inner.next().unwrap());
+                self.args(inner.next().unwrap());
+            }
+            Rule::Call => self.call(inner),
+            Rule::TryCatch => {
+                let r#try = inner.next().unwrap();
+                self.out.push_str("test ");
+                self.block(r#try.into_inner().next().unwrap());
+
+                let mut catch = inner.next().unwrap().into_inner().peekable();
+                self.out.push_str(" wall ");
+                if let Some(ident) = catch.next_if(|x| x.as_rule() == Rule::Ident) {
+                    self.out.push_str(ident.as_str());
+                    self.out.push(' ');
+                }
+                self.block(catch.next().unwrap());
+            }
+            Rule::Throw => {
+                self.out.push_str("shoot ");
+                self.expr(inner.next().unwrap());
+            }
+            Rule::Import => {
+                // The keyword is not in the tree
+                let keyword = if text.starts_with("bringme") {
+                    "bringme"
+                } else {
+                    "weneed"
+                };
+                self.out.push_str(keyword);
+                self.out.push(' ');
+
+                let first = inner.next().unwrap();
+                if first.as_rule() == Rule::StdImport {
+                    self.out.push_str(first.as_str());
+                } else {
+                    let path = [first].into_iter().chain(inner);
+                    let path = path.map(|x| x.as_str()).collect::<Vec<_>>();
+                    self.out.push_str("r/");
+                    self.out.push_str(&path.join("."));
+                }
+            }
+            Rule::Variable => {
+                for modifier in inner.next().unwrap().into_inner() {
+                    self.out.push_str(modifier.as_str().trim_end());
+                    self.out.push(' ');
+                }
+                self.out.push_str("meth ");
+                self.declaration(inner.next().unwrap());
+                self.out.push(' ');
+                self.operator(inner.next().unwrap());
+                self.out.push(' ');
+                self.expr(inner.next().unwrap());
+            }
+            Rule::AssignmentStatement => {
+                self.out.push_str(inner.next().unwrap().as_str());
+                self.out.push(' ');
+                self.operator(inner.next().unwrap());
+                self.out.push(' ');
+                self.expr(inner.next().unwrap());
+            }
+            Rule::IfBlock => {
+                for (i, node) in inner.enumerate() {
+                    if i > 0 {
+                        self.out.push(' ');
+                    }
+                    let rule = node.as_rule();
+                    let mut node = node.into_inner();
+                    match rule {
+                        Rule::If | Rule::ElseIf => {
+                            self.out
+                                .push_str(if rule == Rule::If { "is " } else { "but " });
+                            self.expr(node.next().unwrap());
+                            self.out.push(' ');
+                        }
+                        Rule::Else => self.out.push_str("isn't "),
+                        _ => bug!("INVALID_IFNODE({:?})", rule),
+                    }
+                    self.block(node.next().unwrap());
+                }
+            }
+            Rule::Class => {
+                self.out.push_str("school ");
+                self.out.push_str(inner.next().unwrap().as_str());
+                self.out.push(' ');
+                self.block(inner.next().unwrap());
+            }
+            Rule::Return => {
+                self.out.push_str("spez ");
+                self.expr(inner.next().unwrap());
+            }
+            _ => bug!("UNEXPECTED_STATEMENT({:?})", rule),
+        }
+    }
+
+    fn modifiers(&mut self, modifiers: Pair<Rule>) {
+        for modifier in modifiers.into_inner() {
+            self.out.push_str(modifier.as_str().trim_end());
+            self.out.push(' ');
+        }
+    }
+
+    /// `(a damn Number, b,)`
+    fn args(&mut self, args: Pair<Rule>) {
+        self.out.push('(');
+        for (i, arg) in args.into_inner().enumerate() {
+            if i > 0 {
+                self.out.push(' ');
+            }
+            self.declaration(arg);
+            self.out.push(',');
+        }
+        self.out.push(')');
+    }
+
+    fn declaration(&mut self, declaration: Pair<Rule>) {
+        let mut inner = declaration.into_inner();
+        self.out.push_str(inner.next().unwrap().as_str());
+        if let Some(r#type) = inner.next() {
+            self.out.push_str(" damn ");
+            self.r#type(r#type);
+        }
+    }
+
+    /// `Number`, `Number >> Array` or `(String, Number,) >> Dictionary`
+    fn r#type(&mut self, r#type: Pair<Rule>) {
+        let inner = r#type.into_inner().collect::<Vec<_>>();
+        let Some((root, generics)) = inner.split_last() else {
+            bug!("EMPTY_TYPE");
+        };
+
+        match generics {
+            [] => {}
+            [generic] if generic.as_rule() == Rule::Ident => {
+                self.out.push_str(generic.as_str());
+                self.out.push_str(" >> ");
+            }
+            generics => {
+                self.out.push('(');
+                for (i, generic) in generics.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push(' ');
+                    }
+                    self.r#type(generic.clone());
+                    self.out.push(',');
+                }
+                self.out.push_str(") >> ");
+            }
+        }
+        self.out.push_str(root.as_str());
+    }
+
+    /// `call name(a, b,)`
+    fn call(&mut self, mut inner: Pairs<Rule>) {
+        self.out.push_str("call ");
+        self.out.push_str(inner.next().unwrap().as_str());
+        self.out.push('(');
+        let args = inner.next().into_iter().flat_map(|x| x.into_inner());
+        for (i, arg) in args.enumerate() {
+            if i > 0 {
+                self.out.push(' ');
+            }
+            self.expr(arg);
+            self.out.push(',');
+        }
+        self.out.push(')');
+    }
+
+    fn expr(&mut self, expr: Pair<Rule>) {
+        self.term(expr.into_inner().next().unwrap());
+    }
+
+    /// Anything that can be inside of an `Expr`, an `Expr` in a term is parenthesized
+    fn term(&mut self, pair: Pair<Rule>) {
+        match pair.as_rule() {
+            Rule::Expr => {
+                self.out.push('(');
+                self.expr(pair);
+                self.out.push(')');
+            }
+            Rule::ConditionalExpr | Rule::BinaryExpr => {
+                for (i, part) in pair.into_inner().enumerate() {
+                    if i > 0 {
+                        self.out.push(' ');
+                    }
+                    self.term(part);
+                }
+            }
+            Rule::IndexExpr => {
+                let mut inner = pair.into_inner();
+                self.term(inner.next().unwrap());
+                self.out.push('[');
+                self.index(inner.next().unwrap());
+                self.out.push(']');
+            }
+            Rule::Call => self.call(pair.into_inner()),
+            Rule::Array => {
+                self.out.push('[');
+                for (i, item) in pair.into_inner().enumerate() {
+                    if i > 0 {
+                        self.out.push(' ');
+                    }
+                    self.expr(item);
+                    self.out.push(',');
+                }
+                self.out.push(']');
+            }
+            // Expressions in a string are formatted as well
+            Rule::String => {
+                let text = pair.as_str();
+                self.out.push_str(&text[..1]);
+                for part in pair.into_inner() {
+                    if part.as_rule() == Rule::Interpolation {
+                        self.out.push('{');
+                        self.expr(part.into_inner().next().unwrap());
+                        self.out.push('}');
+                    } else {
+                        self.out.push_str(part.as_str());
+                    }
+                }
+                self.out.push_str(&text[text.len() - 1..]);
+            }
+            Rule::MathOperator | Rule::ConditionalOperator => self.operator(pair),
+            // The sign of a number
+            Rule::Number | Rule::UNumber if self.unicode => {
+                for part in pair.into_inner() {
+                    self.operator(part);
+                }
+            }
+            // Literals and identifiers
+            _ => self.out.push_str(pair.as_str()),
+        }
+    }
+
+    /// An operator as it is written, or its Unicode operator with `unicode`
+    fn operator(&mut self, pair: Pair<Rule>) {
+        let text = pair.as_str();
+        self.out.push_str(match operators::canonical(text) {
+            Some(operator) if self.unicode => operator,
+            _ => text,
+        });
+    }
+
+    /// An `Index` in an `Index` is parenthesized
+    fn index(&mut self, index: Pair<Rule>) {
+        let inner = index.into_inner().next().unwrap();
+        if inner.as_rule() == Rule::Index {
+            self.out.push('(');
+            self.index(inner);
+            self.out.push(')');
+        } else {
+            self.term(inner);
+        }
+    }
+}
+
+/// Ranges of the blocks of a statement, without the blocks inside of them
+fn blocks(pair: Pair<Rule>) -> Vec<Range<usize>> {
+    pair.into_inner()
+        .flat_map(|x| match x.as_rule() {
+            Rule::Block => {
+                let span = x.as_span();
+                vec![Range {
+                    start: span.start(),
+                    end: span.end(),
+                }]
+            }
+            _ => blocks(x),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_source;
+    use crate::parser::cst::tests::sources;
+
+    fn format(source: &str) -> String {
+        format_source(source, false).unwrap()
+    }
+
+    #[test]
+    fn keeps_comments_in_interpolations() {
+        let formatted = format("meth s ∑ \"a{x #* keep me *#}b\"\n");
+        assert_eq!(formatted, "#* keep me *#\nmeth s ∑ \"a{x}b\"\n");
+        assert_eq!(format(&formatted), formatted);
+    }
+
+    #[test]
+    fn moves_comments_in_conditions_above_the_statement() {
+        let source = "is x ⅀ 1 {\n} but x #* c *# ⅀ 2 {\n    sthu # end\n} isn't {}\n";
+        let formatted = format(source);
+        assert_eq!(
+            formatted,
+            "#* c *#\nis x ⅀ 1 {} but x ⅀ 2 {\n    sthu # end\n} isn't {}\n"
+        );
+        assert_eq!(format(&formatted), formatted);
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let source = "# top\nmeth   x∑[1,2,]   # trailing\n\n\n\nbar callmeonmycellphone f(a,b damn Number,){\n#* inside *#\nspez a⨋b\n}\ntest{shoot \"x\"}wall e{call coitusinterruptus(e,)}\n";
+        let formatted = format(source);
+        assert_eq!(format(&formatted), formatted);
+        assert!(formatted.contains("meth x ∑ [1, 2,] # trailing\n\nbar"));
+        assert!(formatted.contains("{\n    #* inside *#\n    spez a ⨋ b\n}"));
+    }
+
+    #[test]
+    fn examples_are_idempotent() {
+        for (name, source) in sources("examples").iter().chain(&sources("tests/programs")) {
+            for unicode in [false, true] {
+                let formatted =
+                    format_source(source, unicode).unwrap_or_else(|x| panic!("{}: {}", name, x));
+                let again = format_source(&formatted, unicode).unwrap();
+                assert_eq!(again, formatted, "formatting {} is not idempotent", name);
+            }
+        }
+    }
+}
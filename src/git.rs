@@ -1,77 +1,50 @@
-use git2::{Direction, Repository, ResetType};
+use git2::{Oid, Repository};
 use std::{fs, path::Path};
 
 use crate::utils::Result;
 
-pub fn fetch(repo: &Repository, remote: &str, branch: &str) -> Result<()> {
-    Ok(repo.find_remote(remote)?.fetch(&[branch], None, None)?)
-}
-
-pub fn hard_reset(repo: &Repository, remote: &str, branch: &str) -> Result<()> {
-    let remote_branch_ref = repo.refname_to_id(&format!("refs/remotes/{}/{}", remote, branch))?;
-    let object = repo.find_object(remote_branch_ref, None)?;
-    repo.reset(&object, ResetType::Hard, None)?;
-    Ok(())
-}
-
-fn pull(repo: &Repository, remote: &str, branch: &str) -> Result<()> {
-    fetch(&repo, remote, branch)?;
-    hard_reset(&repo, remote, branch)?;
-    Ok(())
-}
-
-pub fn clone_else_pull<P: AsRef<Path>>(url: &str, into: P, branch: &str) -> Result<()> {
-    let cloned = into.as_ref().try_exists()?;
-    if cloned {
-        let repo = Repository::open(&into)?;
-        pull(&repo, "origin", &branch)?;
-    } else {
-        Repository::clone(&url, &into)?;
-    }
-    Ok(())
-}
-
-/// Update a repository to the latest commit, if it is not cloned, do so. if it is cloned, compare
-/// remote and local hashes to see if it is up to date, if it is not, pull. Returns a value that
-/// indicates if the repository is up to date.
-pub fn update<P: AsRef<Path>>(url: &str, into: P, branch: &str) -> Result<bool> {
-    let cloned = into.as_ref().try_exists()?;
-    if cloned {
+/// Clones the repository if it is not cloned yet, otherwise fetches it. Then checks out `rev`,
+/// which can be a branch, a tag or a commit, and returns the commit it points to.
+pub fn checkout_rev<P: AsRef<Path>>(url: &str, into: P, rev: &str) -> Result<Oid> {
+    let repo = if into.as_ref().try_exists()? {
         let repo = Repository::open(&into)?;
-
-        let local = repo.head()?;
-        let local_hash = local.peel_to_commit()?.id();
-
-        let mut remote = repo.find_remote("origin")?;
-        let connection = remote.connect_auth(Direction::Fetch, None, None)?;
-
-        let remote_hash = connection.list()?[0].oid();
-
-        if local_hash != remote_hash {
-            pull(&repo, "origin", &branch)?;
-        }
-        Ok(local_hash == remote_hash)
+        repo.find_remote("origin")?
+            .fetch(&[] as &[&str], None, None)?;
+        repo
     } else {
-        Repository::clone(&url, &into)?;
-        Ok(false)
-    }
+        Repository::clone(url, &into)?
+    };
+
+    // Branches are checked first so `main` is the remote branch, not a stale local one
+    let object = [
+        format!("refs/remotes/origin/{}", rev),
+        format!("refs/tags/{}", rev),
+    ]
+    .iter()
+    .find_map(|x| repo.revparse_single(x).ok())
+    .map_or_else(|| repo.revparse_single(rev), Ok)?;
+
+    let commit = object.peel_to_commit()?.id();
+    repo.set_head_detached(commit)?;
+    repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
+    Ok(commit)
 }
 
 pub fn checkout(repo: &Repository, refname: &str) -> Result<()> {
-    repo.set_head(&refname)?;
+    repo.set_head(refname)?;
     repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
     Ok(())
 }
 
 /// Makes a new repository with the content of the repository at `url`
 pub fn generate<P: AsRef<Path>>(url: &str, branch: Option<&str>, into: P) -> Result<()> {
-    let repo = Repository::clone(&url, &into)?;
+    let repo = Repository::clone(url, &into)?;
 
     if let Some(branch) = branch {
         checkout(&repo, branch)?;
     }
 
-    fs::remove_dir_all(&into.as_ref().join(".git"))?;
+    fs::remove_dir_all(into.as_ref().join(".git"))?;
     Repository::init(&into)?;
     Ok(())
 }
@@ -0,0 +1,467 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+    rc::Rc,
+};
+
+use crate::{
+    bug,
+    parser::{ConditionalOperator, FunctionMod, MathOperator},
+    semantic::{
+        Callee, Local, Program, TypedCall, TypedExpr, TypedExprKind, TypedFunction, TypedModule,
+        TypedNode, TypedTree, ValidType, VariableRef, MAIN_MODULE,
+    },
+    utils::Result,
+};
+
+pub mod stdlib;
+
+/// A RedditLang value, `wat` strings and arrays are None
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Boolean(bool),
+    String(Option<Rc<str>>),
+    Array(Option<Rc<[Value]>>),
+}
+
+impl Value {
+    /// Value of a variable before it is set, same as the zero initialized values of codegen
+    pub fn zero(r#type: &ValidType) -> Self {
+        match r#type {
+            ValidType::Number => Value::Number(0.0),
+            ValidType::Boolean => Value::Boolean(false),
+            ValidType::String => Value::String(None),
+            ValidType::Array(_) => Value::Array(None),
+            ValidType::Infer(_) => bug!("UNRESOLVED_TYPE({:?})", r#type),
+        }
+    }
+
+    pub fn number(&self) -> f64 {
+        match self {
+            Value::Number(x) => *x,
+            _ => bug!("NOT_A_NUMBER({:?})", self),
+        }
+    }
+
+    pub fn boolean(&self) -> bool {
+        match self {
+            Value::Boolean(x) => *x,
+            _ => bug!("NOT_A_BOOLEAN({:?})", self),
+        }
+    }
+
+    pub fn string(&self) -> Option<&str> {
+        match self {
+            Value::String(x) => x.as_deref(),
+            _ => bug!("NOT_A_STRING({:?})", self),
+        }
+    }
+
+    /// `wat` arrays have no elements
+    pub fn array(&self) -> &[Value] {
+        match self {
+            Value::Array(x) => x.as_deref().unwrap_or_default(),
+            _ => bug!("NOT_AN_ARRAY({:?})", self),
+        }
+    }
+}
+
+/// Why execution stops before the end of a statement list
+pub enum Unwind {
+    /// A bullet that was shot, None if it is `wat`
+    Bullet(Option<Rc<str>>),
+    /// `exit` was called
+    Exit(i32),
+}
+
+pub type Exec<T> = std::result::Result<T, Unwind>;
+
+/// What happens after a statement
+enum Flow {
+    Next,
+    Break,
+    Return(Value),
+}
+
+/// Locals of the function that is running
+struct Frame<'a> {
+    /// Indexed by `LocalId`
+    locals: Vec<Value>,
+    /// Set if the function is a traced `debug` function
+    trace: Option<Trace<'a>>,
+    /// Line of the current statement
+    line: usize,
+}
+
+/// What a `debug` function prints when one of its variables changes
+struct Trace<'a> {
+    file: String,
+    locals: &'a [Local],
+}
+
+/// Runs analyzed programs without compiling them
+pub struct Interpreter<'a> {
+    functions: HashMap<&'a str, &'a TypedFunction>,
+    /// Modules by the symbol of their entry
+    entries: HashMap<&'a str, &'a TypedModule>,
+    /// Entries that already ran, every import runs the entry of the imported module but it
+    /// should only run once
+    initialized: HashSet<&'a str>,
+    /// Module level variables by symbol
+    pub globals: HashMap<String, Value>,
+    /// If `debug` functions print their variables, like debug builds
+    trace: bool,
+}
+
+/// Runs the `main` of a program and returns its exit code
+pub fn run(program: &Program, trace: bool) -> Result<i32> {
+    let main = program
+        .modules
+        .iter()
+        .find(|x| x.name == MAIN_MODULE)
+        .unwrap_or_else(|| bug!("NO_MAIN_MODULE"));
+
+    let mut interpreter = Interpreter::new(program, trace);
+    let code = match interpreter.run_entry(main) {
+        Ok(code) => code,
+        Err(Unwind::Exit(code)) => code,
+        Err(Unwind::Bullet(bullet)) => {
+            io::stdout().flush()?;
+            eprintln!("Uncaught bullet: {}", bullet.as_deref().unwrap_or("wat"));
+            1
+        }
+    };
+    io::stdout().flush()?;
+
+    Ok(code)
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(program: &'a Program, trace: bool) -> Self {
+        let modules = &program.modules;
+        Self {
+            functions: modules
+                .iter()
+                .flat_map(|x| &x.functions)
+                .map(|x| (x.symbol.as_str(), x))
+                .collect(),
+            entries: modules.iter().map(|x| (x.entry.as_str(), x)).collect(),
+            initialized: HashSet::new(),
+            globals: modules
+                .iter()
+                .flat_map(|x| &x.globals)
+                .map(|x| (x.symbol.clone(), Value::zero(&x.r#type)))
+                .collect(),
+            trace,
+        }
+    }
+
+    /// Runs the top level statements of a module and returns the exit code, only the first call
+    /// for a module runs them
+    pub fn run_entry(&mut self, module: &'a TypedModule) -> Exec<i32> {
+        if !self.initialized.insert(&module.entry) {
+            return Ok(0);
+        }
+
+        let mut frame = Frame {
+            locals: module
+                .locals
+                .iter()
+                .map(|x| Value::zero(&x.r#type))
+                .collect(),
+            trace: None,
+            line: 1,
+        };
+        match self.exec(&module.body, &mut frame)? {
+            Flow::Return(code) => Ok(code.number() as i32),
+            _ => Ok(0),
+        }
+    }
+
+    /// None if the function does not return a value
+    fn call_function(
+        &mut self,
+        function: &'a TypedFunction,
+        args: Vec<Value>,
+    ) -> Exec<Option<Value>> {
+        let mut locals = function
+            .locals
+            .iter()
+            .map(|x| Value::zero(&x.r#type))
+            .collect::<Vec<_>>();
+        for (param, value) in function.params.iter().zip(args) {
+            locals[param.0] = value;
+        }
+
+        let is_debug = function
+            .modifiers
+            .iter()
+            .any(|x| matches!(x, FunctionMod::Debug));
+        let trace = (self.trace && is_debug).then(|| Trace {
+            file: self
+                .functions_module(function)
+                .path
+                .file_name()
+                .map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            locals: &function.locals,
+        });
+
+        let mut frame = Frame {
+            locals,
+            trace,
+            line: function.span.line,
+        };
+        match self.exec(&function.body, &mut frame)? {
+            Flow::Return(value) => Ok(Some(value)),
+            // Falling off the end of a function returns `wat`
+            _ => Ok(function.signature.ret.as_ref().map(Value::zero)),
+        }
+    }
+
+    fn functions_module(&self, function: &TypedFunction) -> &'a TypedModule {
+        self.entries
+            .values()
+            .find(|x| x.functions.iter().any(|x| x.symbol == function.symbol))
+            .unwrap_or_else(|| bug!("FUNCTION_WITHOUT_MODULE({})", function.symbol))
+    }
+
+    fn exec(&mut self, tree: &'a TypedTree, frame: &mut Frame<'a>) -> Exec<Flow> {
+        for node in tree {
+            frame.line = node.span.line;
+
+            match &node.node {
+                TypedNode::Variable(variable, value) | TypedNode::Assignment(variable, value) => {
+                    let value = self.eval(value, frame)?;
+                    if let Some(trace) = &frame.trace {
+                        trace_variable(trace, frame.line, variable, &value);
+                    }
+                    self.store(frame, variable, value);
+                }
+                TypedNode::Loop(body) => loop {
+                    match self.exec(body, frame)? {
+                        Flow::Next => {}
+                        Flow::Break => break,
+                        flow @ Flow::Return(_) => return Ok(flow),
+                    }
+                },
+                TypedNode::Break => return Ok(Flow::Break),
+                TypedNode::If(cases, r#else) => {
+                    let mut body = r#else.as_ref();
+                    for (condition, case) in cases {
+                        if self.eval(condition, frame)?.boolean() {
+                            body = Some(case);
+                            break;
+                        }
+                    }
+
+                    if let Some(body) = body {
+                        match self.exec(body, frame)? {
+                            Flow::Next => {}
+                            flow => return Ok(flow),
+                        }
+                    }
+                }
+                TypedNode::Call(call) => {
+                    self.call(call, frame)?;
+                }
+                TypedNode::Return(value) => return Ok(Flow::Return(self.eval(value, frame)?)),
+                TypedNode::Throw(bullet) => {
+                    let bullet = self.eval(bullet, frame)?;
+                    return Err(Unwind::Bullet(bullet.string().map(Rc::from)));
+                }
+                TypedNode::TryCatch(r#try, variable, catch) => {
+                    let flow = match self.exec(r#try, frame) {
+                        Err(Unwind::Bullet(bullet)) => {
+                            if let Some(variable) = variable {
+                                self.store(frame, variable, Value::String(bullet));
+                            }
+                            self.exec(catch, frame)?
+                        }
+                        x => x?,
+                    };
+                    if !matches!(flow, Flow::Next) {
+                        return Ok(flow);
+                    }
+                }
+                TypedNode::Import(entry) => {
+                    let module = self
+                        .entries
+                        .get(entry.as_str())
+                        .unwrap_or_else(|| bug!("UNKNOWN_ENTRY({})", entry));
+                    self.run_entry(module)?;
+                }
+            }
+        }
+
+        Ok(Flow::Next)
+    }
+
+    fn store(&mut self, frame: &mut Frame, variable: &VariableRef, value: Value) {
+        match variable {
+            VariableRef::Local(id) => frame.locals[id.0] = value,
+            VariableRef::Global(symbol) => {
+                self.globals.insert(symbol.clone(), value);
+            }
+        }
+    }
+
+    fn load(&self, frame: &Frame, variable: &VariableRef) -> Value {
+        match variable {
+            VariableRef::Local(id) => frame.locals[id.0].clone(),
+            VariableRef::Global(symbol) => self
+                .globals
+                .get(symbol)
+                .cloned()
+                .unwrap_or_else(|| bug!("UNKNOWN_GLOBAL({})", symbol)),
+        }
+    }
+
+    /// None if the function does not return a value
+    fn call(&mut self, call: &'a TypedCall, frame: &mut Frame<'a>) -> Exec<Option<Value>> {
+        let args = call
+            .args
+            .iter()
+            .map(|x| self.eval(x, frame))
+            .collect::<Exec<Vec<_>>>()?;
+
+        match &call.callee {
+            Callee::Function(symbol) => {
+                let function = self
+                    .functions
+                    .get(symbol.as_str())
+                    .unwrap_or_else(|| bug!("UNKNOWN_FUNCTION({})", symbol));
+                self.call_function(function, args)
+            }
+            Callee::Std(name) => stdlib::call(name, args),
+            // C functions can't be called without compiling the program
+            Callee::Extern(symbol) => Err(Unwind::Bullet(Some(Rc::from(format!(
+                "{} is outsourced, outsourced functions can't be called by the interpreter",
+                symbol
+            ))))),
+        }
+    }
+
+    fn eval(&mut self, expr: &'a TypedExpr, frame: &mut Frame<'a>) -> Exec<Value> {
+        Ok(match &expr.kind {
+            TypedExprKind::Number(x) => Value::Number(*x),
+            TypedExprKind::String(x) => Value::String(Some(Rc::from(x.as_str()))),
+            TypedExprKind::Boolean(x) => Value::Boolean(*x),
+            TypedExprKind::Null => Value::zero(&expr.r#type),
+            TypedExprKind::Array(items) => Value::Array(Some(
+                items
+                    .iter()
+                    .map(|x| self.eval(x, frame))
+                    .collect::<Exec<Rc<[Value]>>>()?,
+            )),
+            TypedExprKind::Variable(variable) => self.load(frame, variable),
+            TypedExprKind::Call(call) => self
+                .call(call, frame)?
+                .unwrap_or_else(|| bug!("VOID_CALL_EXPR({:?})", call.callee)),
+            // `wat` is joined as `wat`, like in a string
+            TypedExprKind::Binary(lhs, _, rhs) if expr.r#type == ValidType::String => {
+                let lhs = self.eval(lhs, frame)?;
+                let rhs = self.eval(rhs, frame)?;
+                Value::String(Some(Rc::from(
+                    [lhs.string().unwrap_or("wat"), rhs.string().unwrap_or("wat")].concat(),
+                )))
+            }
+            TypedExprKind::Binary(lhs, operator, rhs) => {
+                let lhs = self.eval(lhs, frame)?.number();
+                let rhs = self.eval(rhs, frame)?.number();
+
+                Value::Number(match operator {
+                    MathOperator::Add => lhs + rhs,
+                    MathOperator::Subtract => lhs - rhs,
+                    MathOperator::Multiply => lhs * rhs,
+                    MathOperator::Divide => lhs / rhs,
+                    MathOperator::XOR => ((lhs as i64) ^ (rhs as i64)) as f64,
+                    MathOperator::Modulus => lhs % rhs,
+                })
+            }
+            TypedExprKind::Conditional(lhs, operator, rhs) => {
+                let lhs = self.eval(lhs, frame)?;
+                let rhs = self.eval(rhs, frame)?;
+
+                Value::Boolean(match (&lhs, &rhs) {
+                    (Value::Number(lhs), Value::Number(rhs)) => match operator {
+                        ConditionalOperator::Equality => lhs == rhs,
+                        ConditionalOperator::AntiEquality => lhs != rhs,
+                        ConditionalOperator::GreaterThan => lhs > rhs,
+                        ConditionalOperator::GreaterThanOrEqual => lhs >= rhs,
+                        ConditionalOperator::LessThan => lhs < rhs,
+                        ConditionalOperator::LessThanOrEqual => lhs <= rhs,
+                    },
+                    // `wat` is before every other string
+                    (Value::String(lhs), Value::String(rhs)) => match operator {
+                        ConditionalOperator::Equality => lhs == rhs,
+                        ConditionalOperator::AntiEquality => lhs != rhs,
+                        ConditionalOperator::GreaterThan => lhs > rhs,
+                        ConditionalOperator::GreaterThanOrEqual => lhs >= rhs,
+                        ConditionalOperator::LessThan => lhs < rhs,
+                        ConditionalOperator::LessThanOrEqual => lhs <= rhs,
+                    },
+                    (Value::Boolean(_), Value::Boolean(_)) => match operator {
+                        ConditionalOperator::Equality => lhs == rhs,
+                        _ => lhs != rhs,
+                    },
+                    _ => bug!("INVALID_COMPARISON({:?})", operator),
+                })
+            }
+            TypedExprKind::Interpolation(parts) => {
+                let mut text = String::new();
+                for part in parts {
+                    match self.eval(part, frame)? {
+                        Value::Number(x) => text.push_str(&stdlib::format_number(x)),
+                        Value::Boolean(x) => text.push_str(if x { "Yup" } else { "Nope" }),
+                        Value::String(x) => text.push_str(x.as_deref().unwrap_or("wat")),
+                        x => bug!("INVALID_INTERPOLATION({:?})", x),
+                    }
+                }
+                Value::String(Some(Rc::from(text)))
+            }
+            TypedExprKind::Index(array, index) => {
+                let array = self.eval(array, frame)?;
+                let index = self.eval(index, frame)?.number();
+
+                // Array indexes start with 1
+                let elements = array.array();
+                let element = (index as i64)
+                    .checked_sub(1)
+                    .and_then(|x| usize::try_from(x).ok())
+                    .and_then(|x| elements.get(x));
+                match element {
+                    Some(x) => x.clone(),
+                    None => {
+                        return Err(Unwind::Bullet(Some(Rc::from(format!(
+                            "Index {} is out of bounds of an array with {} elements",
+                            stdlib::format_number(index),
+                            elements.len()
+                        )))))
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Prints the new value of a variable of a `debug` function, ex. `[main.rl:4] x = 5`
+fn trace_variable(trace: &Trace, line: usize, variable: &VariableRef, value: &Value) {
+    let name = match variable {
+        VariableRef::Local(id) => &trace.locals[id.0].name,
+        // `<module>::meth::<name>`
+        VariableRef::Global(symbol) => symbol.rsplit("::").next().unwrap_or(symbol),
+    };
+    let value = match value {
+        Value::Number(x) => stdlib::format_number(*x),
+        Value::Boolean(x) => if *x { "Yup" } else { "Nope" }.to_string(),
+        Value::String(Some(x)) => format!("\"{}\"", x),
+        Value::String(None) => "wat".to_string(),
+        // Only the length of arrays is printed
+        Value::Array(x) => format!("[{} elements]", x.as_deref().map_or(0, <[_]>::len)),
+    };
+
+    let _ = io::stdout().flush();
+    eprintln!("[{}:{}] {} = {}", trace.file, line, name, value);
+}
@@ -0,0 +1,183 @@
+use std::{
+    io::{self, BufRead, Write},
+    rc::Rc,
+    thread,
+    time::Duration,
+};
+
+use crate::bug;
+
+use super::{Exec, Unwind, Value};
+
+/// Calls a standard library function, they behave like the ones of the runtime. None if the
+/// function does not return a value
+pub fn call(name: &str, args: Vec<Value>) -> Exec<Option<Value>> {
+    let arg = |i: usize| {
+        args.get(i)
+            .unwrap_or_else(|| bug!("MISSING_STD_ARG({}, {})", name, i))
+    };
+
+    Ok(match name {
+        "coitusinterruptus" => {
+            println!("{}", arg(0).string().unwrap_or("wat"));
+            None
+        }
+        "pulloutnt" => Some(Value::String(Some(Rc::from(read_line())))),
+        "nums" => Some(Value::String(Some(Rc::from(format_number(
+            arg(0).number(),
+        ))))),
+        "unnums" => Some(Value::Number(parse_number(arg(0).string())?)),
+        // `wat` is an empty string for these
+        "length" => Some(Value::Number(
+            arg(0).string().unwrap_or_default().chars().count() as f64,
+        )),
+        "substring" => Some(Value::String(Some(Rc::from(substring(
+            arg(0).string().unwrap_or_default(),
+            arg(1).number(),
+            arg(2).number(),
+        )?)))),
+        "split" => {
+            let separator = arg(1).string().unwrap_or_default();
+            if separator.is_empty() {
+                return Err(bullet("Cannot split with an empty separator"));
+            }
+            let parts = arg(0)
+                .string()
+                .unwrap_or_default()
+                .split(separator)
+                .map(|x| Value::String(Some(Rc::from(x))))
+                .collect();
+            Some(Value::Array(Some(parts)))
+        }
+        "contains" => Some(Value::Boolean(
+            arg(0)
+                .string()
+                .unwrap_or_default()
+                .contains(arg(1).string().unwrap_or_default()),
+        )),
+        "exit" => return Err(Unwind::Exit(arg(0).number() as i32)),
+        "zzz" => {
+            let time_ms = arg(0).number();
+            if time_ms > 0.0 {
+                thread::sleep(Duration::from_secs_f64(time_ms / 1000.0));
+            }
+            None
+        }
+        _ => bug!("UNKNOWN_STD_FUNCTION({})", name),
+    })
+}
+
+/// A line of stdin without the line break
+fn read_line() -> String {
+    let _ = io::stdout().flush();
+
+    let mut line = vec![];
+    let _ = io::stdin().lock().read_until(b'\n', &mut line);
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    String::from_utf8_lossy(&line).into_owned()
+}
+
+fn parse_number(text: Option<&str>) -> Exec<f64> {
+    let text = text.unwrap_or_default();
+    if text.is_empty() {
+        return Err(bullet("Cannot convert an empty string to a Number"));
+    }
+
+    // Like `strtod`, which skips leading whitespace of the C locale
+    text.trim_start_matches([' ', '\t', '\n', '\x0b', '\x0c', '\r'])
+        .parse()
+        .map_err(|_| bullet(&format!("Cannot convert \"{}\" to a Number", text)))
+}
+
+/// `count` characters from the character at `start`, the first character is at 1
+fn substring(text: &str, start: f64, count: f64) -> Exec<String> {
+    let length = text.chars().count();
+    // Checked before casting, so huge numbers, infinities and NaN are out of bounds too
+    let (first, taken) = (start.trunc(), count.trunc());
+    let in_bounds = first >= 1.0 && taken >= 0.0 && first - 1.0 + taken <= length as f64;
+    if !in_bounds {
+        return Err(bullet(&format!(
+            "Substring of {} characters at {} is out of bounds of a string with {} characters",
+            format_number(count),
+            format_number(start),
+            length
+        )));
+    }
+    Ok(text
+        .chars()
+        .skip(first as usize - 1)
+        .take(taken as usize)
+        .collect())
+}
+
+fn bullet(text: &str) -> Unwind {
+    Unwind::Bullet(Some(Rc::from(text)))
+}
+
+/// Shortest representation that converts back to the same number, same as `%g` of the C runtime
+pub fn format_number(number: f64) -> String {
+    if number.is_nan() {
+        return if number.is_sign_negative() {
+            "-nan"
+        } else {
+            "nan"
+        }
+        .to_string();
+    }
+    if number.is_infinite() {
+        return if number < 0.0 { "-inf" } else { "inf" }.to_string();
+    }
+
+    let text = (1..=17)
+        .map(|precision| format_g(number, precision))
+        .find(|x| x.parse() == Ok(number))
+        .unwrap_or_else(|| format_g(number, 17));
+
+    // The shortest precision can be too short for the integer digits, ex. 2e+01 for 20
+    let digits = text.split_once('e').map_or(0, |(_, exponent)| {
+        exponent.parse::<i32>().unwrap_or_default() + 1
+    });
+    if digits > 0 && digits <= 17 {
+        format_g(number, digits as usize)
+    } else {
+        text
+    }
+}
+
+/// `%.<precision>g` of C
+fn format_g(number: f64, precision: usize) -> String {
+    // The exponent after rounding to `precision` digits decides the notation
+    let scientific = format!("{:.*e}", precision - 1, number);
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .unwrap_or_else(|| bug!("NO_EXPONENT({})", scientific));
+    let exponent = exponent
+        .parse::<i32>()
+        .unwrap_or_else(|_| bug!("INVALID_EXPONENT({})", scientific));
+
+    if exponent < -4 || exponent >= precision as i32 {
+        format!(
+            "{}e{}{:02}",
+            trim_zeros(mantissa),
+            if exponent < 0 { '-' } else { '+' },
+            exponent.abs()
+        )
+    } else {
+        let decimals = (precision as i32 - 1 - exponent) as usize;
+        trim_zeros(&format!("{:.*}", decimals, number)).to_string()
+    }
+}
+
+/// `%g` removes trailing zeros of the fraction
+fn trim_zeros(text: &str) -> &str {
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        text
+    }
+}
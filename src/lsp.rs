@@ -0,0 +1,623 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+        LogMessage, Notification as _, PublishDiagnostics,
+    },
+    request::{Completion, Formatting, GotoDefinition, HoverRequest, Request as _},
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DidSaveTextDocumentParams, DocumentFormattingParams,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, Location, LogMessageParams, MarkupContent, MarkupKind, MessageType,
+    OneOf, Position, PublishDiagnosticsParams, Range, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url,
+};
+use pest::error::InputLocation;
+
+use crate::{
+    doc::function_signature,
+    formatter::format_source,
+    parser::{FunctionMod, Ident, IfNode, Import, Node, Span, Tree, VariableMod},
+    project::{load_modules, module_name, Project, SourceModule},
+    semantic::{
+        analyze_references,
+        stdlib::{PRELUDE, STD_FUNCTIONS},
+        Reference, ReferenceKind, MAIN_MODULE,
+    },
+    utils::Result,
+    Rule,
+};
+
+/// Keywords offered by completion
+const KEYWORDS: &[&str] = &[
+    "meth",
+    "callmeonmycellphone",
+    "outsourced",
+    "call",
+    "spez",
+    "repeatdatshid",
+    "sthu",
+    "is",
+    "but",
+    "isn't",
+    "test",
+    "wall",
+    "shoot",
+    "weneed",
+    "bringme",
+    "school",
+    "bar",
+    "debug",
+    "damn",
+    "wat",
+    "Yup",
+    "Nope",
+];
+
+/// Runs a language server on stdin and stdout until the editor shuts it down
+pub fn run() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions::default()),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    Server::default().main_loop(&connection)?;
+    // The threads stop once the connection is gone
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+#[derive(Default)]
+struct Server {
+    /// Text of the open documents, it can be newer than the files
+    documents: HashMap<PathBuf, String>,
+    /// Last analysis of every root module, by the path of its file
+    analyses: HashMap<PathBuf, Analysis>,
+}
+
+/// A root module and every module it imports
+struct Analysis {
+    src_dir: PathBuf,
+    /// Modules of the last time they could be parsed
+    modules: Vec<SourceModule>,
+    references: Vec<Reference>,
+    /// Files that have diagnostics in the editor
+    published: HashSet<PathBuf>,
+}
+
+impl Server {
+    fn main_loop(&mut self, connection: &Connection) -> Result<()> {
+        for message in &connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.request(request);
+                    connection.sender.send(Message::Response(response))?;
+                }
+                Message::Notification(notification) => {
+                    // A bad notification should not stop the server
+                    if let Err(error) = self.notification(connection, notification) {
+                        log(connection, error.to_string())?;
+                    }
+                }
+                Message::Response(_) => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn request(&self, request: Request) -> Response {
+        let id = request.id.clone();
+        let method = request.method.clone();
+
+        match self.result(request) {
+            Ok(Some(result)) => Response::new_ok(id, result),
+            Ok(None) => Response::new_err(
+                id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unknown request `{}`", method),
+            ),
+            Err(error) => Response::new_err(id, ErrorCode::InvalidParams as i32, error.to_string()),
+        }
+    }
+
+    /// None if the request is not supported
+    fn result(&self, request: Request) -> serde_json::Result<Option<serde_json::Value>> {
+        let params = request.params;
+        let result = match request.method.as_str() {
+            HoverRequest::METHOD => {
+                serde_json::to_value(self.hover(serde_json::from_value(params)?))
+            }
+            GotoDefinition::METHOD => {
+                serde_json::to_value(self.definition(serde_json::from_value(params)?))
+            }
+            Completion::METHOD => {
+                serde_json::to_value(self.completion(serde_json::from_value(params)?))
+            }
+            Formatting::METHOD => {
+                serde_json::to_value(self.formatting(serde_json::from_value(params)?))
+            }
+            _ => return Ok(None),
+        };
+        result.map(Some)
+    }
+
+    fn notification(&mut self, connection: &Connection, notification: Notification) -> Result<()> {
+        let params = notification.params;
+        let path = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams = serde_json::from_value(params)?;
+                let path = file_path(&params.text_document.uri)?;
+                self.documents
+                    .insert(path.clone(), params.text_document.text);
+                path
+            }
+            DidChangeTextDocument::METHOD => {
+                let mut params: DidChangeTextDocumentParams = serde_json::from_value(params)?;
+                let path = file_path(&params.text_document.uri)?;
+                // Documents are synced in full, so a change is the whole text
+                if let Some(change) = params.content_changes.pop() {
+                    self.documents.insert(path.clone(), change.text);
+                }
+                path
+            }
+            DidSaveTextDocument::METHOD => {
+                let params: DidSaveTextDocumentParams = serde_json::from_value(params)?;
+                file_path(&params.text_document.uri)?
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams = serde_json::from_value(params)?;
+                self.documents
+                    .remove(&file_path(&params.text_document.uri)?);
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+
+        self.update(connection, &path)
+    }
+
+    /// Analyzes the project of a file again
+    fn update(&mut self, connection: &Connection, path: &Path) -> Result<()> {
+        let project = path
+            .ancestors()
+            .skip(1)
+            .find(|x| x.join("walter.yml").is_file());
+        if let Some(project) = project {
+            let src_dir = project.join("src");
+            // An invalid walter.yml is reported when building, not here
+            let ascii_operators = Project::from_path(project)
+                .map(|x| x.config.ascii_operators)
+                .unwrap_or(false);
+            self.analyze(
+                connection,
+                &src_dir,
+                Path::new(MAIN_MODULE),
+                ascii_operators,
+            )?;
+            if self.module(path).is_some() {
+                return Ok(());
+            }
+
+            // Modules that are not imported by main are analyzed on their own
+            if let Ok(relative) = path.strip_prefix(&src_dir) {
+                return self.analyze(
+                    connection,
+                    &src_dir,
+                    &relative.with_extension(""),
+                    ascii_operators,
+                );
+            }
+        }
+
+        // A file that is not part of a project
+        let src_dir = path.parent().unwrap_or(Path::new(""));
+        let root = path.file_stem().unwrap_or_default();
+        self.analyze(connection, src_dir, Path::new(root), false)
+    }
+
+    /// Analyzes a root module and publishes the diagnostics of every module it imports
+    fn analyze(
+        &mut self,
+        connection: &Connection,
+        src_dir: &Path,
+        root: &Path,
+        ascii_operators: bool,
+    ) -> Result<()> {
+        let documents = &self.documents;
+        let read = |path: &Path| match documents.get(path) {
+            Some(x) => Ok(x.clone()),
+            None => fs::read_to_string(path),
+        };
+
+        let analysis = self
+            .analyses
+            .entry(src_dir.join(root.with_extension("rl")))
+            .or_insert_with(|| Analysis {
+                src_dir: src_dir.to_path_buf(),
+                modules: vec![],
+                references: vec![],
+                published: HashSet::new(),
+            });
+
+        let mut diagnostics: HashMap<PathBuf, Vec<lsp_types::Diagnostic>> = HashMap::new();
+        match load_modules(src_dir, root, ascii_operators, read) {
+            Ok(modules) => {
+                let (problems, references) = analyze_references(&modules);
+                for problem in problems {
+                    let Some(module) = modules.iter().find(|x| x.name == problem.module) else {
+                        continue;
+                    };
+                    let span = problem.span;
+                    diagnostics
+                        .entry(module.path.clone())
+                        .or_default()
+                        .push(diagnostic(
+                            &module.source,
+                            span.start,
+                            span.end,
+                            problem.message,
+                        ));
+                }
+
+                analysis.modules = modules;
+                analysis.references = references;
+            }
+            Err(error) => match error.downcast::<pest::error::Error<Rule>>() {
+                Ok(error) => {
+                    let path = PathBuf::from(error.path().unwrap_or_default());
+                    let source = read(&path).unwrap_or_default();
+                    let (start, end) = match error.location {
+                        InputLocation::Pos(x) => (x, x),
+                        InputLocation::Span(x) => x,
+                    };
+                    let message = error.variant.message().to_string();
+                    diagnostics.insert(path, vec![diagnostic(&source, start, end, message)]);
+                }
+                // Ex. an imported module that does not exist
+                Err(error) => log(connection, error.to_string())?,
+            },
+        }
+
+        // Files that had diagnostics and now have none are cleared
+        for path in analysis.published.drain() {
+            diagnostics.entry(path).or_default();
+        }
+        for (path, diagnostics) in diagnostics {
+            if !diagnostics.is_empty() {
+                analysis.published.insert(path.clone());
+            }
+            let Ok(uri) = Url::from_file_path(&path) else {
+                continue;
+            };
+            let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+            let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+            connection
+                .sender
+                .send(Message::Notification(notification))?;
+        }
+
+        Ok(())
+    }
+
+    /// The last analysis that has the file, and its module
+    fn module(&self, path: &Path) -> Option<(&Analysis, &SourceModule)> {
+        self.analyses.values().find_map(|analysis| {
+            let module = analysis.modules.iter().find(|x| x.path == path)?;
+            Some((analysis, module))
+        })
+    }
+
+    /// Type or signature and doc comment of a name
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let params = params.text_document_position_params;
+        let (analysis, module) = self.module(&file_path(&params.text_document.uri).ok()?)?;
+        let offset = offset(&module.source, params.position);
+        let reference = analysis.reference_at(module, offset)?;
+
+        let mut value = format!("```redditlang\n{}\n```", analysis.describe(reference));
+        match analysis.declaration(reference) {
+            Some(Node::Function(x)) => value.extend(x.doc.as_ref().map(|x| format!("\n\n{}", x))),
+            Some(Node::Extern(x)) => value.extend(x.doc.as_ref().map(|x| format!("\n\n{}", x))),
+            Some(Node::Variable(x)) => value.extend(x.doc.as_ref().map(|x| format!("\n\n{}", x))),
+            _ => (),
+        }
+        if let ReferenceKind::Function(_) = reference.kind {
+            if let Some(function) = STD_FUNCTIONS.iter().find(|x| x.name == reference.ident.0) {
+                if reference.definition.is_none() {
+                    value.push_str(&format!("\n\nPart of `std/{}`", function.module));
+                }
+            }
+        }
+
+        let span = reference.ident.1;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(range(&module.source, span.start, span.end)),
+        })
+    }
+
+    /// Declaration of a name, or the file of an imported module
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let params = params.text_document_position_params;
+        let (analysis, module) = self.module(&file_path(&params.text_document.uri).ok()?)?;
+        let offset = offset(&module.source, params.position);
+
+        if let Some(reference) = analysis.reference_at(module, offset) {
+            let (name, span) = reference.definition.as_ref()?;
+            let target = analysis.modules.iter().find(|x| &x.name == name)?;
+            let uri = Url::from_file_path(&target.path).ok()?;
+            let range = range(&target.source, span.start, span.end);
+            return Some(GotoDefinitionResponse::Scalar(Location::new(uri, range)));
+        }
+
+        let import = module.tree.iter().find_map(|node| match &node.node {
+            Node::Import(Import::Module(path))
+                if node.span.start <= offset && offset <= node.span.end =>
+            {
+                Some(path)
+            }
+            _ => None,
+        })?;
+        let uri = Url::from_file_path(analysis.src_dir.join(import.with_extension("rl"))).ok()?;
+        Some(GotoDefinitionResponse::Scalar(Location::new(
+            uri,
+            Range::default(),
+        )))
+    }
+
+    /// Keywords and the names that can be used at the cursor
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let params = params.text_document_position;
+        let mut items = KEYWORDS
+            .iter()
+            .map(|x| CompletionItem {
+                label: x.to_string(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        let path = file_path(&params.text_document.uri).ok()?;
+        if let Some((analysis, module)) = self.module(&path) {
+            let offset = offset(&module.source, params.position);
+            items.extend(analysis.completions(module, offset));
+        }
+        Some(CompletionResponse::Array(items))
+    }
+
+    fn formatting(&self, params: DocumentFormattingParams) -> Option<Vec<TextEdit>> {
+        let path = file_path(&params.text_document.uri).ok()?;
+        let source = match self.documents.get(&path) {
+            Some(x) => x.clone(),
+            None => fs::read_to_string(&path).ok()?,
+        };
+
+        let formatted = format_source(&source, false).ok()?;
+        let range = range(&source, 0, source.len());
+        Some(vec![TextEdit::new(range, formatted)])
+    }
+}
+
+impl Analysis {
+    fn reference_at(&self, module: &SourceModule, offset: usize) -> Option<&Reference> {
+        self.references.iter().find(|x| {
+            x.module == module.name && x.ident.1.start <= offset && offset <= x.ident.1.end
+        })
+    }
+
+    /// The function, outsourced function or variable a reference points to
+    fn declaration(&self, reference: &Reference) -> Option<&Node> {
+        let (name, span) = reference.definition.as_ref()?;
+        let module = self.modules.iter().find(|x| &x.name == name)?;
+        declaration(&module.tree, *span)
+    }
+
+    /// How the name is declared, with the inferred types
+    fn describe(&self, reference: &Reference) -> String {
+        let name = &reference.ident.0;
+        match &reference.kind {
+            ReferenceKind::Variable(r#type) => format!("meth {} damn {}", name, r#type),
+            ReferenceKind::Function(signature) => match self.declaration(reference) {
+                Some(Node::Function(x)) => {
+                    function_signature("callmeonmycellphone", name, &x.args, signature)
+                }
+                Some(Node::Extern(x)) => {
+                    function_signature("outsourced callmeonmycellphone", name, &x.args, signature)
+                }
+                // The standard library has no argument names
+                _ => {
+                    let mut out = format!("callmeonmycellphone {}", name);
+                    if let Some(ret) = &signature.ret {
+                        out.push_str(&format!(" damn {}", ret));
+                    }
+                    let params = signature.params.iter().map(|x| format!("{},", x));
+                    out.push_str(&format!("({})", params.collect::<Vec<_>>().join(" ")));
+                    out
+                }
+            },
+        }
+    }
+
+    /// Declared names of the module that can be used at `offset`, public items of imported
+    /// modules and standard library functions
+    fn completions(&self, module: &SourceModule, offset: usize) -> Vec<CompletionItem> {
+        let declared = self.references.iter().filter(|x| {
+            let declaration = x
+                .definition
+                .as_ref()
+                .is_some_and(|(name, span)| name == &module.name && *span == x.ident.1);
+            // Local variables can only be used in their function
+            let visible = x.function.is_none_or(|function| {
+                function.start <= offset && offset <= function.end && x.ident.1.end < offset
+            });
+            x.module == module.name && declaration && visible
+        });
+
+        let mut imported = vec![];
+        let mut std_modules = vec![PRELUDE.to_string()];
+        for node in &module.tree {
+            match &node.node {
+                Node::Import(Import::Module(path)) => {
+                    let name = module_name(path);
+                    let Some(import) = self.modules.iter().find(|x| x.name == name) else {
+                        continue;
+                    };
+                    for ident in import.tree.iter().filter_map(|x| public_ident(&x.node)) {
+                        imported.extend(
+                            self.references
+                                .iter()
+                                .find(|x| x.module == import.name && x.ident.1 == ident.1),
+                        );
+                    }
+                }
+                Node::Import(Import::Std(x)) => std_modules.push(x.clone()),
+                _ => (),
+            }
+        }
+
+        let mut names = HashSet::new();
+        let mut items = vec![];
+        for reference in declared.chain(imported) {
+            if !names.insert(reference.ident.0.clone()) {
+                continue;
+            }
+            let kind = match reference.kind {
+                ReferenceKind::Variable(_) => CompletionItemKind::VARIABLE,
+                ReferenceKind::Function(_) => CompletionItemKind::FUNCTION,
+            };
+            items.push(CompletionItem {
+                label: reference.ident.0.clone(),
+                kind: Some(kind),
+                detail: Some(self.describe(reference)),
+                ..Default::default()
+            });
+        }
+        for function in STD_FUNCTIONS.iter() {
+            if std_modules.iter().any(|x| x == function.module)
+                && names.insert(function.name.to_string())
+            {
+                items.push(CompletionItem {
+                    label: function.name.to_string(),
+                    kind: Some(CompletionItemKind::FUNCTION),
+                    detail: Some(format!("std/{}", function.module)),
+                    ..Default::default()
+                });
+            }
+        }
+        items
+    }
+}
+
+/// The function, outsourced function or variable declared with the identifier at `span`
+fn declaration(tree: &Tree, span: Span) -> Option<&Node> {
+    tree.iter().find_map(|node| match &node.node {
+        Node::Function(x) if x.declaration.ident.1 == span => Some(&node.node),
+        Node::Extern(x) if x.declaration.ident.1 == span => Some(&node.node),
+        Node::Variable(x) if x.declaration.ident.1 == span => Some(&node.node),
+        Node::Function(x) => declaration(&x.body, span),
+        Node::Loop(x) => declaration(&x.0, span),
+        Node::If(x) => x.if_nodes.iter().find_map(|x| match x {
+            IfNode::Case(x) => declaration(&x.body, span),
+            IfNode::Else(x) => declaration(&x.body, span),
+        }),
+        Node::TryCatch(x) => {
+            declaration(&x.r#try.0, span).or_else(|| declaration(&x.catch.1, span))
+        }
+        _ => None,
+    })
+}
+
+/// Identifier of a `bar` function, outsourced function or variable
+fn public_ident(node: &Node) -> Option<&Ident> {
+    let public = |x: &[FunctionMod]| x.iter().any(|x| matches!(x, FunctionMod::Public));
+    match node {
+        Node::Function(x) if public(&x.modifiers) => Some(&x.declaration.ident),
+        Node::Extern(x) if public(&x.modifiers) => Some(&x.declaration.ident),
+        Node::Variable(x) if x.modifiers.iter().any(|x| matches!(x, VariableMod::Public)) => {
+            Some(&x.declaration.ident)
+        }
+        _ => None,
+    }
+}
+
+fn file_path(uri: &Url) -> Result<PathBuf> {
+    uri.to_file_path()
+        .map_err(|_| format!("{} is not a file", uri).into())
+}
+
+fn log(connection: &Connection, message: String) -> Result<()> {
+    let params = LogMessageParams {
+        typ: MessageType::ERROR,
+        message,
+    };
+    let notification = Notification::new(LogMessage::METHOD.to_string(), params);
+    connection
+        .sender
+        .send(Message::Notification(notification))?;
+    Ok(())
+}
+
+fn diagnostic(source: &str, start: usize, end: usize, message: String) -> lsp_types::Diagnostic {
+    lsp_types::Diagnostic {
+        range: range(source, start, end),
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("walter".to_string()),
+        message,
+        ..Default::default()
+    }
+}
+
+fn range(source: &str, start: usize, end: usize) -> Range {
+    Range::new(position(source, start), position(source, end))
+}
+
+/// Position of a byte offset, editors count columns in UTF-16 code units
+fn position(source: &str, offset: usize) -> Position {
+    let before = &source[..offset.min(source.len())];
+    let line_start = before.rfind('\n').map_or(0, |x| x + 1);
+    Position::new(
+        before.matches('\n').count() as u32,
+        before[line_start..].encode_utf16().count() as u32,
+    )
+}
+
+/// Byte offset of a position
+fn offset(source: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match source[line_start..].find('\n') {
+            Some(x) => line_start += x + 1,
+            None => return source.len(),
+        }
+    }
+
+    let mut column = 0;
+    for (i, x) in source[line_start..].char_indices() {
+        if column >= position.character || x == '\n' {
+            return line_start + i;
+        }
+        column += x.len_utf16() as u32;
+    }
+    source.len()
+}
@@ -1,41 +1,61 @@
 use crate::{
-    compiler::{
-        compile,
-        linking::{build_libstd, define_libstd, link},
-        CompileMetadata, Compiler, Scope,
-    },
-    errors::syntax_error,
-    project::ProjectConfiguration,
+    errors::{format_diagnostic, format_error, syntax_error},
+    formatter::format_source,
+    project::{OptLevel, ProjectConfiguration, ProjectType, SourceModule},
+    semantic::{analyze, uncaught_tests, Diagnostic, Program, MAIN_MODULE},
 };
 use clap::{Parser, Subcommand};
 use colored::Colorize;
 use git::generate;
-use inkwell::{
-    context::Context,
-    module::Module,
-    targets::{CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine},
-    OptimizationLevel,
-};
-use parser::{parse, Tree};
-use pest::Parser as PestParser;
+use parser::cst::parse_cst;
 use pest_derive::Parser as PestParser;
 use project::Project;
 use semver::Version;
 use std::{
-    collections::{HashMap, HashSet},
     env, fs,
-    hash::Hash,
     path::{Path, PathBuf},
-    process::Command,
+    process,
 };
 use utils::Result;
+#[cfg(feature = "llvm")]
+use {
+    crate::{
+        compiler::{
+            compile_module,
+            debug_info::DebugInfo,
+            jit,
+            library::{compile_exports, exports, init_symbol, write_header, Export},
+            linking::{build_libstd, link, link_library, link_wasm},
+            optimize::{codegen_level, optimize},
+            runtime::define_runtime,
+            target::BuildTarget,
+            Compiler,
+        },
+        project::TargetConfiguration,
+    },
+    inkwell::{
+        builder::Builder,
+        context::Context,
+        module::Module,
+        targets::{FileType, TargetMachine},
+    },
+    std::process::Command,
+};
 
+#[cfg(feature = "llvm")]
 pub mod compiler;
+pub mod doc;
 pub mod errors;
+pub mod formatter;
 pub mod git;
+pub mod interpreter;
 pub mod logger;
+pub mod lsp;
 pub mod parser;
 pub mod project;
+#[cfg(feature = "llvm")]
+pub mod repl;
+pub mod semantic;
 pub mod utils;
 
 #[derive(PestParser)]
@@ -49,63 +69,122 @@ struct Args {
     command: Commands,
 }
 
-#[derive(Subcommand, Debug)]
-enum Commands {
-    /// Builds a program
-    Cook {
-        /// Enables release mode, longer build but more optimizations.
-        #[arg(short, long)]
-        release: bool,
+/// Options of `cook` and `serve`
+#[derive(clap::Args, Debug)]
+struct BuildArgs {
+    /// Enables release mode, longer build but more optimizations.
+    #[arg(short, long)]
+    release: bool,
 
-        /// Compiles LLVM to an assembly file instead of an object file before linking
-        #[arg(short, long)]
-        assembly: bool,
+    /// Optimization level, overrides the opt-level of the profile in walter.yml
+    #[arg(short = 'O', value_enum)]
+    opt_level: Option<OptLevel>,
 
-        /// Don't link the standard library
-        #[arg(short, long)]
-        no_std: bool,
+    /// Files to write to build/<profile>/, the executable or library if not set
+    #[arg(long, value_enum, value_delimiter = ',')]
+    emit: Vec<Emit>,
 
-        /// Strip the resulting executable
-        #[arg(short, long)]
-        strip: bool,
+    /// Don't link the external standard library from walter.yml
+    #[arg(short, long)]
+    no_std: bool,
 
-        /// Prints the LLVM IR when compiling
-        #[arg(short = 'i', long)]
-        print_ir: bool,
+    /// Uses the cached standard library instead of downloading it
+    #[arg(long)]
+    offline: bool,
 
-        /// Prints the AST when parsing
-        #[arg(short = 't', long)]
-        print_ast: bool,
-    },
-    /// Builds and runs program
-    Serve {
-        /// Enables release mode, longer build but more optimizations.
-        #[arg(short, long)]
-        release: bool,
+    /// Strip the resulting executable
+    #[arg(short, long)]
+    strip: bool,
+
+    /// Prints the LLVM IR of the program before it is written
+    #[arg(short = 'i', long)]
+    print_ir: bool,
+
+    /// Prints the AST when parsing
+    #[arg(short = 't', long)]
+    print_ast: bool,
 
-        /// Compiles LLVM to an assembly file instead of an object file before linking
-        #[arg(short, long)]
-        assembly: bool,
+    /// Target triple to compile for, ex. aarch64-unknown-linux-gnu. Defaults to the host
+    #[arg(long)]
+    target: Option<String>,
 
-        /// Don't link the standard library
-        #[arg(short, long)]
-        no_std: bool,
+    /// CPU to compile for, `native` for the CPU of this machine
+    #[arg(long)]
+    target_cpu: Option<String>,
 
-        /// Strip the resulting executable
-        #[arg(short, long)]
-        strip: bool,
+    /// LLVM target features, ex. +avx2,+fma
+    #[arg(long)]
+    target_features: Option<String>,
+}
 
-        /// Prints the LLVM IR when compiling
-        #[arg(short = 'i', long)]
-        print_ir: bool,
+/// Something `cook` can write
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Emit {
+    /// LLVM IR, `.ll`
+    LlvmIr,
+    /// LLVM bitcode, `.bc`
+    LlvmBc,
+    /// Assembly, `.s`
+    Asm,
+    /// Object file, `.o`
+    Obj,
+    /// Executable, or the static and shared library of a library project
+    Exe,
+}
 
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Builds a program
+    #[cfg(feature = "llvm")]
+    Cook {
+        #[command(flatten)]
+        build: BuildArgs,
+    },
+    /// Builds and runs program
+    Serve {
+        #[command(flatten)]
+        build: BuildArgs,
+        /// Runs the program inside of walter with a JIT, no C compiler or linker is needed
+        #[cfg(feature = "llvm")]
+        #[arg(long, conflicts_with = "interpret")]
+        jit: bool,
+        /// Runs the program with an interpreter, nothing is compiled. Always used when walter is
+        /// built without LLVM
+        #[arg(long)]
+        interpret: bool,
+        /// Optional arguments to pass to the program. Only compiled programs get them
+        #[cfg_attr(feature = "llvm", arg(conflicts_with_all = ["jit", "interpret"]))]
+        #[cfg_attr(not(feature = "llvm"), arg(conflicts_with = "interpret"))]
+        args: Option<Vec<String>>,
+    },
+    /// Parses and type checks a program without building it
+    Check {
         /// Prints the AST when parsing
         #[arg(short = 't', long)]
         print_ast: bool,
-
-        /// Optional arguments to pass to the program.
-        args: Option<Vec<String>>,
+        /// Prints the concrete syntax tree when parsing, it has every comment and space
+        #[arg(long)]
+        print_cst: bool,
+    },
+    /// Generates an mdBook with the public items of every module to `build/doc`
+    Doc,
+    /// Starts a language server on stdin and stdout for editors
+    Lsp,
+    /// Formats RedditLang files
+    Fmt {
+        /// Files or directories to format, `src/` of the project if not set
+        paths: Vec<PathBuf>,
+        /// Lists the files that are not formatted instead of formatting them, fails if there are
+        /// any
+        #[arg(long)]
+        check: bool,
+        /// Replaces operators written in ASCII by their Unicode operator, ex. `+` by `⨋`
+        #[arg(long)]
+        unicode: bool,
     },
+    /// Starts an interactive prompt that runs RedditLang code
+    #[cfg(feature = "llvm")]
+    Repl,
     /// Removes build dir
     Clean,
     /// Creates a new walter project
@@ -125,19 +204,22 @@ fn main_r(args: Args) -> Result<()> {
     logger::init().unwrap();
 
     match args.command {
-        Commands::Cook {
-            release,
-            assembly,
-            no_std,
-            print_ir,
-            print_ast,
-            strip,
-        } => {
-            let output_file = cook(release, assembly, no_std, print_ir, print_ast, strip)?;
-            log::info!(
-                "Done! Executable is avalible at {}",
-                output_file.to_str().unwrap().bold()
-            );
+        #[cfg(feature = "llvm")]
+        Commands::Cook { build } => {
+            let project = Project::from_current()?;
+            let outputs = cook(&project, &build)?;
+            let outputs = outputs
+                .iter()
+                .map(|x| x.to_str().unwrap().bold().to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            if !build.emit.is_empty() && !build.emit.contains(&Emit::Exe) {
+                log::info!("Done! Wrote {}", outputs);
+            } else if project.config.r#type == ProjectType::Library {
+                log::info!("Done! Library is avalible at {}", outputs);
+            } else {
+                log::info!("Done! Executable is avalible at {}", outputs);
+            }
         }
         Commands::Rise { name } => {
             let cwd = env::current_dir()?;
@@ -163,11 +245,49 @@ fn main_r(args: Args) -> Result<()> {
             let yaml = serde_yaml::to_string(&ProjectConfiguration {
                 name,
                 version: Version::new(0, 0, 1),
+                r#type: ProjectType::Executable,
+                target: None,
+                profiles: None,
+                std: None,
+                libs: vec![],
+                lib_paths: vec![],
+                ascii_operators: false,
             })
             .unwrap();
 
-            fs::write(&path.join("walter.yml"), yaml)?;
+            fs::write(path.join("walter.yml"), yaml)?;
+        }
+        Commands::Check {
+            print_ast,
+            print_cst,
+        } => {
+            let project = Project::from_current()?;
+            if print_cst {
+                print_csts(&project)?;
+            }
+            let program = check(&project, print_ast, false)?;
+            log::info!(
+                "Done! No problems found in {} {}",
+                program.modules.len().to_string().bold(),
+                if program.modules.len() == 1 {
+                    "module"
+                } else {
+                    "modules"
+                }
+            );
         }
+        Commands::Doc => {
+            let project = Project::from_current()?;
+            doc(&project)?;
+        }
+        Commands::Fmt {
+            paths,
+            check,
+            unicode,
+        } => fmt(paths, check, unicode)?,
+        Commands::Lsp => lsp::run()?,
+        #[cfg(feature = "llvm")]
+        Commands::Repl => repl::run()?,
         Commands::Clean => {
             let project = Project::from_current()?;
             let build_dir = Path::new(&project.path).join("build");
@@ -175,185 +295,621 @@ fn main_r(args: Args) -> Result<()> {
             log::info!("Cleaning");
             fs::remove_dir_all(build_dir).unwrap();
         }
+        #[cfg(feature = "llvm")]
         Commands::Serve {
-            release,
-            assembly,
-            no_std,
-            strip,
-            print_ir,
-            print_ast,
+            mut build,
+            jit,
+            interpret,
             args,
         } => {
-            let output_file = cook(release, assembly, no_std, print_ir, print_ast, strip)?;
+            let project = runnable_project()?;
+
+            if jit || interpret {
+                let code = if jit {
+                    serve_jit(&project, &build)?
+                } else {
+                    serve_interpreted(&project, &build)?
+                };
+                if code != 0 {
+                    process::exit(code);
+                }
+                return Ok(());
+            }
+
+            let target = build_target(&project, &build);
+            if target.is_wasm() && !target.is_wasi() {
+                return Err(format!(
+                    "{} modules need a JavaScript host, load them with runtime.js",
+                    target.triple_str().bold()
+                )
+                .into());
+            }
+            if !target.is_host() && !target.is_wasi() {
+                return Err(format!(
+                    "Programs built for {} can't run on this machine",
+                    target.triple_str().bold()
+                )
+                .into());
+            }
+
+            if !build.emit.is_empty() && !build.emit.contains(&Emit::Exe) {
+                build.emit.push(Emit::Exe);
+            }
+            let output_file = cook(&project, &build)?.remove(0);
             log::info!("Running {}", output_file.to_str().unwrap().bold());
 
-            let mut command = Command::new(output_file);
+            let mut command = if target.is_wasi() {
+                wasm_runner(&output_file)?
+            } else {
+                Command::new(output_file)
+            };
             if let Some(args) = args {
                 command.args(args);
             }
 
-            command.spawn()?;
+            // Exits with the exit code of the program
+            let status = command.status()?;
+            if !status.success() {
+                process::exit(status.code().unwrap_or(1));
+            }
+        }
+        // Without LLVM programs can only be interpreted
+        #[cfg(not(feature = "llvm"))]
+        Commands::Serve { build, args, .. } => {
+            if args.is_some() {
+                return Err(
+                    "Arguments can only be passed to compiled programs, walter was built without LLVM"
+                        .into(),
+                );
+            }
+            let project = runnable_project()?;
+
+            let code = serve_interpreted(&project, &build)?;
+            if code != 0 {
+                process::exit(code);
+            }
         }
     }
     Ok(())
 }
 
-fn parse_file(file: &str) -> Result<Tree> {
-    match RLParser::parse(Rule::Program, file) {
-        Ok(x) => parse(x),
-        Err(x) => syntax_error(x),
+/// The project in the current directory, if it is not a library
+fn runnable_project() -> Result<Project> {
+    let project = Project::from_current()?;
+    if project.config.r#type == ProjectType::Library {
+        return Err(format!(
+            "Libraries can't be ran, use {} to build it",
+            "walter cook".bold()
+        )
+        .into());
     }
+    Ok(project)
 }
 
-// should be a config struct
-fn cook(
-    release: bool,
-    assembly: bool,
-    no_std: bool,
-    print_ir: bool,
-    print_ast: bool,
-    strip: bool,
-) -> Result<PathBuf> {
-    let project = Project::from_current()?;
-    let std_path = build_libstd()?;
+/// Parses `src/main.rl` and every module it imports, the main module is first
+fn load_modules(project: &Project) -> Result<Vec<SourceModule>> {
+    let src_dir = Path::new(&project.path).join("src");
+    crate::project::load_modules(
+        &src_dir,
+        Path::new(MAIN_MODULE),
+        project.config.ascii_operators,
+        |path| fs::read_to_string(path),
+    )
+    .map_err(|x| match x.downcast::<pest::error::Error<Rule>>() {
+        Ok(x) => syntax_error(*x),
+        Err(x) => x,
+    })
+}
 
-    let project_dir = Path::new(&project.path);
-    let build_dir = project_dir
-        .join("build")
-        .join(if release { "release" } else { "debug" });
-    let src_dir = project_dir.join("src");
-    let main_file = src_dir.join("main.rl");
-    let main_file = fs::read_to_string(&main_file)?;
+/// Runs semantic analysis, logging every diagnostic
+fn analyze_modules(modules: &[SourceModule]) -> Result<Program> {
+    analyze(modules).map_err(|diagnostics| report(modules, &diagnostics))
+}
 
-    fs::create_dir_all(&build_dir)?;
+/// Logs every diagnostic and returns the error that stops the command
+fn report(modules: &[SourceModule], diagnostics: &[Diagnostic]) -> Box<dyn std::error::Error> {
+    for diagnostic in diagnostics {
+        let module = modules
+            .iter()
+            .find(|x| x.name == diagnostic.module)
+            .unwrap_or_else(|| bug!("UNKNOWN_MODULE({})", diagnostic.module));
+        log::error!("{}", format_diagnostic(diagnostic, module));
+    }
+
+    format!(
+        "Could not compile due to {} previous {}",
+        diagnostics.len(),
+        if diagnostics.len() == 1 {
+            "error"
+        } else {
+            "errors"
+        }
+    )
+    .into()
+}
 
+/// Parses and analyzes the project, everything before code generation. `wasm` rejects what
+/// can't be built for WebAssembly
+fn check(project: &Project, print_ast: bool, wasm: bool) -> Result<Program> {
     log::info!("Lexing/Parsing");
 
-    let tree = parse_file(&main_file)?;
+    let modules = load_modules(project)?;
 
     if print_ast {
-        println!("{:#?}", tree);
+        for module in &modules {
+            println!("Module: {}", module.name.bold());
+            println!("{:#?}", module.tree);
+        }
     }
 
-    log::info!("Building module tree");
-
-    /// Collects every created module and canocalizes the path
-    /// parent_module_path is the module path of the modules parent,
-    /// ex if the full module file path is a.b.c, this should be a.b
-    /// base_path is the FS path where main.rl is located
-    fn get_all_modules<P: AsRef<Path>>(
-        tree: &Tree,
-        src_dir: P,
-    ) -> Result<HashMap<PathBuf, Tree>> {
-        fn recursive(
-            tree: &Tree,
-            parent_module_path: &Path,
-            base_path: &Path,
-            imports: &mut HashMap<PathBuf, Tree>,
-        ) -> Result<()> {
-            for node in tree {
-                match node {
-                    parser::Node::Import(import) => {
-                        // base_path + parent_module_path + import.0 + ".rl" = path to file
-                        let module_path = parent_module_path.join(&import.0);
-                        let file_path = base_path
-                            .clone()
-                            .to_path_buf()
-                            .join(module_path.with_extension("rl"));
-
-                        let file_contents = fs::read_to_string(&file_path)?;
-
-                        if !imports.contains_key(&module_path) {
-                            let tree = parse_file(&file_contents)?;
-
-                            imports.insert(module_path, tree.clone());
-                            recursive(&tree, parent_module_path, base_path, imports)?;
-                        }
-                    }
-                    _ => (),
+    log::info!("Analyzing");
+
+    let program = analyze_modules(&modules)?;
+    if wasm {
+        let diagnostics = uncaught_tests(&program);
+        if !diagnostics.is_empty() {
+            return Err(report(&modules, &diagnostics));
+        }
+    }
+    Ok(program)
+}
+
+/// Prints the concrete syntax tree of every module
+fn print_csts(project: &Project) -> Result<()> {
+    for module in load_modules(project)? {
+        let cst = parse_cst(&module.source)
+            .map_err(|x| format_error(x.with_path(module.path.to_str().unwrap())))?;
+        println!("Module: {}", module.name.bold());
+        print!("{}", cst.print_tree());
+    }
+    Ok(())
+}
+
+/// Writes the documentation of every module of the project
+fn doc(project: &Project) -> Result<()> {
+    let out_dir = Path::new(&project.path).join("build").join("doc");
+
+    log::info!("Lexing/Parsing");
+    let modules = load_modules(project)?;
+    log::info!("Analyzing");
+    let program = analyze_modules(&modules)?;
+
+    log::info!("Documenting");
+    doc::write_book(&out_dir, &project.config.name, &modules, &program)?;
+    log::info!("Done! Wrote {}", out_dir.to_str().unwrap().bold());
+    Ok(())
+}
+
+/// Formats every `.rl` file of the paths, or lists the files that are not formatted with `check`.
+/// `unicode` replaces operators written in ASCII
+fn fmt(paths: Vec<PathBuf>, check: bool, unicode: bool) -> Result<()> {
+    fn rl_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        if path.is_dir() {
+            let mut entries = fs::read_dir(path)?
+                .map(|x| x.map(|x| x.path()))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            entries.sort();
+            for entry in entries {
+                if entry.is_dir() || entry.extension().is_some_and(|x| x == "rl") {
+                    rl_files(&entry, files)?;
                 }
             }
+        } else {
+            files.push(path.to_path_buf());
+        }
+        Ok(())
+    }
 
-            Ok(())
+    let paths = if paths.is_empty() {
+        let project = Project::from_current()?;
+        vec![Path::new(&project.path).join("src")]
+    } else {
+        paths
+    };
+    let mut files = vec![];
+    for path in &paths {
+        rl_files(path, &mut files)?;
+    }
+
+    let mut changed = vec![];
+    for file in &files {
+        let source = fs::read_to_string(file)?;
+        let formatted = format_source(&source, unicode)
+            .map_err(|x| format_error(x.with_path(file.to_str().unwrap())))?;
+        if formatted == source {
+            continue;
+        }
+
+        if check {
+            log::warn!("{} is not formatted", file.to_str().unwrap().bold());
+        } else {
+            fs::write(file, formatted)?;
         }
-        let mut imports = HashMap::new();
-        recursive(&tree, "".as_ref(), &src_dir.as_ref(), &mut imports)?;
-        Ok(imports)
+        changed.push(file);
+    }
+
+    let count = |n: usize| format!("{} {}", n, if n == 1 { "file" } else { "files" });
+    if check && !changed.is_empty() {
+        return Err(format!("{} not formatted", count(changed.len()).bold()).into());
+    }
+    log::info!(
+        "Done! Formatted {}, {} already formatted",
+        count(changed.len()).bold(),
+        files.len() - changed.len()
+    );
+    Ok(())
+}
+
+/// Command running a `wasm32-wasi` module with a wasm runtime from the `PATH`, arguments added to it
+/// are passed to the module
+#[cfg(feature = "llvm")]
+fn wasm_runner(module: &Path) -> Result<Command> {
+    let installed = |runtime: &str| {
+        Command::new(runtime)
+            .arg("--version")
+            .output()
+            .is_ok_and(|x| x.status.success())
+    };
+
+    let mut command;
+    if installed("wasmtime") {
+        command = Command::new("wasmtime");
+        command.arg("run").arg(module);
+    } else if installed("wasmer") {
+        command = Command::new("wasmer");
+        command.arg("run").arg(module).arg("--");
+    } else {
+        return Err(format!(
+            "No wasm runtime found, install {} or {}",
+            "wasmtime".bold(),
+            "wasmer".bold()
+        )
+        .into());
     }
 
-    // 1. recursively navigate tree, following all imports.
-    // 2. compile each tree
-    // 3. link all modules together
+    Ok(command)
+}
+
+/// Compiles the program and runs it in this process, returns its exit code
+#[cfg(feature = "llvm")]
+fn serve_jit(project: &Project, build: &BuildArgs) -> Result<i32> {
+    let target = build_target(project, build);
+    if !target.is_host() {
+        return Err(format!(
+            "{} runs the program on this machine, it can't be used with {}",
+            "--jit".bold(),
+            target.triple_str().bold()
+        )
+        .into());
+    }
+    if !build.emit.is_empty() {
+        log::warn!(
+            "{} writes nothing, {} is ignored",
+            "--jit".bold(),
+            "--emit".bold()
+        );
+    }
+    if project.config.std.is_some() && !build.no_std {
+        log::warn!(
+            "libstd is a static library, it is not linked with {}",
+            "--jit".bold()
+        );
+    }
+
+    let program = check(project, build.print_ast, false)?;
+
+    let options = CodegenOptions::new(project, build, false);
+    let opt = codegen_level(options.opt_level);
+    let target_machine = target.create_target_machine(opt)?;
+
+    let context = Context::create();
+    let builder = context.create_builder();
+    let module = codegen(
+        &context,
+        &builder,
+        &program,
+        &[],
+        &target,
+        &target_machine,
+        &options,
+    )?;
+
+    log::info!("Running {} with the JIT", project.config.name.bold());
+    jit::run(&module, project, opt)
+}
+
+/// Runs the program with the interpreter, returns its exit code
+fn serve_interpreted(project: &Project, build: &BuildArgs) -> Result<i32> {
+    if !build.emit.is_empty() {
+        log::warn!(
+            "{} writes nothing, {} is ignored",
+            "--interpret".bold(),
+            "--emit".bold()
+        );
+    }
+    if project.config.std.is_some() && !build.no_std {
+        log::warn!("libstd is not available with {}", "--interpret".bold());
+    }
 
-    let mut trees = get_all_modules(&tree, &src_dir)?;
-    trees.insert(PathBuf::from("main"), tree);
+    let program = check(project, build.print_ast, false)?;
 
     log::info!(
-        "Compiling {} {}",
-        trees.len().to_string().bold(),
-        if trees.len() == 1 { "tree" } else { "trees" }
+        "Running {} with the interpreter",
+        project.config.name.bold()
     );
+    interpreter::run(&program, !build.release)
+}
+
+/// The target flags override the `target` of `walter.yml`
+#[cfg(feature = "llvm")]
+fn build_target(project: &Project, build: &BuildArgs) -> BuildTarget {
+    let config = project.config.target.clone().unwrap_or_default();
+    BuildTarget::from_configuration(config.merge(TargetConfiguration {
+        triple: build.target.clone(),
+        cpu: build.target_cpu.clone(),
+        features: build.target_features.clone(),
+    }))
+}
+
+/// Builds the project and returns the emitted files. The executable, or the static and shared
+/// library, comes first
+#[cfg(feature = "llvm")]
+fn cook(project: &Project, build: &BuildArgs) -> Result<Vec<PathBuf>> {
+    let BuildArgs {
+        release,
+        no_std,
+        offline,
+        strip,
+        print_ast,
+        ..
+    } = *build;
+    let target = build_target(project, build);
+
+    let emit = if build.emit.is_empty() {
+        vec![Emit::Exe]
+    } else {
+        build.emit.clone()
+    };
+
+    let library = project.config.r#type == ProjectType::Library;
+    if library && target.is_wasm() {
+        return Err(format!(
+            "Libraries can't be built for {}",
+            target.triple_str().bold()
+        )
+        .into());
+    }
+
+    // Analysis is cheap and needs no network, so errors show up before libstd is fetched
+    let mut program = check(project, print_ast, target.is_wasm())?;
+
+    let std_path = match project.config.std {
+        Some(_) if target.is_wasm() => {
+            log::warn!("libstd is not linked into wasm modules");
+            None
+        }
+        Some(_) if !no_std => Some(build_libstd(project, offline)?),
+        _ => None,
+    };
+    if std_path.is_some() && !target.is_host() {
+        log::warn!(
+            "libstd is built for this machine, not for {}",
+            target.triple_str().bold()
+        );
+    }
+
+    let project_dir = Path::new(&project.path);
+    let build_dir = project_dir
+        .join("build")
+        .join(if release { "release" } else { "debug" });
+
+    fs::create_dir_all(&build_dir)?;
+
+    // A library has no `main`, the top level statements of the main module run before its exports
+    if library {
+        program.modules[0].entry = init_symbol(&project.config.name);
+    } else if target.is_wasi() {
+        // wasi-libc calls `__main_void` instead of `main`
+        program.modules[0].entry = "__main_void".to_string();
+    }
+    let exports = if library {
+        exports(&project.config.name, &program)?
+    } else {
+        vec![]
+    };
+
+    let options = CodegenOptions::new(project, build, library);
+    let target_machine = target.create_target_machine(codegen_level(options.opt_level))?;
 
     let context = Context::create();
     let builder = context.create_builder();
+    let combined_module = codegen(
+        &context,
+        &builder,
+        &program,
+        &exports,
+        &target,
+        &target_machine,
+        &options,
+    )?;
+    let opt_level = options.opt_level;
 
-    let combined_module = trees
-        .into_iter()
-        .map(|(name, tree)| {
-            let name = name
-                .components()
-                .map(|x| x.as_os_str().to_str().unwrap())
-                .collect::<Vec<_>>()
-                .join(".");
+    let artifact =
+        |extension: &str| build_dir.join(format!("{}.reddit.{}", project.config.name, extension));
+    let mut outputs = vec![];
+
+    if emit.contains(&Emit::LlvmIr) {
+        let path = artifact("ll");
+        combined_module.print_to_file(&path)?;
+        outputs.push(path);
+    }
+    if emit.contains(&Emit::LlvmBc) {
+        let path = artifact("bc");
+        if !combined_module.write_bitcode_to_path(&path) {
+            return Err(format!("Could not write {}", path.display()).into());
+        }
+        outputs.push(path);
+    }
+    if emit.contains(&Emit::Asm) {
+        let path = artifact("s"); // "s" being asm, could do .asm but whatever
+        target_machine.write_to_file(&combined_module, FileType::Assembly, &path)?;
+        outputs.push(path);
+    }
+
+    let exe = emit.contains(&Emit::Exe);
+    if !exe && !emit.contains(&Emit::Obj) {
+        return Ok(outputs);
+    }
+
+    // Linking needs the object even if it is not emitted
+    let object_path = &artifact("o");
+    target_machine.write_to_file(&combined_module, FileType::Object, object_path)?;
+    if emit.contains(&Emit::Obj) {
+        outputs.push(object_path.clone());
+    }
+    if !exe {
+        return Ok(outputs);
+    }
+
+    log::info!("Linking");
+
+    if library {
+        let header_path = build_dir.join(format!("{}.h", project.config.name));
+        write_header(&project.config.name, &exports, &header_path)?;
+
+        let mut linked = link_library(
+            project,
+            &target.triple,
+            &build_dir,
+            object_path,
+            std_path.as_ref(),
+            opt_level,
+            strip,
+        )?;
+        linked.push(header_path);
+        linked.extend(outputs);
+        return Ok(linked);
+    }
+
+    let linked = if target.is_wasm() {
+        link_wasm(project, &target, &build_dir, object_path, opt_level, strip)?
+    } else {
+        link(
+            project,
+            &target.triple,
+            &build_dir,
+            object_path,
+            std_path.as_ref(),
+            opt_level,
+            strip,
+        )?
+    };
+
+    let mut linked = vec![linked];
+    linked.extend(outputs);
+    Ok(linked)
+}
+
+/// Settings of code generation, from the flags and the profile in `walter.yml`
+#[cfg(feature = "llvm")]
+struct CodegenOptions {
+    opt_level: OptLevel,
+    /// If DWARF is emitted
+    debug: bool,
+    /// If `debug` functions print their variables
+    trace: bool,
+    library: bool,
+    print_ir: bool,
+}
 
-            let module = context.create_module(&name);
+#[cfg(feature = "llvm")]
+impl CodegenOptions {
+    fn new(project: &Project, build: &BuildArgs, library: bool) -> Self {
+        let release = build.release;
+        let profile = project.profile(release);
+        let default_opt_level = if release { OptLevel::O3 } else { OptLevel::O0 };
+
+        Self {
+            opt_level: build
+                .opt_level
+                .or(profile.opt_level)
+                .unwrap_or(default_opt_level),
+            debug: profile.debug.unwrap_or(!release),
+            trace: !release,
+            library,
+            print_ir: build.print_ir,
+        }
+    }
+}
+
+/// Compiles every module, links them into one module and optimizes it
+#[cfg(feature = "llvm")]
+fn codegen<'ctx>(
+    context: &'ctx Context,
+    builder: &'ctx Builder<'ctx>,
+    program: &Program,
+    exports: &[Export],
+    target: &BuildTarget,
+    target_machine: &TargetMachine,
+    options: &CodegenOptions,
+) -> Result<Module<'ctx>> {
+    log::info!(
+        "Compiling {} {}",
+        program.modules.len().to_string().bold(),
+        if program.modules.len() == 1 {
+            "module"
+        } else {
+            "modules"
+        }
+    );
+
+    let CodegenOptions {
+        opt_level,
+        debug,
+        trace,
+        library,
+        print_ir,
+    } = *options;
+    let target_data = target_machine.get_target_data();
+    let pointer_bits = target_data.get_pointer_byte_size(None) as u64 * 8;
+
+    let combined_module = program
+        .modules
+        .iter()
+        .map(|typed_module| {
+            let module = context.create_module(&typed_module.name);
+            module.set_triple(&target.triple);
+            module.set_data_layout(&target_data.get_data_layout());
+
+            let debug_info = debug.then(|| {
+                DebugInfo::new(
+                    &module,
+                    &typed_module.path,
+                    opt_level != OptLevel::O0,
+                    pointer_bits,
+                )
+            });
             let compiler = Compiler {
-                context: &context,
+                context,
                 module,
-                builder: &builder,
+                builder,
+                library,
+                debug_info,
+                trace,
             };
 
-            define_libstd(&compiler);
+            define_runtime(&compiler);
 
-            let compiler = &compiler;
-            let main_type = compiler.context.i32_type().fn_type(&[], false);
-            let main_fn = compiler.module.add_function(
-                if name == "main" {
-                    "main".to_string()
-                } else {
-                    format!("{}.main", &name)
-                }
-                .as_str(),
-                main_type,
-                None,
-            );
+            compile_module(&compiler, typed_module)?;
 
-            let entry_basic_block = compiler.context.append_basic_block(main_fn, "");
-            compiler.builder.position_at_end(entry_basic_block);
-
-            compile(
-                &compiler,
-                &tree,
-                &mut CompileMetadata {
-                    r#loop: None,
-                    fn_value: main_fn,
-                    function_scope: Scope {
-                        variables: HashMap::new(),
-                    },
-                },
-            )?;
-
-            // Add return
-            compiler
-                .builder
-                .build_return(Some(&compiler.context.i32_type().const_zero()));
+            compile_exports(&compiler, typed_module, exports);
 
-            let module_name = &compiler.module.get_name().to_str()?;
-            if print_ir {
-                println!("Module: {}", module_name.bold());
-                println!("{}", &compiler.module.print_to_string().to_str().unwrap());
+            if let Some(debug_info) = &compiler.debug_info {
+                debug_info.finalize();
             }
 
+            let module_name = &compiler.module.get_name().to_str()?;
+
             // LLVM errors
             if let Err(x) = compiler.module.verify() {
                 log::error!("│ Module verification for {} failed", module_name.bold());
@@ -366,7 +922,7 @@ fn cook(
 
             Ok(compiler.module.clone())
         })
-        .reduce(|a: Result<Module<'_>>, c| {
+        .reduce(|a: Result<Module<'ctx>>, c| {
             let a = a?;
             let c = c?;
 
@@ -376,50 +932,13 @@ fn cook(
         })
         .unwrap()?;
 
-    // TODO: allow user chosen targets
-    Target::initialize_x86(&InitializationConfig::default());
+    log::info!("Optimizing with -O{}", opt_level.as_str());
 
-    let opt = if release {
-        OptimizationLevel::Aggressive
-    } else {
-        OptimizationLevel::None
-    };
-
-    let reloc = RelocMode::PIC; // required for some bizzare reason
-    let model = CodeModel::Default;
-
-    let object_path = &build_dir.join(format!(
-        "{}.reddit.{}",
-        project.config.name,
-        if assembly { "s" } else { "o" } // "s" being asm, could do .asm but whatever
-    ));
-
-    let target = Target::from_name("x86-64").unwrap();
-    let target_triple = &TargetMachine::get_default_triple();
-    let target_machine = target
-        .create_target_machine(target_triple, "x86-64", "+avx2", opt, reloc, model)
-        .unwrap();
-
-    target_machine.write_to_file(
-        &combined_module,
-        if assembly {
-            FileType::Assembly
-        } else {
-            FileType::Object
-        },
-        &object_path,
-    )?;
+    optimize(&combined_module, target_machine, opt_level)?;
 
-    log::info!("Linking");
+    if print_ir {
+        println!("{}", combined_module.print_to_string().to_str().unwrap());
+    }
 
-    link(
-        &project,
-        &target_triple,
-        &build_dir,
-        &object_path,
-        &std_path,
-        release,
-        no_std,
-        strip,
-    )
+    Ok(combined_module)
 }
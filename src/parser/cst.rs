@@ -0,0 +1,294 @@
+use std::{fmt, ops::Range};
+
+use pest::{
+    iterators::{Pair, Pairs},
+    Parser,
+};
+
+use crate::{RLParser, Rule};
+
+/// A node of the concrete syntax tree, a rule of the grammar with everything inside of it.
+/// Unlike the AST nothing is left out, the text of the tree is the source it was parsed from
+#[derive(Debug, Clone)]
+pub struct SyntaxNode {
+    /// `Rule::Program` for the root
+    pub rule: Rule,
+    /// Byte range in the source
+    pub range: Range<usize>,
+    pub children: Vec<SyntaxElement>,
+}
+
+#[derive(Debug, Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(Token),
+}
+
+/// Text that is not split up any further
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Text of an atomic rule, ex. an `Ident` or a `String`
+    Leaf(Rule),
+    /// Keywords and punctuation that are not rules, ex. `meth` or `(`
+    Text,
+    /// Spaces and tabs
+    Whitespace,
+    Newline,
+    /// `# ...` or `#* ... *#`
+    Comment,
+}
+
+/// Parses a source file into a lossless syntax tree, operators can be written in ASCII
+pub fn parse_cst(source: &str) -> Result<SyntaxNode, Box<pest::error::Error<Rule>>> {
+    let pairs = RLParser::parse(Rule::AsciiProgram, source)?;
+    Ok(from_pairs(source, pairs))
+}
+
+/// Builds the syntax tree of a source file from the pairs of `Rule::Program`
+pub fn from_pairs(source: &str, pairs: Pairs<Rule>) -> SyntaxNode {
+    // `Program` is silent, so the root is made here
+    let pairs = pairs
+        .filter(|x| x.as_rule() != Rule::EOI)
+        .collect::<Vec<_>>();
+    node(source, Rule::Program, 0..source.len(), pairs)
+}
+
+fn node(source: &str, rule: Rule, range: Range<usize>, pairs: Vec<Pair<Rule>>) -> SyntaxNode {
+    let mut children = vec![];
+    let mut position = range.start;
+
+    for pair in pairs {
+        let span = pair.as_span();
+        trivia(source, position..span.start(), &mut children);
+        position = span.end();
+
+        let rule = pair.as_rule();
+        let inner = pair.into_inner().collect::<Vec<_>>();
+        // Atomic rules can't contain trivia, other rules without inner rules still can, ex. an
+        // empty `Block` with a comment. Interpolations in a string can have comments
+        let interpolated = inner.iter().any(|x| x.as_rule() == Rule::Interpolation);
+        let atomic = match rule {
+            Rule::String => !interpolated,
+            _ => matches!(
+                rule,
+                Rule::Ident
+                    | Rule::DoubleQuotedText
+                    | Rule::SingleQuotedText
+                    | Rule::Number
+                    | Rule::UNumber
+                    | Rule::StdImport
+            ),
+        };
+        if atomic {
+            children.push(SyntaxElement::Token(Token {
+                kind: TokenKind::Leaf(rule),
+                range: span.start()..span.end(),
+                text: span.as_str().to_string(),
+            }));
+        } else {
+            let node = node(source, rule, span.start()..span.end(), inner);
+            children.push(SyntaxElement::Node(node));
+        }
+    }
+    trivia(source, position..range.end, &mut children);
+
+    SyntaxNode {
+        rule,
+        range,
+        children,
+    }
+}
+
+/// Splits text between rules into whitespace, line breaks, comments and keywords
+fn trivia(source: &str, range: Range<usize>, tokens: &mut Vec<SyntaxElement>) {
+    let mut position = range.start;
+    while position < range.end {
+        let rest = &source[position..range.end];
+        let first = rest.chars().next().unwrap();
+
+        let (kind, len) = if rest.starts_with("\r\n") {
+            (TokenKind::Newline, 2)
+        } else if first == '\n' {
+            (TokenKind::Newline, 1)
+        } else if first == ' ' || first == '\t' {
+            let len = rest.find(|x| x != ' ' && x != '\t').unwrap_or(rest.len());
+            (TokenKind::Whitespace, len)
+        } else if first == '#' {
+            let len = match rest.strip_prefix("#*").and_then(|x| x.find("*#")) {
+                Some(end) => end + 4,
+                // Whitespace at the end of the line is not part of the comment
+                None => rest[..rest.find(['\r', '\n']).unwrap_or(rest.len())]
+                    .trim_end()
+                    .len(),
+            };
+            (TokenKind::Comment, len)
+        } else {
+            let len = rest
+                .find([' ', '\t', '\r', '\n', '#'])
+                .unwrap_or(rest.len());
+            (TokenKind::Text, len)
+        };
+
+        tokens.push(SyntaxElement::Token(Token {
+            kind,
+            range: position..position + len,
+            text: rest[..len].to_string(),
+        }));
+        position += len;
+    }
+}
+
+impl SyntaxNode {
+    /// Every token in the node, in source order
+    pub fn tokens(&self) -> Vec<&Token> {
+        let mut tokens = vec![];
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a Token>) {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(x) => x.collect_tokens(tokens),
+                SyntaxElement::Token(x) => tokens.push(x),
+            }
+        }
+    }
+
+    /// The tree with one node or token per line, ex. `Declaration 5..6` or `Text "meth"`
+    pub fn print_tree(&self) -> String {
+        let mut out = String::new();
+        self.write_tree(&mut out, 0);
+        out
+    }
+
+    fn write_tree(&self, out: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth);
+        out.push_str(&format!(
+            "{}{:?} {}..{}\n",
+            indent, self.rule, self.range.start, self.range.end
+        ));
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(x) => x.write_tree(out, depth + 1),
+                SyntaxElement::Token(x) => {
+                    out.push_str(&format!("{}  {:?} {:?}\n", indent, x.kind, x.text))
+                }
+            }
+        }
+    }
+
+    /// Every comment in the node, in source order
+    pub fn comments(&self) -> Vec<&Token> {
+        let mut tokens = self.tokens();
+        tokens.retain(|x| x.kind == TokenKind::Comment);
+        tokens
+    }
+}
+
+/// The exact source text of the node
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(x) => write!(f, "{}", x)?,
+                SyntaxElement::Token(x) => f.write_str(&x.text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{fs, path::Path};
+
+    use super::parse_cst;
+
+    /// Sources where most of the text is comments, whitespace and newlines
+    const TRIVIA: &[&str] = &[
+        "",
+        "\n\n\t  \n",
+        "# only a comment",
+        "#* a block\n   comment *#\n\n# and a line comment\n",
+        "# leading\n#* block *#\n\nmeth x ∑ 1 #* inline *# ⨋ 2   # trailing   \n\tmeth y ∑ \"a{x #* inside *#}b\"\t\n",
+        "callmeonmycellphone f(a, #* arg *# b damn Number,) {\n    # only a comment\n\n\n}\n",
+        "is x ⅀ 1 {\n    #* empty *#\n} but x ≠ 2 {} isn't {   }\ntest {} wall bullet { # c\n}\n",
+        "meth x ∑ 1\r\n# windows\r\n\r\ncall f()\r\n",
+        "meth x = 1 + 2 # ascii\nx = x * 3",
+        "repeatdatshid {\n\tsthu\t# tab\n}   ",
+    ];
+
+    /// Parses `source` and checks that the tokens cover it in order, without gaps or overlaps
+    fn round_trip(name: &str, source: &str) {
+        let cst = parse_cst(source).unwrap_or_else(|x| panic!("{}: {}", name, x));
+        assert_eq!(cst.to_string(), source, "{} is not lossless", name);
+
+        let mut end = 0;
+        for token in cst.tokens() {
+            assert_eq!(
+                token.range.start, end,
+                "{}: gap or overlap at {:?}",
+                name, token
+            );
+            assert_eq!(&source[token.range.clone()], token.text, "{}", name);
+            end = token.range.end;
+        }
+        assert_eq!(end, source.len(), "{}", name);
+    }
+
+    /// Every `.rl` file in a directory of the repository
+    pub(crate) fn sources(dir: &str) -> Vec<(String, String)> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(dir);
+        let mut sources = fs::read_dir(dir)
+            .unwrap()
+            .map(|x| x.unwrap().path())
+            .filter(|x| x.extension().is_some_and(|x| x == "rl"))
+            .map(|x| (x.display().to_string(), fs::read_to_string(&x).unwrap()))
+            .collect::<Vec<_>>();
+        sources.sort();
+        assert!(!sources.is_empty());
+        sources
+    }
+
+    #[test]
+    fn examples_round_trip() {
+        for (name, source) in sources("examples").iter().chain(&sources("tests/programs")) {
+            round_trip(name, source);
+        }
+    }
+
+    #[test]
+    fn trivia_round_trips() {
+        for (i, source) in TRIVIA.iter().enumerate() {
+            round_trip(&format!("TRIVIA[{}]", i), source);
+        }
+    }
+
+    #[test]
+    fn comments_are_tokens() {
+        let cst = parse_cst(TRIVIA[4]).unwrap();
+        let comments = cst
+            .comments()
+            .iter()
+            .map(|x| x.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            comments,
+            [
+                "# leading",
+                "#* block *#",
+                "#* inline *#",
+                "# trailing",
+                "#* inside *#"
+            ]
+        );
+    }
+}
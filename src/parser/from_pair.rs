@@ -3,14 +3,15 @@ use std::path::PathBuf;
 use crate::errors::syntax_error;
 use crate::parser::{
     parse, parse_one, Assignment, BinaryExpr, BinaryExprTerm, Break, Call, Catch, Class,
-    ConditionExprTerm, ConditionalExpr, ConditionalOperator, Declaration, Else, Expr, Function,
-    FunctionMod, Ident, IfBlock, IfCase, IfNode, Import, Index, IndexExpr, Loop, MathOperator,
+    ConditionExprTerm, ConditionalExpr, ConditionalOperator, Declaration, Else, Expr, Extern,
+    Function, FunctionMod, Ident, IfBlock, IfCase, IfNode, Import, IndexExpr, Loop, MathOperator,
     Node, Number, Return, Term, Throw, Tree, Try, TryCatch, Type, Variable, VariableMod,
 };
 use crate::utils::{is_unique, Result};
 use crate::{bug, Rule};
 use pest::error::Error;
-use pest::iterators::Pair;
+use pest::iterators::{Pair, Pairs};
+use pest::{Position, Span};
 
 pub trait Parse {
     fn parse_from(pair: Pair<'_, Rule>) -> Result<Self>
@@ -47,57 +48,108 @@ impl Parse for Declaration {
         let mut inner = pair.into_inner();
         let ident = Ident::parse_from(inner.next().unwrap())?;
 
-        let r#type = Type::parse_from(inner.next().unwrap())?;
+        let r#type = inner.next().map(Type::parse_from).transpose()?;
 
         Ok(Self { ident, r#type })
     }
 }
 
+/// Text of the `##` comments on the lines right above a declaration, without the `##`.
+/// Comments are not in the tree, so they are read from the source
+fn doc_comment(pair: &Pair<'_, Rule>) -> Option<String> {
+    let span = pair.as_span();
+    let (above, indent) = span.get_input()[..span.start()].rsplit_once('\n')?;
+    if !indent.trim().is_empty() {
+        return None;
+    }
+
+    let mut lines = above
+        .lines()
+        .rev()
+        .map_while(|x| x.trim().strip_prefix("##"))
+        .map(|x| x.strip_prefix(' ').unwrap_or(x))
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        return None;
+    }
+    lines.reverse();
+    Some(lines.join("\n"))
+}
+
+/// Modifiers, declaration and arguments of a function or an extern
+fn parse_signature(
+    inner: &mut Pairs<'_, Rule>,
+    start_pos: Position<'_>,
+) -> Result<(Vec<FunctionMod>, Declaration, Vec<Declaration>)> {
+    let modifiers: Vec<FunctionMod> = inner
+        .next()
+        .unwrap()
+        .into_inner()
+        .map(|modifier| modifier.as_str().trim_end().to_string())
+        .map(|modifier| match modifier.as_str() {
+            "debug" => FunctionMod::Debug,
+            "bar" => FunctionMod::Public,
+            _ => syntax_error(Error::new_from_pos(
+                pest::error::ErrorVariant::CustomError {
+                    message: "Invalid modifier".to_owned(),
+                },
+                start_pos,
+            )),
+        })
+        .collect();
+
+    let declaration = Declaration::parse_from(inner.next().unwrap()).unwrap();
+
+    let raw_args = inner.next().unwrap();
+    let start_pos = raw_args.as_span().start_pos();
+    let args: Vec<Declaration> = raw_args
+        .into_inner()
+        .map(|x| Declaration::parse_from(x).unwrap())
+        .collect();
+
+    let has_duplicates = !is_unique(args.iter().map(|x| &x.ident.0));
+    if has_duplicates {
+        return Err(Box::new(Error::<Rule>::new_from_pos(
+            pest::error::ErrorVariant::CustomError {
+                message: "Duplicate arguments".to_owned(),
+            },
+            start_pos,
+        )));
+    }
+
+    Ok((modifiers, declaration, args))
+}
+
 impl Parse for Function {
     fn parse_from(pair: Pair<'_, Rule>) -> Result<Self> {
+        let doc = doc_comment(&pair);
         let start_pos = pair.as_span().start_pos();
         let mut inner = pair.into_inner();
-        let modifiers: Vec<FunctionMod> = inner
-            .next()
-            .unwrap()
-            .into_inner()
-            .map(|modifier| modifier.as_str().trim_end().to_string())
-            .map(|modifier| match modifier.as_str() {
-                "debug" => FunctionMod::Debug,
-                "bar" => FunctionMod::Public,
-                _ => syntax_error(Error::new_from_pos(
-                    pest::error::ErrorVariant::CustomError {
-                        message: "Invalid modifier".to_owned(),
-                    },
-                    start_pos,
-                )),
-            })
-            .collect();
+        let (modifiers, declaration, args) = parse_signature(&mut inner, start_pos)?;
 
-        let declaration = Declaration::parse_from(inner.next().unwrap()).unwrap();
+        let body = Tree::parse_from(inner.next().unwrap())?;
+        Ok(Self {
+            doc,
+            modifiers,
+            declaration,
+            args,
+            body,
+        })
+    }
+}
 
-        let raw_args = inner.next().unwrap();
-        let start_pos = raw_args.as_span().start_pos();
-        let args: Vec<Declaration> = raw_args
-            .into_inner()
-            .map(|x| Declaration::parse_from(x).unwrap())
-            .collect();
+impl Parse for Extern {
+    fn parse_from(pair: Pair<'_, Rule>) -> Result<Self> {
+        let doc = doc_comment(&pair);
+        let start_pos = pair.as_span().start_pos();
+        let mut inner = pair.into_inner();
+        let (modifiers, declaration, args) = parse_signature(&mut inner, start_pos)?;
 
-        let has_duplicates = !is_unique(args.iter().map(|x| &x.ident.0));
-        if has_duplicates {
-            syntax_error(Error::new_from_pos(
-                pest::error::ErrorVariant::CustomError {
-                    message: "Duplicate arguments".to_owned(),
-                },
-                start_pos,
-            ))
-        }
-        let body = Tree::parse_from(inner.next().unwrap()).unwrap();
         Ok(Self {
+            doc,
             modifiers,
             declaration,
             args,
-            body,
         })
     }
 }
@@ -105,9 +157,24 @@ impl Parse for Function {
 impl Parse for Term {
     fn parse_from(pair: Pair<'_, Rule>) -> Result<Self> {
         match pair.as_rule() {
-            Rule::String => Ok(Self::String(
-                enquote::unquote(pair.as_str()).unwrap().to_string(),
-            )),
+            Rule::String => {
+                let parts = pair
+                    .into_inner()
+                    .map(|part| match part.as_rule() {
+                        Rule::DoubleQuotedText | Rule::SingleQuotedText => {
+                            Ok(Expr::Term(Term::String(unescape(&part)?)))
+                        }
+                        Rule::Interpolation => Expr::parse_from(part.into_inner().next().unwrap()),
+                        _ => bug!("INVALID_STRING_PART({:?})", part.as_rule()),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                match parts.as_slice() {
+                    [] => Ok(Self::String(String::new())),
+                    [Expr::Term(Term::String(x))] => Ok(Self::String(x.clone())),
+                    _ => Ok(Self::Interpolation(parts)),
+                }
+            }
             Rule::Number => {
                 let mut inner = pair.into_inner();
                 let has_sign = inner.len() == 2;
@@ -126,9 +193,11 @@ impl Parse for Term {
                 Ok(Self::Number(value))
             }
             Rule::Ident => Ok(Self::Ident(Ident::parse_from(pair).unwrap())),
-            Rule::Array => {
-                todo!(); // TODO: Array parsing
-            }
+            Rule::Array => Ok(Self::Array(
+                pair.into_inner()
+                    .map(Expr::parse_from)
+                    .collect::<Result<Vec<_>>>()?,
+            )),
             Rule::Boolean => {
                 // Boolean > True | False
                 let bool = pair.into_inner().next().unwrap();
@@ -138,7 +207,7 @@ impl Parse for Term {
                     _ => bug!("INVALID_BOOL({:?})", bool.as_rule()),
                 }
             }
-            Rule::Expr => todo!(), // TODO: Wrapped Expr Expr(Expr)
+            Rule::Expr => Ok(Self::Expr(Box::new(Expr::parse_from(pair)?))),
             Rule::Null => Ok(Self::Null),
             _ => Err(format!("INVALID_RULE({:?})", pair.as_rule()).into()),
         }
@@ -149,12 +218,12 @@ impl Parse for Call {
     fn parse_from(pair: Pair<'_, Rule>) -> Result<Self> {
         let mut inner = pair.into_inner();
         let ident = Ident::parse_from(inner.next().unwrap()).unwrap();
+        // `call x` is the same as `call x()`
         let args = inner
             .next()
-            .unwrap()
-            .into_inner()
-            .map(|x| Expr::parse_from(x).unwrap())
-            .collect();
+            .map(|x| x.into_inner().map(Expr::parse_from).collect::<Result<_>>())
+            .transpose()?
+            .unwrap_or_default();
         Ok(Self { ident, args })
     }
 }
@@ -175,14 +244,19 @@ impl Parse for Throw {
 
 impl Parse for Import {
     fn parse_from(pair: Pair<'_, Rule>) -> Result<Self> {
-        let idents = pair.into_inner();
+        let mut idents = pair.into_inner().peekable();
+        if let Some(x) = idents.next_if(|x| x.as_rule() == Rule::StdImport) {
+            let module = x.into_inner().next().unwrap();
+            return Ok(Self::Std(Ident::parse_from(module)?.0));
+        }
+
         let components = idents
             .map(|x| Ident::parse_from(x).map(|x| x.0))
             .collect::<Result<Vec<_>>>()?;
         let mut path = PathBuf::new();
         path.extend(components);
 
-        Ok(Self(path))
+        Ok(Self::Module(path))
     }
 }
 
@@ -197,7 +271,9 @@ impl Parse for TryCatch {
     fn parse_from(pair: Pair<'_, Rule>) -> Result<Self> {
         let mut inner = pair.into_inner();
 
-        let r#try = Try(Tree::parse_from(inner.next().unwrap())?);
+        // `Try` only contains its block
+        let try_block = inner.next().unwrap().into_inner().next().unwrap();
+        let r#try = Try(Tree::parse_from(try_block)?);
         let mut catch = inner.next().unwrap().into_inner();
 
         let first = catch.next().unwrap();
@@ -215,6 +291,7 @@ impl Parse for TryCatch {
 
 impl Parse for Variable {
     fn parse_from(pair: Pair<'_, Rule>) -> Result<Self> {
+        let doc = doc_comment(&pair);
         let start_pos = pair.as_span().start_pos();
         let mut inner = pair.into_inner();
         let modifiers: Vec<VariableMod> = inner
@@ -233,9 +310,11 @@ impl Parse for Variable {
             })
             .collect();
         let declaration = Declaration::parse_from(inner.next().unwrap())?;
+        inner.next(); // Assignment
         let value = Expr::parse_from(inner.next().unwrap())?;
 
         Ok(Self {
+            doc,
             modifiers,
             declaration,
             value,
@@ -245,31 +324,29 @@ impl Parse for Variable {
 
 impl Parse for BinaryExpr {
     fn parse_from(pair: Pair<'_, Rule>) -> Result<Self> {
-        let mut pairs = pair.into_inner().collect::<Vec<_>>();
-        let first = &[pairs.remove(0)];
-
-        let mut pairs = pairs.chunks(2).collect::<Vec<_>>();
-        pairs.insert(0, first);
-
-        Ok(BinaryExpr {
-            terms: pairs
-                .into_iter()
+        Ok(Self {
+            terms: pair
+                .into_inner()
+                .collect::<Vec<_>>()
+                .chunks(2)
                 .map(|x| {
-                    let operator = if x.len() == 2 { x.get(0) } else { None };
-                    let operator =
-                        operator.map(|x| match x.clone().into_inner().next().unwrap().as_rule() {
-                            Rule::Add => MathOperator::Add,
-                            Rule::Subtract => MathOperator::Subtract,
-                            Rule::Multiply => MathOperator::Multiply,
-                            Rule::Divide => MathOperator::Divide,
-                            Rule::XOR => MathOperator::XOR,
-                            Rule::Modulus => MathOperator::Modulus,
-                            _ => bug!("UNKNOWN_OPERATOR({:?})", x.as_rule()),
-                        });
-                    let operand = Term::parse_from(x.last().unwrap().clone()).unwrap();
-                    BinaryExprTerm { operand, operator }
+                    Ok(BinaryExprTerm {
+                        operand: Term::parse_from((x[0]).clone())?,
+                        operator: x.get(1).map(|x| {
+                            let rule = x.clone().into_inner().next().unwrap().as_rule();
+                            match rule {
+                                Rule::Add => MathOperator::Add,
+                                Rule::Subtract => MathOperator::Subtract,
+                                Rule::Multiply => MathOperator::Multiply,
+                                Rule::Divide => MathOperator::Divide,
+                                Rule::XOR => MathOperator::XOR,
+                                Rule::Modulus => MathOperator::Modulus,
+                                _ => bug!("UNKNOWN_OPERATOR({:?})", rule),
+                            }
+                        }),
+                    })
                 })
-                .collect::<Vec<_>>(),
+                .collect::<Result<Vec<_>>>()?,
         })
     }
 }
@@ -281,18 +358,26 @@ impl Parse for ConditionalExpr {
                 .into_inner()
                 .collect::<Vec<_>>()
                 .chunks(2)
-                .map(|x| ConditionExprTerm {
-                    operand: Term::parse_from((x[0]).clone()).unwrap(),
-                    operator: x.get(1).and_then(|x| {
-                        let rule = x.clone().into_inner().next()?.as_rule();
-                        match rule {
-                            Rule::Equality => Some(ConditionalOperator::Equality),
-                            Rule::Inequality => Some(ConditionalOperator::AntiEquality),
-                            _ => bug!("UNKNOWN_COND_OPERATOR({:?})", rule),
-                        }
-                    }),
+                .map(|x| {
+                    Ok(ConditionExprTerm {
+                        operand: Term::parse_from((x[0]).clone())?,
+                        operator: x.get(1).and_then(|x| {
+                            let rule = x.clone().into_inner().next()?.as_rule();
+                            match rule {
+                                Rule::Equality => Some(ConditionalOperator::Equality),
+                                Rule::Inequality => Some(ConditionalOperator::AntiEquality),
+                                Rule::GreaterThan => Some(ConditionalOperator::GreaterThan),
+                                Rule::GreaterThanOrEqual => {
+                                    Some(ConditionalOperator::GreaterThanOrEqual)
+                                }
+                                Rule::LessThan => Some(ConditionalOperator::LessThan),
+                                Rule::LessThanOrEqual => Some(ConditionalOperator::LessThanOrEqual),
+                                _ => bug!("UNKNOWN_COND_OPERATOR({:?})", rule),
+                            }
+                        }),
+                    })
                 })
-                .collect::<Vec<_>>(),
+                .collect::<Result<Vec<_>>>()?,
         })
     }
 }
@@ -301,6 +386,7 @@ impl Parse for Assignment {
     fn parse_from(pair: Pair<'_, Rule>) -> Result<Self> {
         let mut inner = pair.into_inner();
         let ident = Ident::parse_from(inner.next().unwrap())?;
+        inner.next(); // Assignment
         let value = Expr::parse_from(inner.next().unwrap())?;
         Ok(Self { ident, value })
     }
@@ -308,14 +394,14 @@ impl Parse for Assignment {
 
 impl Parse for Ident {
     fn parse_from(pair: Pair<'_, Rule>) -> Result<Self> {
-        Ok(Self(pair.as_str().to_string()))
+        Ok(Self(pair.as_str().to_string(), pair.as_span().into()))
     }
 }
 
 impl Parse for Expr {
     fn parse_from(pair: Pair<'_, Rule>) -> Result<Self> {
         let start_pos = pair.as_span().start_pos();
-        let value = parse_one(pair).expect("Invalid expression");
+        let value = parse_one(pair)?;
         match value {
             Node::Expr(x) => Ok(x),
             _ => syntax_error(Error::new_from_pos(
@@ -343,19 +429,19 @@ impl Parse for IfBlock {
                     let rule = x.as_rule();
                     let mut inner = x.into_inner();
                     match rule {
-                        Rule::If | Rule::ElseIf => IfNode::Case(IfCase {
-                            expr: Expr::parse_from(inner.next().unwrap()).unwrap(),
-                            body: Tree::parse_from(inner.next().unwrap()).unwrap(),
-                        }),
-                        Rule::Else => IfNode::Else(Else {
-                            body: Tree::parse_from(inner.next().unwrap()).unwrap(),
-                        }),
+                        Rule::If | Rule::ElseIf => Ok(IfNode::Case(IfCase {
+                            expr: Expr::parse_from(inner.next().unwrap())?,
+                            body: Tree::parse_from(inner.next().unwrap())?,
+                        })),
+                        Rule::Else => Ok(IfNode::Else(Else {
+                            body: Tree::parse_from(inner.next().unwrap())?,
+                        })),
                         _ => unreachable!(),
                     }
                 }
                 _ => bug!("INVALID_IFNODE({:?})", x.as_rule()),
             })
-            .collect();
+            .collect::<Result<_>>()?;
 
         Ok(Self { if_nodes })
     }
@@ -371,12 +457,13 @@ impl Parse for Return {
 
 impl Parse for Class {
     fn parse_from(pair: Pair<'_, Rule>) -> Result<Self> {
+        let doc = doc_comment(&pair);
         let mut inner = pair.into_inner();
 
         let ident = Ident::parse_from(inner.next().unwrap())?;
         let body = Tree::parse_from(inner.next().unwrap())?;
 
-        Ok(Self { ident, body })
+        Ok(Self { doc, ident, body })
     }
 }
 
@@ -385,12 +472,211 @@ impl Parse for IndexExpr {
         let mut inner = pair.into_inner();
 
         let term = Term::parse_from(inner.next().unwrap())?;
-        let index = Term::parse_from(inner.next().unwrap())?;
-        let index = match index {
-            Term::Number(x) => Index::Number(x),
-            Term::String(x) => Index::String(x),
-            _ => bug!("INVALID_INDEX_TERM({:?})", index),
-        };
+        let index = Box::new(parse_index(inner.next().unwrap())?);
         Ok(Self { term, index })
     }
 }
+
+/// Index > BinaryExpr | UNumber | String | Ident | Index
+fn parse_index(pair: Pair<'_, Rule>) -> Result<Expr> {
+    let index = pair.into_inner().next().unwrap();
+    match index.as_rule() {
+        Rule::BinaryExpr => Ok(Expr::BinaryExpr(BinaryExpr::parse_from(index)?)),
+        Rule::UNumber => {
+            let magnitude = index.into_inner().last().unwrap();
+            Ok(Expr::Term(Term::Number(magnitude.as_str().parse()?)))
+        }
+        Rule::String | Rule::Ident => Ok(Expr::Term(Term::parse_from(index)?)),
+        Rule::Index => parse_index(index),
+        _ => bug!("INVALID_INDEX({:?})", index.as_rule()),
+    }
+}
+
+/// Decodes the escapes of the text of a string. `\u` escapes of UTF-16 surrogate pairs are
+/// combined, `\x` escapes are ASCII only
+fn unescape(pair: &Pair<'_, Rule>) -> Result<String> {
+    let span = pair.as_span();
+    let text = pair.as_str();
+    let error = |start: usize, len: usize, message: String| {
+        let start = span.start() + start;
+        Box::new(Error::<Rule>::new_from_span(
+            pest::error::ErrorVariant::CustomError { message },
+            Span::new(span.get_input(), start, start + len).unwrap(),
+        ))
+    };
+    let hex = |start: usize, len: usize| u32::from_str_radix(&text[start..start + len], 16).ok();
+
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.char_indices();
+    while let Some((i, char)) = chars.next() {
+        if char != '\\' {
+            out.push(char);
+            continue;
+        }
+
+        let (_, escape) = chars
+            .next()
+            .unwrap_or_else(|| bug!("UNFINISHED_ESCAPE({})", text));
+        let (code, len) = match escape {
+            'b' => (0x08, 2),
+            'f' => (0x0C, 2),
+            'n' => (0x0A, 2),
+            'r' => (0x0D, 2),
+            't' => (0x09, 2),
+            'x' => {
+                let code = hex(i + 2, 2).unwrap_or_else(|| bug!("INVALID_ESCAPE({})", text));
+                if code > 0x7F {
+                    return Err(error(
+                        i,
+                        4,
+                        format!(
+                            "`\\x` escapes are ASCII only, use `\\u{:04X}` for U+{:04X}",
+                            code, code
+                        ),
+                    ));
+                }
+                (code, 4)
+            }
+            'u' => {
+                let code = hex(i + 2, 4).unwrap_or_else(|| bug!("INVALID_ESCAPE({})", text));
+                match code {
+                    0xD800..=0xDBFF => {
+                        let low = text[i + 6..]
+                            .strip_prefix("\\u")
+                            .and_then(|_| hex(i + 8, 4))
+                            .filter(|x| (0xDC00..=0xDFFF).contains(x));
+                        let Some(low) = low else {
+                            return Err(error(
+                                i,
+                                6,
+                                format!(
+                                    "`\\u{:04X}` is the first half of a surrogate pair, it has to \
+                                     be followed by a `\\uDC00` to `\\uDFFF` escape",
+                                    code
+                                ),
+                            ));
+                        };
+                        (0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00), 12)
+                    }
+                    0xDC00..=0xDFFF => {
+                        return Err(error(
+                            i,
+                            6,
+                            format!(
+                                "`\\u{:04X}` is the second half of a surrogate pair, it has to \
+                                 follow a `\\uD800` to `\\uDBFF` escape",
+                                code
+                            ),
+                        ))
+                    }
+                    _ => (code, 6),
+                }
+            }
+            // `\"`, `\'`, `\\`, `\/`, `\{` and `\}`
+            _ => (escape as u32, 2),
+        };
+
+        // Strings are null terminated
+        if code == 0 {
+            return Err(error(
+                i,
+                len,
+                "Strings can't contain the null character".to_string(),
+            ));
+        }
+        out.push(char::from_u32(code).unwrap_or_else(|| bug!("INVALID_CHAR({})", code)));
+        // The escape and its digits are ASCII, so bytes are characters
+        for _ in 2..len {
+            chars.next();
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::{parse_source, Expr, Node, Term};
+
+    /// The string literal as it is parsed, or the message of the syntax error
+    fn string(literal: &str) -> Result<Term, String> {
+        let tree = parse_source(&format!("meth s ∑ {}\n", literal), false)
+            .map_err(|x| x.variant.message().into_owned())?;
+        match tree.into_iter().next().map(|x| x.node) {
+            Some(Node::Variable(variable)) => match variable.value {
+                Expr::Term(term) => Ok(term),
+                x => panic!("{:?} is not a term", x),
+            },
+            x => panic!("{:?} is not a variable", x),
+        }
+    }
+
+    fn text(literal: &str) -> String {
+        match string(literal) {
+            Ok(Term::String(x)) => x,
+            x => panic!("{} is {:?}", literal, x),
+        }
+    }
+
+    #[test]
+    fn decodes_escapes() {
+        assert_eq!(
+            text(r#""\"\'\\\/\b\f\n\r\t\{\}\x41\u00e9""#),
+            "\"'\\/\u{8}\u{c}\n\r\t{}Aé"
+        );
+        assert_eq!(text(r#"'\'\"'"#), "'\"");
+    }
+
+    #[test]
+    fn decodes_surrogate_pairs() {
+        assert_eq!(text(r#""\uD83D\uDE00""#), "😀");
+        assert!(string(r#""\uD83D""#)
+            .unwrap_err()
+            .contains("first half of a surrogate pair"));
+        assert!(string(r#""\uD83Dx\uDE00""#)
+            .unwrap_err()
+            .contains("first half of a surrogate pair"));
+        assert!(string(r#""\uDE00""#)
+            .unwrap_err()
+            .contains("second half of a surrogate pair"));
+    }
+
+    #[test]
+    fn rejects_the_null_character() {
+        assert!(string(r#""\0""#).is_err());
+        for literal in [r#""\x00""#, r#""a\u0000""#] {
+            assert_eq!(
+                string(literal).unwrap_err(),
+                "Strings can't contain the null character"
+            );
+        }
+        assert!(string(r#""\x80""#).unwrap_err().contains("ASCII only"));
+    }
+
+    #[test]
+    fn closes_strings_with_the_opening_quote() {
+        assert_eq!(text(r#"'say "hi"'"#), "say \"hi\"");
+        assert_eq!(text(r#""it's""#), "it's");
+        assert_eq!(text("''"), "");
+        assert!(string(r#""a'"#).is_err());
+        assert!(string(r#"'a""#).is_err());
+    }
+
+    #[test]
+    fn parses_interpolations() {
+        let Ok(Term::Interpolation(parts)) = string(r#""a{x}b{'{1}'}""#) else {
+            panic!("not an interpolation");
+        };
+        assert!(matches!(
+            parts.as_slice(),
+            [
+                Expr::Term(Term::String(a)),
+                Expr::Term(Term::Ident(x)),
+                Expr::Term(Term::String(b)),
+                Expr::Term(Term::Interpolation(inner)),
+            ] if a == "a" && x.0 == "x" && b == "b" && inner.len() == 1
+        ));
+        assert_eq!(text(r#""\{x\}""#), "{x}");
+        assert!(string(r#""a}""#).is_err());
+        assert!(string(r#""{}""#).is_err());
+    }
+}
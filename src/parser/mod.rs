@@ -1,10 +1,14 @@
-use std::path::PathBuf;
+use std::{fmt, path::PathBuf};
 
-use crate::{utils::Result, Rule};
+use pest::Parser;
+
+use crate::{bug, utils::Result, RLParser, Rule};
 
 use self::from_pair::Parse;
 
+pub mod cst;
 pub mod from_pair;
+pub mod operators;
 pub type Number = f64; // Number type
 
 #[derive(Debug, Clone)]
@@ -17,6 +21,10 @@ pub enum Term {
     Null,
 
     Ident(Ident),
+    /// A parenthesized expression
+    Expr(Box<Expr>),
+    /// A string with expressions in it, the text between them is `Term::String`
+    Interpolation(Vec<Expr>),
 }
 
 #[derive(Debug, Clone)]
@@ -28,7 +36,8 @@ pub struct Type {
 #[derive(Debug, Clone)]
 pub struct Declaration {
     pub ident: Ident,
-    pub r#type: Type,
+    /// None if the type should be inferred
+    pub r#type: Option<Type>,
 }
 
 // Statements
@@ -41,12 +50,23 @@ pub struct Break;
 
 #[derive(Debug, Clone)]
 pub struct Function {
+    /// `##` comments right above the function
+    pub doc: Option<String>,
     pub modifiers: Vec<FunctionMod>,
     pub declaration: Declaration,
     pub args: Vec<Declaration>,
     pub body: Tree,
 }
 
+/// A C function, declared with `outsourced`
+#[derive(Debug, Clone)]
+pub struct Extern {
+    pub doc: Option<String>,
+    pub modifiers: Vec<FunctionMod>,
+    pub declaration: Declaration,
+    pub args: Vec<Declaration>,
+}
+
 #[derive(Debug, Clone)]
 pub enum FunctionMod {
     Debug,
@@ -63,7 +83,12 @@ pub struct Call {
 pub struct Throw(pub Expr);
 
 #[derive(Debug, Clone)]
-pub struct Import(pub PathBuf); // using pathbuf for joining and canocalizations
+pub enum Import {
+    /// Module of the project, relative to `src/`
+    Module(PathBuf), // using pathbuf for joining and canocalizations
+    /// Standard library module, ex. `io` for `"std/io"`
+    Std(String),
+}
 
 #[derive(Debug, Clone)]
 pub struct TryCatch {
@@ -78,6 +103,7 @@ pub struct Catch(pub Option<Ident>, pub Tree);
 
 #[derive(Debug, Clone)]
 pub struct Variable {
+    pub doc: Option<String>,
     pub modifiers: Vec<VariableMod>,
     pub declaration: Declaration,
     pub value: Expr,
@@ -118,6 +144,7 @@ pub struct Else {
 
 #[derive(Debug, Clone)]
 pub struct Class {
+    pub doc: Option<String>,
     pub ident: Ident,
     pub body: Tree,
 }
@@ -140,6 +167,36 @@ pub enum MathOperator {
 pub enum ConditionalOperator {
     Equality,
     AntiEquality,
+    GreaterThan,
+    GreaterThanOrEqual,
+    LessThan,
+    LessThanOrEqual,
+}
+
+impl fmt::Display for MathOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MathOperator::Add => "⨋",
+            MathOperator::Subtract => "–",
+            MathOperator::Multiply => "⋇",
+            MathOperator::Divide => "⎲",
+            MathOperator::XOR => "⊕",
+            MathOperator::Modulus => "⨊",
+        })
+    }
+}
+
+impl fmt::Display for ConditionalOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ConditionalOperator::Equality => "⅀",
+            ConditionalOperator::AntiEquality => "≠",
+            ConditionalOperator::GreaterThan => ">",
+            ConditionalOperator::GreaterThanOrEqual => "⋝",
+            ConditionalOperator::LessThan => "<",
+            ConditionalOperator::LessThanOrEqual => "⋜",
+        })
+    }
 }
 
 // Expressions
@@ -153,13 +210,7 @@ pub type BinaryExprTerm = ChainedExprTerm<MathOperator>;
 #[derive(Debug, Clone)]
 pub struct IndexExpr {
     pub term: Term,
-    pub index: Index,
-}
-
-#[derive(Debug, Clone)]
-pub enum Index {
-    Number(Number),
-    String(String),
+    pub index: Box<Expr>,
 }
 
 #[derive(Debug, Clone)]
@@ -176,7 +227,35 @@ pub struct ChainedExprTerm<T> {
 }
 
 #[derive(Debug, Clone)]
-pub struct Ident(pub String);
+pub struct Ident(pub String, pub Span);
+
+/// Location of a node in its source file, `line` and `col` are 1-based
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl From<pest::Span<'_>> for Span {
+    fn from(span: pest::Span<'_>) -> Self {
+        let (line, col) = span.start_pos().line_col();
+        Self {
+            start: span.start(),
+            end: span.end(),
+            line,
+            col,
+        }
+    }
+}
+
+/// A node with the location it was parsed from
+#[derive(Debug, Clone)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
 
 #[derive(Debug, Clone)]
 pub enum Expr {
@@ -193,6 +272,7 @@ pub enum Node {
     Loop(Loop),
     Break(Break),
     Function(Function),
+    Extern(Extern),
     Call(Call),
     Throw(Throw),
     Import(Import),
@@ -206,21 +286,22 @@ pub enum Node {
     EOI,
 }
 
-pub type Tree = Vec<Node>;
+pub type Tree = Vec<Spanned<Node>>;
 
 pub fn parse_one(pair: pest::iterators::Pair<'_, Rule>) -> Result<Node> {
     match pair.as_rule() {
         Rule::Statement => {
             let statement = pair.into_inner().next().unwrap();
             match statement.as_rule() {
-                Rule::Loop => Ok(Node::Loop(Loop::parse_from(statement).unwrap())),
-                Rule::Function => Ok(Node::Function(Function::parse_from(statement).unwrap())),
-                Rule::Call => Ok(Node::Call(Call::parse_from(statement).unwrap())),
-                Rule::Break => Ok(Node::Break(Break::parse_from(statement).unwrap())),
-                Rule::Throw => Ok(Node::Throw(Throw::parse_from(statement).unwrap())),
-                Rule::Import => Ok(Node::Import(Import::parse_from(statement).unwrap())),
-                Rule::TryCatch => Ok(Node::TryCatch(TryCatch::parse_from(statement).unwrap())),
-                Rule::Variable => Ok(Node::Variable(Variable::parse_from(statement).unwrap())),
+                Rule::Loop => Ok(Node::Loop(Loop::parse_from(statement)?)),
+                Rule::Function => Ok(Node::Function(Function::parse_from(statement)?)),
+                Rule::Extern => Ok(Node::Extern(Extern::parse_from(statement)?)),
+                Rule::Call => Ok(Node::Call(Call::parse_from(statement)?)),
+                Rule::Break => Ok(Node::Break(Break::parse_from(statement)?)),
+                Rule::Throw => Ok(Node::Throw(Throw::parse_from(statement)?)),
+                Rule::Import => Ok(Node::Import(Import::parse_from(statement)?)),
+                Rule::TryCatch => Ok(Node::TryCatch(TryCatch::parse_from(statement)?)),
+                Rule::Variable => Ok(Node::Variable(Variable::parse_from(statement)?)),
                 Rule::AssignmentStatement => {
                     Ok(Node::Assignment(Assignment::parse_from(statement)?))
                 }
@@ -259,10 +340,30 @@ pub fn parse(pairs: pest::iterators::Pairs<'_, Rule>) -> Result<Tree> {
     let mut tree: Tree = vec![];
 
     for pair in pairs {
+        let span = pair.as_span().into();
         let node = parse_one(pair)?;
         if !matches!(node, Node::EOI) {
-            tree.push(node);
+            tree.push(Spanned { node, span });
         }
     }
     Ok(tree)
 }
+
+/// Parses a source file, the error points at the first problem in it. Operators written in ASCII
+/// are a syntax error without `ascii_operators`
+pub fn parse_source(
+    source: &str,
+    ascii_operators: bool,
+) -> std::result::Result<Tree, Box<pest::error::Error<Rule>>> {
+    let rule = if ascii_operators {
+        Rule::AsciiProgram
+    } else {
+        Rule::Program
+    };
+    let pairs = RLParser::parse(rule, source)
+        .map_err(|x| operators::near_miss(x, source, ascii_operators))?;
+    parse(pairs).map_err(|x| {
+        x.downcast()
+            .unwrap_or_else(|x| bug!("INVALID_PARSE_ERROR({})", x))
+    })
+}
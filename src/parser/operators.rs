@@ -0,0 +1,184 @@
+use pest::{error::Error, Position};
+
+use crate::Rule;
+
+/// ASCII spellings of operators, allowed with `ascii_operators` in `walter.yml`, and the operator
+/// they stand for
+pub const ALIASES: &[(&str, &str)] = &[
+    ("==", "⅀"),
+    ("!=", "≠"),
+    (">=", "⋝"),
+    ("<=", "⋜"),
+    ("+", "⨋"),
+    ("-", "–"),
+    ("*", "⋇"),
+    ("/", "⎲"),
+    ("^", "⊕"),
+    ("%", "⨊"),
+    ("=", "∑"),
+    ("!", "¡"),
+];
+
+/// Characters that are easily mistaken for an operator, and the operator
+const NEAR_MISSES: &[(char, &str)] = &[
+    ('‒', "–"),
+    ('—', "–"),
+    ('−', "–"),
+    ('×', "⋇"),
+    ('÷', "⎲"),
+    ('≥', "⋝"),
+    ('≤', "⋜"),
+    ('≡', "⅀"),
+];
+
+/// The operator an ASCII spelling stands for, None if it isn't one
+pub fn canonical(alias: &str) -> Option<&'static str> {
+    ALIASES.iter().find(|x| x.0 == alias).map(|x| x.1)
+}
+
+/// `` `–` (U+2013) ``
+fn describe(operator: &str) -> String {
+    let code = operator.chars().next().unwrap() as u32;
+    format!("`{}` (U+{:04X})", operator, code)
+}
+
+/// The ASCII spelling of an operator at `start`, or ending after it like the `=` of `>=`, and
+/// where it starts
+fn ascii_alias(source: &str, start: usize) -> Option<(&'static str, &'static str, usize)> {
+    ALIASES.iter().find_map(|&(alias, operator)| {
+        let at = [start.checked_sub(1), Some(start)]
+            .into_iter()
+            .flatten()
+            .find(|&x| {
+                x + alias.len() > start && source.get(x..).is_some_and(|x| x.starts_with(alias))
+            })?;
+        Some((alias, operator, at))
+    })
+}
+
+/// Points out a character that looks like an operator when a syntax error is at one
+pub fn near_miss(error: Error<Rule>, source: &str, ascii_operators: bool) -> Error<Rule> {
+    let start = match error.location {
+        pest::error::InputLocation::Pos(x) => x,
+        pest::error::InputLocation::Span((x, _)) => x,
+    };
+    // Without `ascii_operators` the ASCII spellings don't parse, so the error is at them
+    if let Some((alias, operator, at)) = ascii_alias(source, start).filter(|_| !ascii_operators) {
+        return Error::new_from_span(
+            pest::error::ErrorVariant::CustomError {
+                message: format!(
+                    "`{}` is not an operator, did you mean {}? ASCII operators need \
+                     `ascii_operators: true` in walter.yml",
+                    alias,
+                    describe(operator),
+                ),
+            },
+            pest::Span::new(source, at, at + alias.len()).unwrap(),
+        );
+    }
+
+    let Some(found) = source[start..].chars().next() else {
+        return error;
+    };
+    let Some((_, operator)) = NEAR_MISSES.iter().find(|x| x.0 == found) else {
+        return error;
+    };
+
+    let mut message = format!(
+        "{} is not an operator, did you mean {}",
+        describe(&found.to_string()),
+        describe(operator)
+    );
+    if ascii_operators {
+        let alias = ALIASES.iter().find(|x| x.1 == *operator).unwrap().0;
+        message.push_str(&format!(" or `{}`", alias));
+    }
+    message.push('?');
+
+    Error::new_from_pos(
+        pest::error::ErrorVariant::CustomError { message },
+        Position::new(source, start).unwrap(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use pest::error::InputLocation;
+
+    use super::describe;
+    use crate::{formatter::format_source, parser::parse_source};
+
+    /// Programs written with ASCII operators and with Unicode operators
+    const PROGRAMS: &[(&str, &str)] = &[
+        ("meth x = 1 + 2 - 3\n", "meth x ∑ 1 ⨋ 2 – 3\n"),
+        (
+            "meth x ∑ -1 * 2 / 3 ^ 4 % 5\n",
+            "meth x ∑ –1 ⋇ 2 ⎲ 3 ⊕ 4 ⨊ 5\n",
+        ),
+        ("is a == b {} but a != b {}\n", "is a ⅀ b {} but a ≠ b {}\n"),
+        ("is a >= b {} but a <= b {}\n", "is a ⋝ b {} but a ⋜ b {}\n"),
+        ("x = 1\n", "x ∑ 1\n"),
+    ];
+
+    /// Message of the syntax error and where it starts
+    fn error(source: &str, ascii_operators: bool) -> (String, usize) {
+        let error = parse_source(source, ascii_operators).unwrap_err();
+        let start = match error.location {
+            InputLocation::Pos(x) | InputLocation::Span((x, _)) => x,
+        };
+        (error.variant.message().into_owned(), start)
+    }
+
+    #[test]
+    fn parses_ascii_operators_when_enabled() {
+        for (ascii, unicode) in PROGRAMS {
+            assert!(parse_source(ascii, true).is_ok(), "{}", ascii);
+            assert_eq!(format_source(ascii, true).unwrap(), *unicode);
+        }
+    }
+
+    #[test]
+    fn rejects_ascii_operators_by_default() {
+        let cases = [
+            (PROGRAMS[0].0, "=", "∑", 7),
+            (PROGRAMS[1].0, "-", "–", 11),
+            (PROGRAMS[2].0, "==", "⅀", 5),
+            ("is a ⅀ b {} but a != b {}\n", "!=", "≠", 20),
+            (PROGRAMS[3].0, ">=", "⋝", 5),
+            (PROGRAMS[4].0, "=", "∑", 2),
+        ];
+        for (source, alias, operator, at) in cases {
+            let message = format!(
+                "`{}` is not an operator, did you mean {}? ASCII operators need \
+                 `ascii_operators: true` in walter.yml",
+                alias,
+                describe(operator)
+            );
+            assert_eq!(error(source, false), (message, at), "{}", source);
+        }
+        for (_, unicode) in PROGRAMS {
+            assert!(parse_source(unicode, false).is_ok(), "{}", unicode);
+        }
+    }
+
+    #[test]
+    fn reports_ascii_operators_before_later_errors() {
+        let (message, at) = error("meth x ∑ 1 - 2\nmeth ∑\n", false);
+        assert!(message.starts_with("`-` is not an operator"), "{}", message);
+        assert_eq!(at, 13);
+        assert!(parse_source("meth s ∑ \"1 - 2 = -1\"\n", false).is_ok());
+    }
+
+    #[test]
+    fn points_out_near_misses() {
+        let source = "meth x ∑ 1 − 2\n";
+        let start = source.find('−').unwrap();
+        let hint = "`−` (U+2212) is not an operator, did you mean `–` (U+2013)";
+        assert_eq!(error(source, false), (format!("{}?", hint), start));
+        assert_eq!(error(source, true), (format!("{} or `-`?", hint), start));
+        assert_eq!(
+            error("is a ≥ b {}\n", false).0,
+            "`≥` (U+2265) is not an operator, did you mean `⋝` (U+22DD)?"
+        );
+    }
+}
@@ -1,15 +1,240 @@
-use std::{fs, path::Path};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 use colored::Colorize;
 use semver::Version;
 use serde::{Deserialize, Serialize};
 
-use crate::utils::Result;
+use crate::{
+    parser::{parse_source, Import, Node, Tree},
+    utils::Result,
+};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ProjectConfiguration {
     pub name: String,
     pub version: Version,
+    #[serde(default)]
+    pub r#type: ProjectType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<TargetConfiguration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profiles: Option<ProfilesConfiguration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub std: Option<StdConfiguration>,
+    /// C libraries to link, ex. `m` for `-lm`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub libs: Vec<String>,
+    /// Directories searched for `libs`, relative to the project
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lib_paths: Vec<PathBuf>,
+    /// Allows writing operators in ASCII, ex. `+` for `⨋`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ascii_operators: bool,
+}
+
+/// What `walter cook` builds, the `type` key of `walter.yml`
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectType {
+    /// A program that runs `src/main.rl`
+    #[default]
+    Executable,
+    /// A static and a shared library exporting the `bar` functions, with a C header
+    Library,
+}
+
+/// Settings of the `debug` and `release` builds, the `profiles` key of `walter.yml`
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ProfilesConfiguration {
+    pub debug: Option<ProfileConfiguration>,
+    pub release: Option<ProfileConfiguration>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct ProfileConfiguration {
+    /// `0` for debug builds and `3` for release builds if not set
+    pub opt_level: Option<OptLevel>,
+    /// Emits DWARF debug info, only for debug builds if not set
+    pub debug: Option<bool>,
+}
+
+/// How much the program is optimized, `-O` or `opt-level` in `walter.yml`
+#[derive(Serialize, Deserialize, clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "OptLevelValue", into = "OptLevelValue")]
+pub enum OptLevel {
+    #[value(name = "0")]
+    O0,
+    #[value(name = "1")]
+    O1,
+    #[value(name = "2")]
+    O2,
+    #[value(name = "3")]
+    O3,
+    /// Optimizes for size
+    #[value(name = "s")]
+    Os,
+}
+
+/// `opt-level` is a number, except for `s`
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+enum OptLevelValue {
+    Number(u8),
+    Name(String),
+}
+
+impl OptLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OptLevel::O0 => "0",
+            OptLevel::O1 => "1",
+            OptLevel::O2 => "2",
+            OptLevel::O3 => "3",
+            OptLevel::Os => "s",
+        }
+    }
+}
+
+impl TryFrom<OptLevelValue> for OptLevel {
+    type Error = String;
+
+    fn try_from(value: OptLevelValue) -> std::result::Result<Self, Self::Error> {
+        let name = match value {
+            OptLevelValue::Number(x) => x.to_string(),
+            OptLevelValue::Name(x) => x,
+        };
+        match name.as_str() {
+            "0" => Ok(OptLevel::O0),
+            "1" => Ok(OptLevel::O1),
+            "2" => Ok(OptLevel::O2),
+            "3" => Ok(OptLevel::O3),
+            "s" => Ok(OptLevel::Os),
+            _ => Err(format!(
+                "invalid opt-level `{}`, expected 0, 1, 2, 3 or s",
+                name
+            )),
+        }
+    }
+}
+
+impl From<OptLevel> for OptLevelValue {
+    fn from(value: OptLevel) -> Self {
+        match value {
+            OptLevel::Os => OptLevelValue::Name("s".to_string()),
+            x => OptLevelValue::Number(x.as_str().parse().unwrap()),
+        }
+    }
+}
+
+/// External standard library linked after the runtime, the `std` key of `walter.yml`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StdConfiguration {
+    /// Branch, tag or commit of the standard library repository. The tag of the walter version,
+    /// ex. `v1.0.0`, if not set
+    pub rev: Option<String>,
+    /// Local checkout of the standard library or a built `libstd.a`, relative to the project.
+    /// Used instead of the repository
+    pub path: Option<PathBuf>,
+}
+
+/// Target of a build, the `target` key of `walter.yml`. `--target`, `--target-cpu` and
+/// `--target-features` override it
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct TargetConfiguration {
+    /// LLVM target triple, ex. `aarch64-unknown-linux-gnu`. The host if not set
+    pub triple: Option<String>,
+    /// CPU to generate code for, `native` for the CPU of the host
+    pub cpu: Option<String>,
+    /// Comma seperated LLVM features, ex. `+avx2,+fma`
+    pub features: Option<String>,
+}
+
+impl TargetConfiguration {
+    /// Uses the fields that are set in `other` instead of the fields of `self`
+    pub fn merge(self, other: TargetConfiguration) -> Self {
+        Self {
+            triple: other.triple.or(self.triple),
+            cpu: other.cpu.or(self.cpu),
+            features: other.features.or(self.features),
+        }
+    }
+}
+
+/// A parsed source file of a project
+#[derive(Debug)]
+pub struct SourceModule {
+    /// Dot seperated module path, `main` for `src/main.rl`
+    pub name: String,
+    pub path: PathBuf,
+    pub source: String,
+    pub tree: Tree,
+}
+
+/// Converts a module file path relative to `src/` into a module path, ex. `a/b` -> `a.b`
+pub fn module_name(path: &Path) -> String {
+    path.components()
+        .map(|x| x.as_os_str().to_str().unwrap())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Parses the module at `root`, usually `main`, and every module it imports. The root module is
+/// first. `read` gives the source of a file, a syntax error is returned as a `pest::error::Error`
+/// with the path of the file
+pub fn load_modules(
+    src_dir: &Path,
+    root: &Path,
+    ascii_operators: bool,
+    read: impl Fn(&Path) -> std::io::Result<String>,
+) -> Result<Vec<SourceModule>> {
+    let load = |module_path: &Path| -> Result<SourceModule> {
+        // src_dir + module_path + ".rl" = path to file
+        let path = src_dir.join(module_path.with_extension("rl"));
+        let source = read(&path)?;
+        let tree = parse_source(&source, ascii_operators)
+            .map_err(|x| x.with_path(path.to_str().unwrap()))?;
+
+        Ok(SourceModule {
+            name: module_name(module_path),
+            path,
+            source,
+            tree,
+        })
+    };
+
+    /// Follows every import of the module at `index`
+    fn recursive(
+        index: usize,
+        modules: &mut Vec<SourceModule>,
+        load: &dyn Fn(&Path) -> Result<SourceModule>,
+    ) -> Result<()> {
+        let imports = modules[index]
+            .tree
+            .iter()
+            .filter_map(|node| match &node.node {
+                Node::Import(Import::Module(path)) => Some(path.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        for module_path in imports {
+            let name = module_name(&module_path);
+            if !modules.iter().any(|x| x.name == name) {
+                modules.push(load(&module_path)?);
+                recursive(modules.len() - 1, modules, load)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    let mut modules = vec![load(root)?];
+    recursive(0, &mut modules, &load)?;
+    Ok(modules)
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -31,6 +256,17 @@ impl Project {
         })
     }
 
+    /// Settings of the `release` or `debug` profile
+    pub fn profile(&self, release: bool) -> ProfileConfiguration {
+        let profiles = self.config.profiles.as_ref();
+        let profile = if release {
+            profiles.and_then(|x| x.release.as_ref())
+        } else {
+            profiles.and_then(|x| x.debug.as_ref())
+        };
+        profile.cloned().unwrap_or_default()
+    }
+
     pub fn from_current() -> Result<Self> {
         Self::from_path(std::env::current_dir()?.as_path()).map_err(|_| {
             format!("No {} found in the current directory", "walter.yml".bold()).into()
@@ -0,0 +1,553 @@
+use std::{
+    fs,
+    io::{self, Write},
+    mem,
+    path::PathBuf,
+};
+
+use colored::Colorize;
+use inkwell::{builder::Builder, context::Context, execution_engine::ExecutionEngine};
+use inkwell::{targets::TargetMachine, IntPredicate, OptimizationLevel};
+use log::LevelFilter;
+use pest::Parser as PestParser;
+use rustyline::{error::ReadlineError, DefaultEditor};
+
+use crate::{
+    bug, codegen,
+    compiler::{
+        jit::{self, RawArray, RawString},
+        runtime::call_runtime,
+        target::BuildTarget,
+        Compiler,
+    },
+    errors::{format_diagnostic, format_error},
+    parser::{parse_source, Import, Node, Tree},
+    project::{OptLevel, Project, SourceModule, TargetConfiguration},
+    semantic::{analyze, Diagnostic, ValidType, MAIN_MODULE},
+    utils::Result,
+    CodegenOptions, RLParser, Rule,
+};
+
+/// Variable that holds the value of the last expression
+const LAST_VALUE: &str = "_";
+/// Runs `main` and catches the bullets it does not catch
+const CATCH_SYMBOL: &str = "repl.main";
+
+const HELP: &str = "\
+Enter statements or expressions, blocks continue until every { is closed.
+Variables and functions are kept, the value of the last expression is in `_`.
+
+:ast   Prints the AST of every input
+:ir    Prints the LLVM IR of every input
+:help  Prints this message
+:quit  Exits, so does Ctrl-D";
+
+/// A `meth` of an earlier input, compiled code uses it through its storage
+struct SessionVariable {
+    name: String,
+    r#type: ValidType,
+    /// Large enough for every type, an array is `{ length, elements }`
+    storage: Box<[u64; 2]>,
+}
+
+/// What an input is ran as
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Statements,
+    /// The value is stored in `_` and printed
+    Expression,
+}
+
+struct Repl<'ctx> {
+    context: &'ctx Context,
+    builder: &'ctx Builder<'ctx>,
+    target: BuildTarget,
+    target_machine: TargetMachine,
+    /// Every input is a module in its own engine, they are kept because values can point into
+    /// them, ex. string literals
+    engines: Vec<ExecutionEngine<'ctx>>,
+
+    /// `weneed "std/..."` statements
+    imports: Vec<String>,
+    /// Functions and outsourced functions by name, as source
+    functions: Vec<(String, String)>,
+    variables: Vec<SessionVariable>,
+
+    /// From the `walter.yml` in the current directory, if there is one
+    ascii_operators: bool,
+    print_ast: bool,
+    print_ir: bool,
+}
+
+/// Starts `walter repl`
+pub fn run() -> Result<()> {
+    // Compiling every input would log a lot
+    log::set_max_level(LevelFilter::Warn);
+
+    let context = Context::create();
+    let builder = context.create_builder();
+    let target = BuildTarget::from_configuration(TargetConfiguration::default());
+    let target_machine = target.create_target_machine(OptimizationLevel::None)?;
+    let mut repl = Repl {
+        context: &context,
+        builder: &builder,
+        target,
+        target_machine,
+        engines: vec![],
+        imports: vec![],
+        functions: vec![],
+        variables: vec![],
+        ascii_operators: Project::from_current().is_ok_and(|x| x.config.ascii_operators),
+        print_ast: false,
+        print_ir: false,
+    };
+
+    let mut editor = DefaultEditor::new()?;
+    let history = history_path();
+    if let Some(history) = &history {
+        let _ = editor.load_history(history);
+    }
+
+    println!(
+        "RedditLang {}, {} for help",
+        env!("CARGO_PKG_VERSION"),
+        ":help".bold()
+    );
+
+    while let Some(input) = read_input(&mut editor)? {
+        let trimmed = input.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(trimmed)?;
+
+        match trimmed {
+            ":quit" | ":q" => break,
+            ":help" => println!("{}", HELP),
+            ":ast" => {
+                repl.print_ast = !repl.print_ast;
+                println!("AST printing {}", on_off(repl.print_ast));
+            }
+            ":ir" => {
+                repl.print_ir = !repl.print_ir;
+                println!("IR printing {}", on_off(repl.print_ir));
+            }
+            _ if trimmed.starts_with(':') => {
+                log::error!("Unknown command {}, see {}", trimmed.bold(), ":help".bold())
+            }
+            _ => repl.eval(trimmed)?,
+        }
+    }
+
+    if let Some(history) = &history {
+        editor.save_history(history)?;
+    }
+    Ok(())
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    let walter_dir = dirs::home_dir()?.join(".walter");
+    fs::create_dir_all(&walter_dir).ok()?;
+    Some(walter_dir.join("repl_history"))
+}
+
+/// Reads lines until every block is closed, None at the end of the input
+fn read_input(editor: &mut DefaultEditor) -> Result<Option<String>> {
+    let mut input = String::new();
+    loop {
+        let prompt = if input.is_empty() { "rl> " } else { "..> " };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                input.push_str(&line);
+                input.push('\n');
+                if brace_depth(&input) <= 0 {
+                    return Ok(Some(input));
+                }
+            }
+            // Ctrl-C throws away the input
+            Err(ReadlineError::Interrupted) => input.clear(),
+            Err(ReadlineError::Eof) => return Ok(None),
+            Err(x) => return Err(x.into()),
+        }
+    }
+}
+
+/// Number of `{` that are not closed, braces in strings and comments don't count
+fn brace_depth(source: &str) -> i32 {
+    let mut depth = 0;
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '"' | '\'' => {
+                while let Some(x) = chars.next() {
+                    match x {
+                        '\\' => {
+                            chars.next();
+                        }
+                        _ if x == c => break,
+                        _ => (),
+                    }
+                }
+            }
+            '#' if chars.peek() == Some(&'*') => {
+                let mut previous = ' ';
+                for x in chars.by_ref() {
+                    if previous == '*' && x == '#' {
+                        break;
+                    }
+                    previous = x;
+                }
+            }
+            '#' => {
+                for x in chars.by_ref() {
+                    if x == '\n' {
+                        break;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    depth
+}
+
+/// If the whole input is one expression
+fn is_expression(input: &str, ascii_operators: bool) -> bool {
+    let rule = if ascii_operators {
+        Rule::AsciiExpr
+    } else {
+        Rule::Expr
+    };
+    RLParser::parse(rule, input)
+        .is_ok_and(|mut x| x.next().is_some_and(|x| x.as_span().end() == input.len()))
+}
+
+fn parse_tree(source: &str, ascii_operators: bool) -> std::result::Result<Tree, String> {
+    parse_source(source, ascii_operators).map_err(|x| format_error(*x))
+}
+
+/// How a type is written in a declaration, nested generics need parentheses
+fn type_source(r#type: &ValidType) -> String {
+    match r#type {
+        ValidType::Array(x) => format!("({},) >> Array", type_source(x)),
+        _ => r#type.to_string(),
+    }
+}
+
+impl<'ctx> Repl<'ctx> {
+    fn eval(&mut self, input: &str) -> Result<()> {
+        let tree = match parse_tree(input, self.ascii_operators) {
+            Ok(tree) => tree,
+            Err(_) if is_expression(input, self.ascii_operators) => {
+                self.run(input, &[], Mode::Expression, false)?;
+                return Ok(());
+            }
+            Err(x) => {
+                log::error!("{}", x);
+                return Ok(());
+            }
+        };
+
+        if tree
+            .iter()
+            .any(|x| matches!(x.node, Node::Import(Import::Module(_))))
+        {
+            log::error!("Only the standard library can be imported in the REPL");
+            return Ok(());
+        }
+        // A call prints its value if it has one
+        if let [node] = tree.as_slice() {
+            if matches!(node.node, Node::Call(_)) && self.run(input, &[], Mode::Expression, true)? {
+                return Ok(());
+            }
+        }
+        self.run(input, &tree, Mode::Statements, false)?;
+        Ok(())
+    }
+
+    /// Source of the session, it is put before every input
+    fn prefix(&self) -> String {
+        let mut prefix = String::new();
+        for import in &self.imports {
+            prefix.push_str(import);
+            prefix.push('\n');
+        }
+        for variable in &self.variables {
+            prefix.push_str(&format!(
+                "meth {} damn {} ∑ wat\n",
+                variable.name,
+                type_source(&variable.r#type)
+            ));
+        }
+        for (_, function) in &self.functions {
+            prefix.push_str(function);
+            prefix.push('\n');
+        }
+        prefix
+    }
+
+    /// Compiles and runs an input, `quiet` hides errors of the analysis. Returns false if the
+    /// input did not compile
+    fn run(&mut self, input: &str, tree: &Tree, mode: Mode, quiet: bool) -> Result<bool> {
+        let input = match mode {
+            Mode::Statements => input.to_string(),
+            Mode::Expression => format!("meth {} ∑ {}", LAST_VALUE, input),
+        };
+        let prefix = self.prefix();
+        let source = format!("{}{}", prefix, input);
+
+        let module = SourceModule {
+            name: MAIN_MODULE.to_string(),
+            path: PathBuf::from("<repl>"),
+            tree: parse_tree(&source, self.ascii_operators)
+                .unwrap_or_else(|x| bug!("INVALID_REPL_SOURCE({})", x)),
+            source,
+        };
+        let result = analyze(std::slice::from_ref(&module));
+
+        if self.print_ast && (result.is_ok() || !quiet) {
+            let input_tree = module
+                .tree
+                .iter()
+                .filter(|x| x.span.start >= prefix.len())
+                .collect::<Vec<_>>();
+            println!("{:#?}", input_tree);
+        }
+
+        let mut program = match result {
+            Ok(x) => x,
+            Err(diagnostics) => {
+                if !quiet {
+                    for diagnostic in &diagnostics {
+                        log_diagnostic(diagnostic, prefix.len(), &input);
+                    }
+                }
+                return Ok(false);
+            }
+        };
+
+        // The declarations of the session would overwrite it
+        let typed_module = &mut program.modules[0];
+        typed_module.body.retain(|x| x.span.start >= prefix.len());
+        // Without definitions the globals are declarations, which are mapped to the storage of
+        // the variables
+        let globals = mem::take(&mut typed_module.globals);
+        let (session_globals, new_globals) = globals.split_at(self.variables.len());
+        let mut new_variables = new_globals
+            .iter()
+            .map(|x| SessionVariable {
+                name: x.name.clone(),
+                r#type: x.r#type.clone(),
+                storage: Box::new([0; 2]),
+            })
+            .collect::<Vec<_>>();
+
+        let options = CodegenOptions {
+            opt_level: OptLevel::O0,
+            debug: false,
+            trace: true,
+            library: false,
+            print_ir: self.print_ir,
+        };
+        let module = codegen(
+            self.context,
+            self.builder,
+            &program,
+            &[],
+            &self.target,
+            &self.target_machine,
+            &options,
+        )?;
+        let compiler = Compiler {
+            context: self.context,
+            builder: self.builder,
+            module,
+            library: false,
+            debug_info: None,
+            trace: false,
+        };
+        define_catch(&compiler);
+        let module = compiler.module;
+
+        let engine = jit::create_engine(&module, OptimizationLevel::None)?;
+        let storages = session_globals
+            .iter()
+            .zip(self.variables.iter_mut().map(|x| &mut x.storage))
+            .chain(
+                new_globals
+                    .iter()
+                    .zip(new_variables.iter_mut().map(|x| &mut x.storage)),
+            );
+        for (global, storage) in storages {
+            if let Some(value) = module.get_global(&global.symbol) {
+                engine.add_global_mapping(&value, storage.as_mut_ptr() as usize);
+            }
+        }
+
+        let bullet = unsafe { jit::call(&engine, CATCH_SYMBOL)? } != 0;
+        io::stdout().flush()?;
+        self.engines.push(engine);
+
+        if bullet {
+            log::error!(
+                "Uncaught bullet: {}",
+                jit::current_bullet().unwrap_or_else(|| "wat".to_string())
+            );
+            return Ok(true);
+        }
+
+        self.commit(input_tree_source(tree, &input), new_variables);
+
+        if mode == Mode::Expression {
+            if let Some(variable) = self.variables.iter().find(|x| x.name == LAST_VALUE) {
+                let value =
+                    unsafe { format_value(variable.storage.as_ptr() as _, &variable.r#type) };
+                println!("{} {}", value, format!("damn {}", variable.r#type).dimmed());
+            }
+        }
+        Ok(true)
+    }
+
+    /// Keeps the imports, functions and variables of an input that ran
+    fn commit(&mut self, items: Vec<(Option<String>, String)>, variables: Vec<SessionVariable>) {
+        for (name, source) in items {
+            match name {
+                Some(name) => {
+                    self.functions.retain(|(x, _)| *x != name);
+                    self.functions.push((name, source));
+                }
+                None if !self.imports.contains(&source) => self.imports.push(source),
+                None => (),
+            }
+        }
+
+        for variable in variables {
+            self.variables.retain(|x| x.name != variable.name);
+            self.variables.push(variable);
+        }
+    }
+}
+
+/// Imports and functions of an input with their source, functions have a name
+fn input_tree_source(tree: &Tree, input: &str) -> Vec<(Option<String>, String)> {
+    tree.iter()
+        .filter_map(|node| {
+            let source = input[node.span.start..node.span.end].to_string();
+            match &node.node {
+                Node::Function(x) => Some((Some(x.declaration.ident.0.clone()), source)),
+                Node::Extern(x) => Some((Some(x.declaration.ident.0.clone()), source)),
+                Node::Import(Import::Std(_)) => Some((None, source)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Logs a diagnostic of the input, the prefix is not shown
+fn log_diagnostic(diagnostic: &Diagnostic, prefix_len: usize, input: &str) {
+    if diagnostic.span.start < prefix_len {
+        log::error!("{}", diagnostic.message);
+        return;
+    }
+
+    let mut diagnostic = diagnostic.clone();
+    diagnostic.span.start -= prefix_len;
+    diagnostic.span.end -= prefix_len;
+    let module = SourceModule {
+        name: MAIN_MODULE.to_string(),
+        path: PathBuf::from("<repl>"),
+        source: input.to_string(),
+        tree: vec![],
+    };
+    log::error!("{}", format_diagnostic(&diagnostic, &module));
+}
+
+/// Defines a function that runs `main` and returns 1 if it shot a bullet, so bullets don't stop
+/// the REPL
+fn define_catch(compiler: &Compiler) {
+    let context = compiler.context;
+    let builder = compiler.builder;
+    let i32_type = context.i32_type();
+
+    let function = compiler
+        .module
+        .add_function(CATCH_SYMBOL, i32_type.fn_type(&[], false), None);
+    let entry = context.append_basic_block(function, "");
+    let run_block = context.append_basic_block(function, "run");
+    let catch_block = context.append_basic_block(function, "catch");
+
+    builder.position_at_end(entry);
+    let buf = call_runtime(compiler, "rl_try_enter", &[]).unwrap_or_else(|| bug!("VOID_TRY_ENTER"));
+    let thrown = call_runtime(compiler, "setjmp", &[buf.into()])
+        .unwrap_or_else(|| bug!("VOID_SETJMP"))
+        .into_int_value();
+    let thrown = builder.build_int_compare(
+        IntPredicate::NE,
+        thrown,
+        thrown.get_type().const_zero(),
+        "thrown",
+    );
+    builder.build_conditional_branch(thrown, catch_block, run_block);
+
+    builder.position_at_end(run_block);
+    let main = compiler
+        .module
+        .get_function(MAIN_MODULE)
+        .unwrap_or_else(|| bug!("UNDECLARED_ENTRY({})", MAIN_MODULE));
+    builder.build_call(main, &[], "");
+    call_runtime(compiler, "rl_try_exit", &[]);
+    builder.build_return(Some(&i32_type.const_zero()));
+
+    builder.position_at_end(catch_block);
+    builder.build_return(Some(&i32_type.const_int(1, false)));
+}
+
+/// Size of a value in memory
+fn value_size(r#type: &ValidType) -> usize {
+    match r#type {
+        ValidType::Number => mem::size_of::<f64>(),
+        ValidType::Boolean => mem::size_of::<bool>(),
+        ValidType::String => mem::size_of::<RawString>(),
+        ValidType::Array(_) => mem::size_of::<RawArray>(),
+        ValidType::Infer(_) => bug!("UNRESOLVED_TYPE({:?})", r#type),
+    }
+}
+
+/// Formats a value in the memory of the program like a literal, ex. `[1, 2,]`
+///
+/// # Safety
+/// `ptr` has to point to a value of the type
+unsafe fn format_value(ptr: *const u8, r#type: &ValidType) -> String {
+    match r#type {
+        ValidType::Number => jit::format_number(*(ptr as *const f64)),
+        ValidType::Boolean if *ptr != 0 => "Yup".to_string(),
+        ValidType::Boolean => "Nope".to_string(),
+        ValidType::String => match (*(ptr as *const RawString)).as_str() {
+            Some(x) => format!("{:?}", x),
+            None => "wat".to_string(),
+        },
+        ValidType::Array(x) => {
+            let array = &*(ptr as *const RawArray);
+            if array.elements.is_null() {
+                return "wat".to_string();
+            }
+
+            let size = value_size(x);
+            let elements = (0..array.length as usize)
+                .map(|i| format_value(array.elements.add(i * size), x) + ",")
+                .collect::<Vec<_>>();
+            format!("[{}]", elements.join(" "))
+        }
+        ValidType::Infer(_) => bug!("UNRESOLVED_TYPE({:?})", r#type),
+    }
+}
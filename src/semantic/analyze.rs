@@ -0,0 +1,1410 @@
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+};
+
+use crate::{
+    bug,
+    parser::{
+        BinaryExpr, Call, ConditionalExpr, ConditionalOperator, Expr, Extern, Function,
+        FunctionMod, Ident, IfNode, Import, IndexExpr, MathOperator, Node, Span, Spanned, Term,
+        Tree, Type, VariableMod,
+    },
+    project::{module_name, SourceModule},
+};
+
+use super::{
+    entry_symbol,
+    infer::Inference,
+    stdlib::{is_std_module, std_function, PRELUDE},
+    Callee, Diagnostic, Global, Local, LocalId, Reference, ReferenceKind, Signature, TypedCall,
+    TypedExpr, TypedExprKind, TypedFunction, TypedModule, TypedNode, TypedTree, ValidType,
+    VariableRef, MAIN_MODULE,
+};
+
+fn function_symbol(module: &str, name: &str) -> String {
+    format!("{}::{}", module, name)
+}
+
+fn global_symbol(module: &str, name: &str) -> String {
+    format!("{}::meth::{}", module, name)
+}
+
+/// If a function body contains a `spez`, functions without one do not return a value
+fn has_return(tree: &Tree) -> bool {
+    tree.iter().any(|node| match &node.node {
+        Node::Return(_) => true,
+        Node::Loop(x) => has_return(&x.0),
+        Node::If(x) => x.if_nodes.iter().any(|x| match x {
+            IfNode::Case(x) => has_return(&x.body),
+            IfNode::Else(x) => has_return(&x.body),
+        }),
+        Node::TryCatch(x) => has_return(&x.r#try.0) || has_return(&x.catch.1),
+        _ => false,
+    })
+}
+
+/// If every path through the statements ends with a `spez` or a `shoot`, or in a loop that is
+/// never left
+fn always_returns(tree: &Tree) -> bool {
+    tree.iter().any(|node| match &node.node {
+        Node::Return(_) | Node::Throw(_) => true,
+        Node::Loop(x) => !breaks(&x.0),
+        Node::If(x) => {
+            x.if_nodes.iter().any(|x| matches!(x, IfNode::Else(_)))
+                && x.if_nodes.iter().all(|x| match x {
+                    IfNode::Case(x) => always_returns(&x.body),
+                    IfNode::Else(x) => always_returns(&x.body),
+                })
+        }
+        Node::TryCatch(x) => always_returns(&x.r#try.0) && always_returns(&x.catch.1),
+        _ => false,
+    })
+}
+
+/// If a `sthu` in the statements leaves their loop, loops in them are left by their own
+fn breaks(tree: &Tree) -> bool {
+    tree.iter().any(|node| match &node.node {
+        Node::Break(_) => true,
+        Node::If(x) => x.if_nodes.iter().any(|x| match x {
+            IfNode::Case(x) => breaks(&x.body),
+            IfNode::Else(x) => breaks(&x.body),
+        }),
+        Node::TryCatch(x) => breaks(&x.r#try.0) || breaks(&x.catch.1),
+        _ => false,
+    })
+}
+
+struct FunctionInfo {
+    symbol: String,
+    signature: Signature,
+    /// Module and identifier of the declaration
+    declared: (String, Span),
+    public: bool,
+    /// A C function, its symbol is its name
+    r#extern: bool,
+}
+
+impl FunctionInfo {
+    fn callee(&self) -> Callee {
+        if self.r#extern {
+            Callee::Extern(self.symbol.clone())
+        } else {
+            Callee::Function(self.symbol.clone())
+        }
+    }
+}
+
+struct GlobalInfo {
+    symbol: String,
+    r#type: ValidType,
+    declared: (String, Span),
+}
+
+/// Everything in a module that can be used before it is declared
+#[derive(Default)]
+struct ModuleInfo {
+    functions: HashMap<String, FunctionInfo>,
+    /// Only public variables
+    globals: HashMap<String, GlobalInfo>,
+    imports: Vec<String>,
+    /// Standard library modules, the prelude is always imported
+    std_imports: Vec<String>,
+}
+
+#[derive(Clone)]
+struct Binding {
+    variable: VariableRef,
+    r#type: ValidType,
+    declared: (String, Span),
+    /// Function a local variable is declared in
+    function: Option<Span>,
+}
+
+struct FunctionContext {
+    ret: Option<ValidType>,
+    span: Span,
+}
+
+pub struct Analyzer {
+    modules: HashMap<String, ModuleInfo>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub references: Vec<Reference>,
+
+    /// Module currently being analyzed
+    module: String,
+    /// Statement currently being analyzed
+    span: Span,
+    scopes: Vec<HashMap<String, Binding>>,
+    locals: Vec<Local>,
+    globals: Vec<Global>,
+    symbols: HashSet<String>,
+    /// None at the top level of a module
+    function: Option<FunctionContext>,
+    loop_depth: usize,
+
+    inference: Inference,
+    /// Types compared with `⅀` or `≠`, which can't be arrays. Checked once every type is known,
+    /// the message of the diagnostic is the operator
+    comparisons: Vec<(ValidType, Diagnostic)>,
+    /// Types put in a string, which can't be arrays either. Checked with `comparisons`
+    interpolations: Vec<(ValidType, Diagnostic)>,
+    /// Types of the operands of `⨋` and of orderings that were unknown when they were analyzed,
+    /// they have to be Numbers or Strings. Checked with `comparisons`
+    operands: Vec<(ValidType, Diagnostic)>,
+}
+
+impl Analyzer {
+    /// Collects the functions, public variables and imports of every module
+    pub fn new(modules: &[SourceModule]) -> Self {
+        let mut analyzer = Self {
+            modules: HashMap::new(),
+            diagnostics: vec![],
+            references: vec![],
+            module: String::new(),
+            span: Span::default(),
+            scopes: vec![],
+            locals: vec![],
+            globals: vec![],
+            symbols: HashSet::new(),
+            function: None,
+            loop_depth: 0,
+            inference: Inference::default(),
+            comparisons: vec![],
+            interpolations: vec![],
+            operands: vec![],
+        };
+
+        for module in modules {
+            let info = analyzer.declare(module);
+            analyzer.modules.insert(module.name.clone(), info);
+        }
+
+        analyzer
+    }
+
+    fn declare(&mut self, module: &SourceModule) -> ModuleInfo {
+        self.module = module.name.clone();
+        let mut info = ModuleInfo::default();
+
+        for node in &module.tree {
+            self.span = node.span;
+            match &node.node {
+                Node::Function(function) => {
+                    let ident = &function.declaration.ident;
+                    if info.functions.contains_key(&ident.0) {
+                        self.error(
+                            ident.1,
+                            format!("Function `{}` is already defined", ident.0),
+                        );
+                        continue;
+                    }
+
+                    if let Some(signature) = self.signature(function) {
+                        info.functions.insert(
+                            ident.0.clone(),
+                            FunctionInfo {
+                                symbol: function_symbol(&module.name, &ident.0),
+                                signature,
+                                declared: (module.name.clone(), ident.1),
+                                public: function
+                                    .modifiers
+                                    .iter()
+                                    .any(|x| matches!(x, FunctionMod::Public)),
+                                r#extern: false,
+                            },
+                        );
+                    }
+                }
+                Node::Extern(function) => {
+                    let ident = &function.declaration.ident;
+                    if info.functions.contains_key(&ident.0) {
+                        self.error(
+                            ident.1,
+                            format!("Function `{}` is already defined", ident.0),
+                        );
+                        continue;
+                    }
+
+                    if let Some(signature) = self.extern_signature(function) {
+                        info.functions.insert(
+                            ident.0.clone(),
+                            FunctionInfo {
+                                symbol: ident.0.clone(),
+                                signature,
+                                declared: (module.name.clone(), ident.1),
+                                public: function
+                                    .modifiers
+                                    .iter()
+                                    .any(|x| matches!(x, FunctionMod::Public)),
+                                r#extern: true,
+                            },
+                        );
+                    }
+                }
+                Node::Variable(variable)
+                    if variable
+                        .modifiers
+                        .iter()
+                        .any(|x| matches!(x, VariableMod::Public)) =>
+                {
+                    let ident = &variable.declaration.ident;
+                    if info.globals.contains_key(&ident.0) {
+                        self.error(
+                            ident.1,
+                            format!("Public variable `{}` is already defined", ident.0),
+                        );
+                        continue;
+                    }
+
+                    let r#type = match &variable.declaration.r#type {
+                        Some(x) => self.r#type(x, ident.1),
+                        None => Some(self.fresh(
+                            ident.1,
+                            format!("Type annotation required for `{}`", ident.0),
+                        )),
+                    };
+                    if let Some(r#type) = r#type {
+                        info.globals.insert(
+                            ident.0.clone(),
+                            GlobalInfo {
+                                symbol: global_symbol(&module.name, &ident.0),
+                                r#type,
+                                declared: (module.name.clone(), ident.1),
+                            },
+                        );
+                    }
+                }
+                Node::Import(Import::Module(path)) => info.imports.push(module_name(path)),
+                Node::Import(Import::Std(module)) => {
+                    if is_std_module(module) {
+                        info.std_imports.push(module.clone());
+                    } else {
+                        self.error(
+                            node.span,
+                            format!("Unknown standard library module `std/{}`", module),
+                        );
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        info
+    }
+
+    /// Analyzes the top level statements and functions of a module
+    pub fn module(&mut self, module: &SourceModule) -> TypedModule {
+        self.module = module.name.clone();
+        self.scopes = vec![HashMap::new()];
+        self.function = None;
+        self.loop_depth = 0;
+        self.symbols = self.modules[&module.name]
+            .globals
+            .values()
+            .map(|x| x.symbol.clone())
+            .collect();
+
+        let mut functions = vec![];
+        let mut body = vec![];
+        for node in &module.tree {
+            match &node.node {
+                Node::Function(function) => functions.extend(self.function(function, node.span)),
+                Node::Extern(function) => {
+                    let ident = &function.declaration.ident;
+                    if let Some(info) = self.modules[&module.name].functions.get(&ident.0) {
+                        let kind = ReferenceKind::Function(info.signature.clone());
+                        let declared = info.declared.clone();
+                        self.reference(ident, kind, Some(declared), None);
+                    }
+                }
+                _ => body.extend(self.statement(node)),
+            }
+        }
+
+        self.scopes.clear();
+        TypedModule {
+            name: module.name.clone(),
+            path: module.path.clone(),
+            entry: entry_symbol(&module.name),
+            globals: mem::take(&mut self.globals),
+            functions,
+            locals: mem::take(&mut self.locals),
+            body,
+        }
+    }
+
+    fn function(&mut self, function: &Function, span: Span) -> Option<TypedFunction> {
+        self.span = span;
+        let name = &function.declaration.ident.0;
+        let info = self.modules[&self.module].functions.get(name)?;
+        let symbol = info.symbol.clone();
+        let signature = info.signature.clone();
+        let declared = info.declared.clone();
+        self.reference(
+            &function.declaration.ident,
+            ReferenceKind::Function(signature.clone()),
+            Some(declared),
+            None,
+        );
+
+        let outer_locals = mem::take(&mut self.locals);
+        let outer_loop_depth = mem::replace(&mut self.loop_depth, 0);
+        self.function = Some(FunctionContext {
+            ret: signature.ret.clone(),
+            span,
+        });
+
+        self.scopes.push(HashMap::new());
+        let params = function
+            .args
+            .iter()
+            .zip(&signature.params)
+            .map(|(arg, r#type)| self.declare_local(&arg.ident, r#type.clone()))
+            .collect();
+        let body = self.tree(&function.body);
+        self.scopes.pop();
+
+        if signature.ret.is_some() && !always_returns(&function.body) {
+            self.error(
+                function.declaration.ident.1,
+                format!(
+                    "Missing `spez`, `{}` can end without returning a value",
+                    name
+                ),
+            );
+        }
+
+        self.function = None;
+        self.loop_depth = outer_loop_depth;
+        let locals = mem::replace(&mut self.locals, outer_locals);
+
+        Some(TypedFunction {
+            name: name.clone(),
+            symbol,
+            modifiers: function.modifiers.clone(),
+            signature,
+            params,
+            locals,
+            body,
+            span,
+        })
+    }
+
+    fn tree(&mut self, tree: &Tree) -> TypedTree {
+        self.scopes.push(HashMap::new());
+        let tree = self.statements(tree);
+        self.scopes.pop();
+        tree
+    }
+
+    fn statements(&mut self, tree: &Tree) -> TypedTree {
+        tree.iter().filter_map(|x| self.statement(x)).collect()
+    }
+
+    fn statement(&mut self, node: &Spanned<Node>) -> Option<Spanned<TypedNode>> {
+        self.span = node.span;
+        let typed = match &node.node {
+            Node::Variable(variable) => {
+                let ident = &variable.declaration.ident;
+                let public = variable
+                    .modifiers
+                    .iter()
+                    .any(|x| matches!(x, VariableMod::Public));
+
+                // Public variables at the top level already got their type when declared
+                let declared = match self.modules[&self.module].globals.get(&ident.0) {
+                    Some(x) if public && self.is_top_level() => Some(x.r#type.clone()),
+                    _ => match &variable.declaration.r#type {
+                        Some(x) => Some(self.r#type(x, ident.1)?),
+                        None => None,
+                    },
+                };
+                let value =
+                    self.expr(&variable.value, declared.as_ref())
+                        .and_then(|x| match &declared {
+                            Some(r#type) => self.expect(x, r#type),
+                            None => Some(x),
+                        });
+
+                let r#type = match (declared, &value) {
+                    (Some(x), _) => x,
+                    (None, Some(value)) => value.r#type.clone(),
+                    // Not reported, the value already has an error
+                    (None, None) => self.inference.fresh(None),
+                };
+                let origin = self.diagnostic(
+                    ident.1,
+                    format!("Type annotation required for `{}`", ident.0),
+                );
+                self.inference.describe(&r#type, &origin);
+
+                // Declared even if the value is invalid to avoid errors on every later use
+                let variable = self.declare_variable(ident, r#type, public);
+
+                TypedNode::Variable(variable, value?)
+            }
+            Node::Assignment(assignment) => {
+                let binding = self.lookup(&assignment.ident)?;
+                let value = self.expr(&assignment.value, Some(&binding.r#type))?;
+                TypedNode::Assignment(binding.variable, self.expect(value, &binding.r#type)?)
+            }
+            Node::Loop(r#loop) => {
+                self.loop_depth += 1;
+                let body = self.tree(&r#loop.0);
+                self.loop_depth -= 1;
+                TypedNode::Loop(body)
+            }
+            Node::Break(_) => {
+                if self.loop_depth == 0 {
+                    self.error(node.span, "`sthu` used outside of a loop");
+                    return None;
+                }
+                TypedNode::Break
+            }
+            Node::If(if_block) => {
+                let mut cases = vec![];
+                let mut r#else = None;
+                let mut valid = true;
+
+                for if_node in &if_block.if_nodes {
+                    match if_node {
+                        IfNode::Case(case) => {
+                            let condition = self
+                                .expr(&case.expr, Some(&ValidType::Boolean))
+                                .and_then(|x| self.expect(x, &ValidType::Boolean));
+                            let body = self.tree(&case.body);
+                            match condition {
+                                Some(condition) => cases.push((condition, body)),
+                                None => valid = false,
+                            }
+                        }
+                        IfNode::Else(x) => r#else = Some(self.tree(&x.body)),
+                    }
+                }
+
+                if !valid {
+                    return None;
+                }
+                TypedNode::If(cases, r#else)
+            }
+            Node::Call(call) => TypedNode::Call(self.call(call)?),
+            Node::Return(r#return) => {
+                let ret = match &self.function {
+                    Some(function) => function.ret.clone(),
+                    // Exit code of the program
+                    None if self.module == MAIN_MODULE => Some(ValidType::Number),
+                    None => {
+                        self.error(
+                            node.span,
+                            "`spez` can only be used in functions and the main module",
+                        );
+                        return None;
+                    }
+                };
+
+                let ret = match ret {
+                    Some(x) => x,
+                    None => {
+                        self.error(node.span, "This function does not return a value");
+                        return None;
+                    }
+                };
+
+                let value = self.expr(&r#return.0, Some(&ret))?;
+                TypedNode::Return(self.expect(value, &ret)?)
+            }
+            Node::Throw(throw) => {
+                let bullet = self.expr(&throw.0, Some(&ValidType::String))?;
+                TypedNode::Throw(self.expect(bullet, &ValidType::String)?)
+            }
+            Node::TryCatch(try_catch) => {
+                let r#try = self.tree(&try_catch.r#try.0);
+
+                self.scopes.push(HashMap::new());
+                let bullet =
+                    try_catch.catch.0.as_ref().map(|ident| {
+                        VariableRef::Local(self.declare_local(ident, ValidType::String))
+                    });
+                let catch = self.statements(&try_catch.catch.1);
+                self.scopes.pop();
+
+                TypedNode::TryCatch(r#try, bullet, catch)
+            }
+            Node::Import(import) => {
+                if !self.is_top_level() {
+                    self.error(
+                        node.span,
+                        "Imports can only be used at the top level of a module",
+                    );
+                    return None;
+                }
+                match import {
+                    Import::Module(path) => TypedNode::Import(entry_symbol(&module_name(path))),
+                    // Standard library functions are linked with every program
+                    Import::Std(_) => return None,
+                }
+            }
+            Node::Extern(_) => {
+                self.error(
+                    node.span,
+                    "Outsourced functions can only be declared at the top level of a module",
+                );
+                return None;
+            }
+            Node::Function(_) => {
+                self.error(
+                    node.span,
+                    "Functions can only be declared at the top level of a module",
+                );
+                return None;
+            }
+            Node::Class(_) => {
+                self.error(node.span, "Classes are not supported yet");
+                return None;
+            }
+            Node::Expr(_) => {
+                self.error(node.span, "Expected a statement, got an expression");
+                return None;
+            }
+            Node::EOI => return None,
+        };
+
+        Some(Spanned {
+            node: typed,
+            span: node.span,
+        })
+    }
+
+    fn call(&mut self, call: &Call) -> Option<TypedCall> {
+        let (callee, signature) = self.resolve_function(&call.ident)?;
+
+        if call.args.len() != signature.params.len() {
+            self.error(
+                call.ident.1,
+                format!(
+                    "`{}` takes {} argument(s) but {} were given",
+                    call.ident.0,
+                    signature.params.len(),
+                    call.args.len()
+                ),
+            );
+            return None;
+        }
+
+        let args = call
+            .args
+            .iter()
+            .zip(&signature.params)
+            .map(|(arg, r#type)| {
+                self.expr(arg, Some(r#type))
+                    .and_then(|x| self.expect(x, r#type))
+            })
+            .collect::<Vec<_>>();
+
+        Some(TypedCall {
+            callee,
+            args: args.into_iter().collect::<Option<Vec<_>>>()?,
+            signature,
+        })
+    }
+
+    fn expr(&mut self, expr: &Expr, expected: Option<&ValidType>) -> Option<TypedExpr> {
+        match expr {
+            Expr::Term(term) => self.term(term, expected),
+            Expr::BinaryExpr(x) => self.binary(x),
+            Expr::ConditionalExpr(x) => self.conditional(x),
+            Expr::IndexExpr(x) => self.index(x, expected),
+            Expr::CallExpr(call) => {
+                let typed = self.call(call)?;
+                match typed.signature.ret.clone() {
+                    Some(r#type) => Some(TypedExpr {
+                        kind: TypedExprKind::Call(typed),
+                        r#type,
+                    }),
+                    None => {
+                        self.error(
+                            call.ident.1,
+                            format!("`{}` does not return a value", call.ident.0),
+                        );
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    fn term(&mut self, term: &Term, expected: Option<&ValidType>) -> Option<TypedExpr> {
+        let (kind, r#type) = match term {
+            Term::Number(x) => (TypedExprKind::Number(*x), ValidType::Number),
+            Term::String(x) => (TypedExprKind::String(x.clone()), ValidType::String),
+            Term::Boolean(x) => (TypedExprKind::Boolean(*x), ValidType::Boolean),
+            Term::Null => match expected {
+                Some(x) => (TypedExprKind::Null, x.clone()),
+                None => {
+                    let origin =
+                        self.diagnostic(self.span, "The type of `wat` cannot be inferred here");
+                    (TypedExprKind::Null, self.inference.fresh_value(origin))
+                }
+            },
+            Term::Array(items) => {
+                let mut element = expected.and_then(|x| match self.inference.shallow(x) {
+                    ValidType::Array(x) => Some(*x),
+                    _ => None,
+                });
+
+                let mut typed = vec![];
+                for item in items {
+                    let item = self.expr(item, element.as_ref())?;
+                    let item = match &element {
+                        Some(element) => self.expect(item, element)?,
+                        None => {
+                            element = Some(item.r#type.clone());
+                            item
+                        }
+                    };
+                    typed.push(item);
+                }
+
+                let element = match element {
+                    Some(x) => x,
+                    None => {
+                        let origin =
+                            self.diagnostic(self.span, "The type of `[]` cannot be inferred here");
+                        self.inference.fresh_value(origin)
+                    }
+                };
+                (
+                    TypedExprKind::Array(typed),
+                    ValidType::Array(Box::new(element)),
+                )
+            }
+            Term::Ident(ident) => {
+                let binding = self.lookup(ident)?;
+                (TypedExprKind::Variable(binding.variable), binding.r#type)
+            }
+            Term::Expr(expr) => return self.expr(expr, expected),
+            Term::Interpolation(parts) => {
+                let mut typed = vec![];
+                for part in parts {
+                    let part = self.expr(part, None)?;
+                    let diagnostic = self.diagnostic(self.span, String::new());
+                    self.interpolations.push((part.r#type.clone(), diagnostic));
+                    typed.push(part);
+                }
+                (TypedExprKind::Interpolation(typed), ValidType::String)
+            }
+        };
+
+        Some(TypedExpr { kind, r#type })
+    }
+
+    fn binary(&mut self, expr: &BinaryExpr) -> Option<TypedExpr> {
+        let mut terms = expr.terms.iter();
+        let first = terms.next()?;
+
+        let mut lhs = self.term(&first.operand, None)?;
+        let mut operator = first.operator.clone();
+        for term in terms {
+            let op = operator
+                .take()
+                .unwrap_or_else(|| bug!("MISSING_OPERATOR({:?})", expr));
+            // `⨋` joins strings, it adds numbers if the operands are not known to be strings yet
+            let r#type = match op {
+                MathOperator::Add => lhs.r#type.clone(),
+                _ => ValidType::Number,
+            };
+            let rhs = self.term(&term.operand, Some(&r#type))?;
+
+            // Both are checked so the types of both get inferred
+            let valid = self.inference.unify(&lhs.r#type, &r#type);
+            let valid = self.inference.unify(&rhs.r#type, &r#type) && valid;
+            let valid = valid
+                && match self.inference.shallow(&r#type) {
+                    ValidType::Number | ValidType::String => true,
+                    // Decided once every type is inferred
+                    ValidType::Infer(_) => {
+                        let diagnostic = self.diagnostic(self.span, op.to_string());
+                        self.operands.push((r#type.clone(), diagnostic));
+                        true
+                    }
+                    _ => false,
+                };
+            if !valid {
+                self.error(
+                    self.span,
+                    format!(
+                        "`{}` cannot be applied to {} and {}",
+                        op,
+                        self.inference.resolve(&lhs.r#type),
+                        self.inference.resolve(&rhs.r#type)
+                    ),
+                );
+                return None;
+            }
+
+            lhs = TypedExpr {
+                kind: TypedExprKind::Binary(Box::new(lhs), op, Box::new(rhs)),
+                r#type,
+            };
+            operator = term.operator.clone();
+        }
+
+        Some(lhs)
+    }
+
+    fn conditional(&mut self, expr: &ConditionalExpr) -> Option<TypedExpr> {
+        let mut result: Option<TypedExpr> = None;
+        for pair in expr.terms.windows(2) {
+            let (left, right) = (&pair[0], &pair[1]);
+            let operator = left
+                .operator
+                .clone()
+                .unwrap_or_else(|| bug!("MISSING_COND_OPERATOR({:?})", expr));
+
+            let lhs = match result.take() {
+                Some(lhs) => lhs,
+                None => self.term(&left.operand, None)?,
+            };
+            let rhs = self.term(&right.operand, Some(&lhs.r#type))?;
+
+            let valid = self.inference.unify(&lhs.r#type, &rhs.r#type)
+                && match operator {
+                    ConditionalOperator::Equality | ConditionalOperator::AntiEquality => true,
+                    // Strings are ordered by their bytes
+                    _ => match self.inference.shallow(&lhs.r#type) {
+                        ValidType::Number | ValidType::String => true,
+                        ValidType::Infer(_) => {
+                            let diagnostic = self.diagnostic(self.span, operator.to_string());
+                            self.operands.push((lhs.r#type.clone(), diagnostic));
+                            true
+                        }
+                        _ => false,
+                    },
+                };
+
+            if !valid {
+                self.error(
+                    self.span,
+                    format!(
+                        "`{}` cannot be applied to {} and {}",
+                        operator,
+                        self.inference.resolve(&lhs.r#type),
+                        self.inference.resolve(&rhs.r#type)
+                    ),
+                );
+                return None;
+            }
+            if matches!(
+                operator,
+                ConditionalOperator::Equality | ConditionalOperator::AntiEquality
+            ) {
+                let diagnostic = self.diagnostic(self.span, operator.to_string());
+                self.comparisons.push((lhs.r#type.clone(), diagnostic));
+            }
+
+            result = Some(TypedExpr {
+                kind: TypedExprKind::Conditional(Box::new(lhs), operator, Box::new(rhs)),
+                r#type: ValidType::Boolean,
+            });
+        }
+
+        result
+    }
+
+    fn index(&mut self, expr: &IndexExpr, expected: Option<&ValidType>) -> Option<TypedExpr> {
+        let array_type = expected.map(|x| ValidType::Array(Box::new(x.clone())));
+        let array = self.term(&expr.term, array_type.as_ref())?;
+        let index = self.expr(&expr.index, Some(&ValidType::Number))?;
+
+        // Not reported if unknown, the array already is
+        let element = self.inference.fresh(None);
+        if !self
+            .inference
+            .unify(&array.r#type, &ValidType::Array(Box::new(element.clone())))
+        {
+            self.error(
+                self.span,
+                format!(
+                    "Cannot index into a value of type {}",
+                    self.inference.resolve(&array.r#type)
+                ),
+            );
+            return None;
+        }
+        let index = self.expect(index, &ValidType::Number)?;
+
+        Some(TypedExpr {
+            kind: TypedExprKind::Index(Box::new(array), Box::new(index)),
+            r#type: element,
+        })
+    }
+
+    fn expect(&mut self, expr: TypedExpr, expected: &ValidType) -> Option<TypedExpr> {
+        if self.inference.unify(&expr.r#type, expected) {
+            Some(expr)
+        } else {
+            self.error(
+                self.span,
+                format!(
+                    "Mismatched types, expected {}, got {}",
+                    self.inference.resolve(expected),
+                    self.inference.resolve(&expr.r#type)
+                ),
+            );
+            None
+        }
+    }
+
+    fn lookup(&mut self, ident: &Ident) -> Option<Binding> {
+        let binding = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|x| x.get(&ident.0))
+            .cloned();
+        if let Some(binding) = binding {
+            self.reference(
+                ident,
+                ReferenceKind::Variable(binding.r#type.clone()),
+                Some(binding.declared.clone()),
+                binding.function,
+            );
+            return Some(binding);
+        }
+
+        let module = &self.modules[&self.module];
+        let imported = module
+            .imports
+            .iter()
+            .filter_map(|x| self.modules.get(x)?.globals.get(&ident.0))
+            .map(|x| Binding {
+                variable: VariableRef::Global(x.symbol.clone()),
+                r#type: x.r#type.clone(),
+                declared: x.declared.clone(),
+                function: None,
+            })
+            .collect::<Vec<_>>();
+
+        match imported.len() {
+            1 => {
+                let binding = imported.into_iter().next()?;
+                self.reference(
+                    ident,
+                    ReferenceKind::Variable(binding.r#type.clone()),
+                    Some(binding.declared.clone()),
+                    None,
+                );
+                Some(binding)
+            }
+            0 if module.functions.contains_key(&ident.0) => {
+                self.error(
+                    ident.1,
+                    format!(
+                        "`{}` is a function, use `call {}` to call it",
+                        ident.0, ident.0
+                    ),
+                );
+                None
+            }
+            0 => {
+                self.error(ident.1, format!("Use of undefined variable `{}`", ident.0));
+                None
+            }
+            _ => {
+                self.error(
+                    ident.1,
+                    format!("`{}` is defined by more than one import", ident.0),
+                );
+                None
+            }
+        }
+    }
+
+    fn resolve_function(&mut self, ident: &Ident) -> Option<(Callee, Signature)> {
+        let module = &self.modules[&self.module];
+        // Functions of the module shadow imported ones
+        let found = match module.functions.get(&ident.0) {
+            Some(function) => vec![function],
+            None => module
+                .imports
+                .iter()
+                .filter_map(|x| self.modules.get(x)?.functions.get(&ident.0))
+                .filter(|x| x.public)
+                .collect(),
+        };
+        let found = found
+            .into_iter()
+            .map(|x| (x.callee(), x.signature.clone(), x.declared.clone()))
+            .collect::<Vec<_>>();
+
+        match found.as_slice() {
+            [(callee, signature, declared)] => {
+                let kind = ReferenceKind::Function(signature.clone());
+                self.reference(ident, kind, Some(declared.clone()), None);
+                Some((callee.clone(), signature.clone()))
+            }
+            [] => match std_function(&ident.0) {
+                Some(function)
+                    if function.module == PRELUDE
+                        || module.std_imports.iter().any(|x| x == function.module) =>
+                {
+                    let kind = ReferenceKind::Function(function.signature());
+                    self.reference(ident, kind, None, None);
+                    Some((Callee::Std(ident.0.clone()), function.signature()))
+                }
+                Some(function) => {
+                    self.error(
+                        ident.1,
+                        format!(
+                            "`{}` is part of `std/{}`, import it with `weneed \"std/{}\"`",
+                            ident.0, function.module, function.module
+                        ),
+                    );
+                    None
+                }
+                None => {
+                    self.error(ident.1, format!("Use of undefined function `{}`", ident.0));
+                    None
+                }
+            },
+            _ => {
+                self.error(
+                    ident.1,
+                    format!("`{}` is defined by more than one import", ident.0),
+                );
+                None
+            }
+        }
+    }
+
+    fn declare_local(&mut self, ident: &Ident, r#type: ValidType) -> LocalId {
+        let id = LocalId(self.locals.len());
+        self.locals.push(Local {
+            name: ident.0.clone(),
+            r#type: r#type.clone(),
+            span: ident.1,
+        });
+        self.bind(ident, VariableRef::Local(id), r#type);
+        id
+    }
+
+    fn is_top_level(&self) -> bool {
+        self.function.is_none() && self.scopes.len() == 1
+    }
+
+    /// Variables at the top level of a module are globals, so functions can use them
+    fn declare_variable(&mut self, ident: &Ident, r#type: ValidType, public: bool) -> VariableRef {
+        if !self.is_top_level() {
+            return VariableRef::Local(self.declare_local(ident, r#type));
+        }
+
+        let name = &ident.0;
+        let mut symbol = global_symbol(&self.module, name);
+        if !public {
+            // Shadowed variables need their own symbol
+            let mut n = 0;
+            while self.symbols.contains(&symbol) {
+                n += 1;
+                symbol = format!("{}.{}", global_symbol(&self.module, name), n);
+            }
+        }
+        self.symbols.insert(symbol.clone());
+
+        self.globals.push(Global {
+            symbol: symbol.clone(),
+            name: name.to_string(),
+            r#type: r#type.clone(),
+            public,
+        });
+
+        let variable = VariableRef::Global(symbol);
+        self.bind(ident, variable.clone(), r#type);
+        variable
+    }
+
+    fn bind(&mut self, ident: &Ident, variable: VariableRef, r#type: ValidType) {
+        let function = match variable {
+            VariableRef::Local(_) => self.function.as_ref().map(|x| x.span),
+            VariableRef::Global(_) => None,
+        };
+        let binding = Binding {
+            variable,
+            r#type,
+            declared: (self.module.clone(), ident.1),
+            function,
+        };
+        let kind = ReferenceKind::Variable(binding.r#type.clone());
+        self.reference(ident, kind, Some(binding.declared.clone()), function);
+
+        self.scopes
+            .last_mut()
+            .unwrap_or_else(|| bug!("NO_SCOPE({})", ident.0))
+            .insert(ident.0.clone(), binding); // allows shadowing
+    }
+
+    fn reference(
+        &mut self,
+        ident: &Ident,
+        kind: ReferenceKind,
+        definition: Option<(String, Span)>,
+        function: Option<Span>,
+    ) {
+        self.references.push(Reference {
+            module: self.module.clone(),
+            ident: ident.clone(),
+            kind,
+            definition,
+            function,
+        });
+    }
+
+    fn signature(&mut self, function: &Function) -> Option<Signature> {
+        let ident = &function.declaration.ident;
+        let params = function
+            .args
+            .iter()
+            .map(|x| match &x.r#type {
+                Some(r#type) => self.r#type(r#type, x.ident.1),
+                None => Some(self.fresh(
+                    x.ident.1,
+                    format!("Type annotation required for argument `{}`", x.ident.0),
+                )),
+            })
+            .collect::<Vec<_>>();
+        let ret = match &function.declaration.r#type {
+            Some(x) => self.r#type(x, ident.1).map(Some),
+            None if has_return(&function.body) => Some(Some(self.fresh(
+                ident.1,
+                format!(
+                    "Type annotation required for the return type of `{}`",
+                    ident.0
+                ),
+            ))),
+            None => Some(None),
+        };
+
+        Some(Signature {
+            params: params.into_iter().collect::<Option<Vec<_>>>()?,
+            ret: ret?,
+        })
+    }
+
+    /// C functions can't have inferred types or take arrays
+    fn extern_signature(&mut self, function: &Extern) -> Option<Signature> {
+        let ident = &function.declaration.ident;
+        if function
+            .modifiers
+            .iter()
+            .any(|x| matches!(x, FunctionMod::Debug))
+        {
+            self.error(ident.1, "`debug` cannot be used on outsourced functions");
+        }
+
+        let params = function
+            .args
+            .iter()
+            .map(|x| match &x.r#type {
+                Some(r#type) => self.c_type(r#type, x.ident.1),
+                None => {
+                    self.error(
+                        x.ident.1,
+                        format!(
+                            "Type annotation required for argument `{}` of an outsourced function",
+                            x.ident.0
+                        ),
+                    );
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        let ret = match &function.declaration.r#type {
+            Some(x) => Some(self.c_type(x, ident.1)?),
+            None => None,
+        };
+
+        Some(Signature {
+            params: params.into_iter().collect::<Option<Vec<_>>>()?,
+            ret,
+        })
+    }
+
+    fn c_type(&mut self, r#type: &Type, span: Span) -> Option<ValidType> {
+        match self.r#type(r#type, span)? {
+            ValidType::Array(_) => {
+                self.error(
+                    span,
+                    "Arrays cannot be passed to or returned from C functions",
+                );
+                None
+            }
+            x => Some(x),
+        }
+    }
+
+    /// A type that is inferred from how it is used, reported at `span` if it never is
+    fn fresh<S: Into<String>>(&mut self, span: Span, message: S) -> ValidType {
+        let origin = self.diagnostic(span, message);
+        self.inference.fresh(Some(origin))
+    }
+
+    /// Reports types that could not be inferred and replaces every inferred type in the
+    /// analyzed modules
+    pub fn finish(&mut self, modules: &mut [TypedModule]) {
+        self.diagnostics.extend(self.inference.unresolved());
+
+        for (r#type, mut diagnostic) in mem::take(&mut self.comparisons) {
+            let r#type = self.inference.resolve(&r#type);
+            if let ValidType::Array(..) = r#type {
+                diagnostic.message = format!(
+                    "`{}` cannot be applied to {} and {}",
+                    diagnostic.message, r#type, r#type
+                );
+                self.diagnostics.push(diagnostic);
+            }
+        }
+        for (r#type, mut diagnostic) in mem::take(&mut self.operands) {
+            let r#type = self.inference.resolve(&r#type);
+            // Unknown types are already reported
+            if let ValidType::Boolean | ValidType::Array(..) = r#type {
+                diagnostic.message = format!(
+                    "`{}` cannot be applied to {} and {}",
+                    diagnostic.message, r#type, r#type
+                );
+                self.diagnostics.push(diagnostic);
+            }
+        }
+        for (r#type, mut diagnostic) in mem::take(&mut self.interpolations) {
+            let r#type = self.inference.resolve(&r#type);
+            if let ValidType::Array(..) = r#type {
+                diagnostic.message = format!("{} cannot be put in a string", r#type);
+                self.diagnostics.push(diagnostic);
+            }
+        }
+
+        for module in modules {
+            self.inference.resolve_module(module);
+        }
+        for reference in &mut self.references {
+            match &mut reference.kind {
+                ReferenceKind::Variable(r#type) => *r#type = self.inference.resolve(r#type),
+                ReferenceKind::Function(signature) => self.inference.resolve_signature(signature),
+            }
+        }
+    }
+
+    fn r#type(&mut self, r#type: &Type, span: Span) -> Option<ValidType> {
+        match ValidType::try_from(r#type) {
+            Ok(x) => Some(x),
+            Err(message) => {
+                self.error(span, message);
+                None
+            }
+        }
+    }
+
+    fn diagnostic<S: Into<String>>(&self, span: Span, message: S) -> Diagnostic {
+        Diagnostic {
+            module: self.module.clone(),
+            span,
+            message: message.into(),
+        }
+    }
+
+    fn error<S: Into<String>>(&mut self, span: Span, message: S) {
+        let diagnostic = self.diagnostic(span, message);
+        self.diagnostics.push(diagnostic);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{
+        parser::parse_source,
+        project::SourceModule,
+        semantic::{analyze, uncaught_tests, Program, ValidType, MAIN_MODULE},
+    };
+
+    fn check(source: &str) -> Result<Program, Vec<String>> {
+        let module = SourceModule {
+            name: MAIN_MODULE.to_string(),
+            path: PathBuf::from("main.rl"),
+            tree: parse_source(source, false).unwrap(),
+            source: source.to_string(),
+        };
+        analyze(&[module]).map_err(|x| x.into_iter().map(|x| x.message).collect())
+    }
+
+    fn errors(source: &str) -> Vec<String> {
+        check(source).err().unwrap_or_default()
+    }
+
+    /// Parameters and return type of the first function
+    fn signature(source: &str) -> (Vec<ValidType>, Option<ValidType>) {
+        let program = check(source).unwrap();
+        let signature = &program.modules[0].functions[0].signature;
+        (signature.params.clone(), signature.ret.clone())
+    }
+
+    #[test]
+    fn infers_from_the_body() {
+        let source = "callmeonmycellphone f(a, b,) { spez a ⋇ b }\n";
+        let number = Some(ValidType::Number);
+        assert_eq!(signature(source), (vec![ValidType::Number; 2], number));
+    }
+
+    #[test]
+    fn infers_strings_joined_with_a_parameter() {
+        let source = "callmeonmycellphone g(s,) { spez s ⨋ \"!\" }\ncall g(\"hi\",)\n";
+        let string = Some(ValidType::String);
+        assert_eq!(signature(source), (vec![ValidType::String], string));
+    }
+
+    #[test]
+    fn infers_from_call_sites() {
+        let source =
+            "callmeonmycellphone g(a, b,) { spez a ⨋ b }\nmeth x ∑ call g(\"a\", \"b\",)\n";
+        let string = Some(ValidType::String);
+        assert_eq!(signature(source), (vec![ValidType::String; 2], string));
+
+        let source = "callmeonmycellphone g(a, b,) { spez a < b }\ncall g(1, 2,)\n";
+        let boolean = Some(ValidType::Boolean);
+        assert_eq!(signature(source), (vec![ValidType::Number; 2], boolean));
+    }
+
+    #[test]
+    fn infers_variables_from_later_uses() {
+        let source = "callmeonmycellphone f() {\n    meth a ∑ []\n    meth b ∑ wat\n    a ∑ [b,]\n    b ∑ \"x\"\n}\n";
+        let program = check(source).unwrap();
+        let locals = &program.modules[0].functions[0].locals;
+        let string = ValidType::String;
+        assert_eq!(locals[0].r#type, ValidType::Array(Box::new(string.clone())));
+        assert_eq!(locals[1].r#type, string);
+    }
+
+    #[test]
+    fn reports_types_that_stay_unknown() {
+        assert_eq!(
+            errors("callmeonmycellphone f(a, b,) { spez a ⨋ b }\n"),
+            ["Type annotation required for argument `a`"]
+        );
+        assert_eq!(
+            errors("meth w ∑ wat\n"),
+            ["Type annotation required for `w`"]
+        );
+    }
+
+    #[test]
+    fn reports_mismatched_operands() {
+        assert_eq!(
+            errors("meth x ∑ 1\nmeth y ∑ x ⨋ \"a\"\n"),
+            ["`⨋` cannot be applied to Number and String"]
+        );
+        assert_eq!(
+            errors("meth x ∑ Yup ⨋ Nope\n"),
+            ["`⨋` cannot be applied to Boolean and Boolean"]
+        );
+        assert_eq!(
+            errors("meth x ∑ \"a\" ⋇ 2\n"),
+            ["`⋇` cannot be applied to String and Number"]
+        );
+    }
+
+    #[test]
+    fn reports_operands_once_they_are_inferred() {
+        let source = "callmeonmycellphone h(a,) { spez a ⨋ a }\nmeth x ∑ call h(Yup,)\n";
+        assert_eq!(
+            errors(source),
+            ["`⨋` cannot be applied to Boolean and Boolean"]
+        );
+
+        let source = "callmeonmycellphone h(a,) { spez a ⅀ a }\nmeth x ∑ call h([1,],)\n";
+        assert_eq!(
+            errors(source),
+            ["`⅀` cannot be applied to Number >> Array and Number >> Array"]
+        );
+    }
+
+    #[test]
+    fn reports_mismatched_types() {
+        assert_eq!(
+            errors("meth x ∑ 1\nx ∑ \"a\"\n"),
+            ["Mismatched types, expected Number, got String"]
+        );
+        assert_eq!(
+            errors("callmeonmycellphone g(s,) { spez s ⨋ \"!\" }\ncall g(1,)\n"),
+            ["Mismatched types, expected String, got Number"]
+        );
+    }
+
+    #[test]
+    fn accepts_returns_on_every_path() {
+        let sources = [
+            "callmeonmycellphone f(x,) {\n    is x {\n        spez 1\n    } isn't {\n        shoot \"no\"\n    }\n}\n",
+            "callmeonmycellphone f() {\n    repeatdatshid {\n        is Yup {\n            spez 1\n        }\n    }\n}\n",
+            "callmeonmycellphone f() {\n    test {\n        spez 1\n    } wall {\n        spez 2\n    }\n}\n",
+        ];
+        for source in sources {
+            assert!(check(source).is_ok(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn reports_missing_returns() {
+        let sources = [
+            "callmeonmycellphone f(x,) {\n    is x {\n        spez 1\n    }\n}\n",
+            "callmeonmycellphone f(x damn Boolean,) {\n    is x {\n        spez 1\n    } isn't {}\n}\n",
+            "callmeonmycellphone f() {\n    repeatdatshid {\n        is Yup {\n            sthu\n        }\n        spez 1\n    }\n}\n",
+            "callmeonmycellphone f() {\n    test {\n        spez 1\n    } wall {}\n}\n",
+            "callmeonmycellphone f damn Number() {}\n",
+        ];
+        for source in sources {
+            assert_eq!(
+                errors(source),
+                ["Missing `spez`, `f` can end without returning a value"],
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn reports_recursive_types() {
+        let source = "callmeonmycellphone f(x,) {\n    x ∑ [x,]\n}\n";
+        assert_eq!(
+            errors(source),
+            [
+                "Mismatched types, expected _, got _ >> Array",
+                "Type annotation required for argument `x`"
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_tests_for_webassembly() {
+        let source = r#"callmeonmycellphone f() {
+    repeatdatshid {
+        test {
+            shoot "a"
+        } wall bullet {
+            test {
+                shoot bullet
+            } wall {
+            }
+        }
+    }
+}
+
+test {
+} wall {
+}
+"#;
+        let lines = uncaught_tests(&check(source).unwrap())
+            .iter()
+            .map(|x| x.span.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [14, 3, 6]);
+    }
+}
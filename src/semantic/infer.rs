@@ -0,0 +1,234 @@
+use std::collections::HashSet;
+
+use super::{
+    Diagnostic, Signature, TypedCall, TypedExpr, TypedExprKind, TypedModule, TypedNode, TypedTree,
+    ValidType,
+};
+
+struct TypeVariable {
+    r#type: Option<ValidType>,
+    /// Reported if the type is never inferred, None for variables that only exist because of
+    /// another error
+    origin: Option<Diagnostic>,
+    /// Created for a `wat` or `[]`, the variable it is stored in is a better place to report
+    value: bool,
+}
+
+/// Type variables for values without a type annotation, their types are decided by unifying
+/// them with the types of the values they are used with.
+#[derive(Default)]
+pub struct Inference {
+    variables: Vec<TypeVariable>,
+}
+
+impl Inference {
+    pub fn fresh(&mut self, origin: Option<Diagnostic>) -> ValidType {
+        self.variables.push(TypeVariable {
+            r#type: None,
+            origin,
+            value: false,
+        });
+        ValidType::Infer(self.variables.len() - 1)
+    }
+
+    /// The type of a value that fits any type, see `describe`
+    pub fn fresh_value(&mut self, origin: Diagnostic) -> ValidType {
+        self.variables.push(TypeVariable {
+            r#type: None,
+            origin: Some(origin),
+            value: true,
+        });
+        ValidType::Infer(self.variables.len() - 1)
+    }
+
+    /// Follows type variables until a concrete type or an unknown variable is found
+    pub fn shallow(&self, r#type: &ValidType) -> ValidType {
+        let mut r#type = r#type.clone();
+        while let ValidType::Infer(x) = r#type {
+            match &self.variables[x].r#type {
+                Some(x) => r#type = x.clone(),
+                None => break,
+            }
+        }
+        r#type
+    }
+
+    /// Replaces every known type variable with its type
+    pub fn resolve(&self, r#type: &ValidType) -> ValidType {
+        match self.shallow(r#type) {
+            ValidType::Array(x) => ValidType::Array(Box::new(self.resolve(&x))),
+            x => x,
+        }
+    }
+
+    /// Makes both types the same, false if they can't be
+    pub fn unify(&mut self, a: &ValidType, b: &ValidType) -> bool {
+        match (self.shallow(a), self.shallow(b)) {
+            (ValidType::Infer(x), ValidType::Infer(y)) if x == y => true,
+            (ValidType::Infer(x), r#type) | (r#type, ValidType::Infer(x)) => {
+                if self.occurs(x, &r#type) {
+                    return false;
+                }
+                self.variables[x].r#type = Some(r#type);
+                true
+            }
+            (ValidType::Array(x), ValidType::Array(y)) => self.unify(&x, &y),
+            (x, y) => x == y,
+        }
+    }
+
+    fn occurs(&self, variable: usize, r#type: &ValidType) -> bool {
+        match self.shallow(r#type) {
+            ValidType::Infer(x) => x == variable,
+            ValidType::Array(x) => self.occurs(variable, &x),
+            _ => false,
+        }
+    }
+
+    /// Points the unknown parts of the type of a value at the variable it is stored in
+    pub fn describe(&mut self, r#type: &ValidType, origin: &Diagnostic) {
+        match self.shallow(r#type) {
+            ValidType::Infer(x) if self.variables[x].value => {
+                self.variables[x].origin = Some(origin.clone());
+                self.variables[x].value = false;
+            }
+            ValidType::Array(x) => self.describe(&x, origin),
+            _ => (),
+        }
+    }
+
+    /// One diagnostic for every type that could not be inferred
+    pub fn unresolved(&self) -> Vec<Diagnostic> {
+        let mut reported = HashSet::new();
+        self.variables
+            .iter()
+            .enumerate()
+            .filter_map(|(i, variable)| {
+                let root = match self.shallow(&ValidType::Infer(i)) {
+                    ValidType::Infer(x) => x,
+                    _ => return None,
+                };
+                let origin = variable.origin.as_ref()?;
+                reported.insert(root).then(|| origin.clone())
+            })
+            .collect()
+    }
+
+    pub fn resolve_module(&self, module: &mut TypedModule) {
+        for global in &mut module.globals {
+            global.r#type = self.resolve(&global.r#type);
+        }
+        for local in &mut module.locals {
+            local.r#type = self.resolve(&local.r#type);
+        }
+        for function in &mut module.functions {
+            self.resolve_signature(&mut function.signature);
+            for local in &mut function.locals {
+                local.r#type = self.resolve(&local.r#type);
+            }
+            self.resolve_tree(&mut function.body);
+        }
+        self.resolve_tree(&mut module.body);
+    }
+
+    fn resolve_tree(&self, tree: &mut TypedTree) {
+        for node in tree {
+            match &mut node.node {
+                TypedNode::Variable(_, value)
+                | TypedNode::Assignment(_, value)
+                | TypedNode::Return(value)
+                | TypedNode::Throw(value) => self.resolve_expr(value),
+                TypedNode::Loop(body) => self.resolve_tree(body),
+                TypedNode::If(cases, r#else) => {
+                    for (condition, body) in cases {
+                        self.resolve_expr(condition);
+                        self.resolve_tree(body);
+                    }
+                    if let Some(body) = r#else {
+                        self.resolve_tree(body);
+                    }
+                }
+                TypedNode::Call(call) => self.resolve_call(call),
+                TypedNode::TryCatch(r#try, _, catch) => {
+                    self.resolve_tree(r#try);
+                    self.resolve_tree(catch);
+                }
+                TypedNode::Break | TypedNode::Import(_) => (),
+            }
+        }
+    }
+
+    fn resolve_expr(&self, expr: &mut TypedExpr) {
+        expr.r#type = self.resolve(&expr.r#type);
+        match &mut expr.kind {
+            TypedExprKind::Array(items) | TypedExprKind::Interpolation(items) => {
+                for item in items {
+                    self.resolve_expr(item);
+                }
+            }
+            TypedExprKind::Call(call) => self.resolve_call(call),
+            TypedExprKind::Binary(lhs, _, rhs)
+            | TypedExprKind::Conditional(lhs, _, rhs)
+            | TypedExprKind::Index(lhs, rhs) => {
+                self.resolve_expr(lhs);
+                self.resolve_expr(rhs);
+            }
+            TypedExprKind::Number(_)
+            | TypedExprKind::String(_)
+            | TypedExprKind::Boolean(_)
+            | TypedExprKind::Null
+            | TypedExprKind::Variable(_) => (),
+        }
+    }
+
+    fn resolve_call(&self, call: &mut TypedCall) {
+        self.resolve_signature(&mut call.signature);
+        for arg in &mut call.args {
+            self.resolve_expr(arg);
+        }
+    }
+
+    pub fn resolve_signature(&self, signature: &mut Signature) {
+        for param in &mut signature.params {
+            *param = self.resolve(param);
+        }
+        signature.ret = signature.ret.as_ref().map(|x| self.resolve(x));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Inference;
+    use crate::semantic::ValidType;
+
+    fn array(r#type: ValidType) -> ValidType {
+        ValidType::Array(Box::new(r#type))
+    }
+
+    #[test]
+    fn unifies_variables_with_types() {
+        let mut inference = Inference::default();
+        let (a, b) = (inference.fresh(None), inference.fresh(None));
+        assert!(inference.unify(&a, &b));
+        assert!(inference.unify(&array(b.clone()), &array(ValidType::String)));
+        assert_eq!(inference.resolve(&a), ValidType::String);
+        assert_eq!(inference.resolve(&array(a)), array(ValidType::String));
+    }
+
+    #[test]
+    fn rejects_different_types() {
+        let mut inference = Inference::default();
+        let a = inference.fresh(None);
+        assert!(inference.unify(&a, &ValidType::Number));
+        assert!(!inference.unify(&a, &ValidType::String));
+        assert!(!inference.unify(&array(a), &ValidType::Number));
+    }
+
+    #[test]
+    fn rejects_recursive_types() {
+        let mut inference = Inference::default();
+        let a = inference.fresh(None);
+        assert!(!inference.unify(&a, &array(a.clone())));
+        assert_eq!(inference.shallow(&a), a);
+    }
+}
@@ -0,0 +1,297 @@
+use std::{fmt, path::PathBuf};
+
+use crate::{
+    parser::{ConditionalOperator, FunctionMod, Ident, MathOperator, Number, Span, Spanned, Type},
+    project::SourceModule,
+};
+
+use self::analyze::Analyzer;
+
+pub mod analyze;
+pub mod infer;
+pub mod stdlib;
+
+/// Name of the module built from `src/main.rl`
+pub const MAIN_MODULE: &str = "main";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidType {
+    Number,
+    Boolean,
+    String,
+    Array(Box<ValidType>), // Array is generic
+    // Null, // it is not a type, but a value that is any type
+    /// A type that is not known yet, only exists during analysis
+    Infer(usize),
+}
+
+impl TryFrom<Type> for ValidType {
+    type Error = String;
+
+    fn try_from(value: Type) -> Result<Self, Self::Error> {
+        (&value).try_into()
+    }
+}
+
+impl<'a> TryFrom<&'a Type> for ValidType {
+    type Error = String;
+
+    fn try_from(value: &'a Type) -> Result<Self, Self::Error> {
+        match value.root_type.0.as_str() {
+            "Number" => Ok(Self::Number),
+            "Boolean" => Ok(Self::Boolean),
+            "String" => Ok(Self::String),
+            "Array" => {
+                let generic1 = value
+                    .generics
+                    .first()
+                    .ok_or("Array requires an element type, ex. `Number >> Array`")?;
+                let generic1 = ValidType::try_from(generic1)?;
+                Ok(Self::Array(Box::from(generic1)))
+            }
+            "Null" => Err("Null is not a valid type, did you mean to use `wat`?".to_string()),
+            _ => Err(format!("Invalid type, got {}", value.root_type.0)),
+        }
+    }
+}
+
+impl fmt::Display for ValidType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidType::Number => f.write_str("Number"),
+            ValidType::Boolean => f.write_str("Boolean"),
+            ValidType::String => f.write_str("String"),
+            ValidType::Array(x) => write!(f, "{} >> Array", x),
+            ValidType::Infer(_) => f.write_str("_"),
+        }
+    }
+}
+
+/// A problem found in a module, points to the code that caused it
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub module: String,
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub params: Vec<ValidType>,
+    /// None if the function does not return a value
+    pub ret: Option<ValidType>,
+}
+
+/// What a name refers to
+#[derive(Debug, Clone)]
+pub enum ReferenceKind {
+    Variable(ValidType),
+    Function(Signature),
+}
+
+/// A name in the source and what it refers to, declarations included. The language server
+/// answers hovers, completions and go to definition with them
+#[derive(Debug, Clone)]
+pub struct Reference {
+    /// Module the name is written in
+    pub module: String,
+    pub ident: Ident,
+    pub kind: ReferenceKind,
+    /// Module and identifier of the declaration, None for standard library functions
+    pub definition: Option<(String, Span)>,
+    /// Function a local variable is declared in
+    pub function: Option<Span>,
+}
+
+/// Index into the `locals` of the enclosing function
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LocalId(pub usize);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariableRef {
+    Local(LocalId),
+    /// Symbol of a module level variable
+    Global(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Local {
+    pub name: String,
+    pub r#type: ValidType,
+    /// Identifier of the declaration
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Global {
+    pub symbol: String,
+    pub name: String,
+    pub r#type: ValidType,
+    pub public: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum Callee {
+    /// Symbol of a RedditLang function
+    Function(String),
+    /// A standard library function
+    Std(String),
+    /// Symbol of a C function
+    Extern(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct TypedCall {
+    pub callee: Callee,
+    pub args: Vec<TypedExpr>,
+    pub signature: Signature,
+}
+
+#[derive(Debug, Clone)]
+pub struct TypedExpr {
+    pub kind: TypedExprKind,
+    pub r#type: ValidType,
+}
+
+#[derive(Debug, Clone)]
+pub enum TypedExprKind {
+    Number(Number),
+    String(String),
+    Boolean(bool),
+    Null,
+    Array(Vec<TypedExpr>),
+    Variable(VariableRef),
+    Call(TypedCall),
+    Binary(Box<TypedExpr>, MathOperator, Box<TypedExpr>),
+    Conditional(Box<TypedExpr>, ConditionalOperator, Box<TypedExpr>),
+    /// Array, 1-based index
+    Index(Box<TypedExpr>, Box<TypedExpr>),
+    /// Strings, Numbers and Booleans joined into a string
+    Interpolation(Vec<TypedExpr>),
+}
+
+#[derive(Debug, Clone)]
+pub enum TypedNode {
+    /// Declaration of a variable, always the first store to it
+    Variable(VariableRef, TypedExpr),
+    Assignment(VariableRef, TypedExpr),
+    Loop(TypedTree),
+    Break,
+    If(Vec<(TypedExpr, TypedTree)>, Option<TypedTree>),
+    Call(TypedCall),
+    Return(TypedExpr),
+    Throw(TypedExpr),
+    TryCatch(TypedTree, Option<VariableRef>, TypedTree),
+    /// Runs the entry of the imported module, contains its symbol
+    Import(String),
+}
+
+pub type TypedTree = Vec<Spanned<TypedNode>>;
+
+#[derive(Debug, Clone)]
+pub struct TypedFunction {
+    pub name: String,
+    pub symbol: String,
+    pub modifiers: Vec<FunctionMod>,
+    pub signature: Signature,
+    pub params: Vec<LocalId>,
+    pub locals: Vec<Local>,
+    pub body: TypedTree,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct TypedModule {
+    pub name: String,
+    /// Source file of the module
+    pub path: PathBuf,
+    /// Symbol of the function that runs the top level statements
+    pub entry: String,
+    pub globals: Vec<Global>,
+    pub functions: Vec<TypedFunction>,
+    /// Locals of the entry function
+    pub locals: Vec<Local>,
+    pub body: TypedTree,
+}
+
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub modules: Vec<TypedModule>,
+}
+
+/// Symbol of the function that runs the top level statements of a module
+pub fn entry_symbol(module: &str) -> String {
+    if module == MAIN_MODULE {
+        MAIN_MODULE.to_string()
+    } else {
+        format!("{}.main", module)
+    }
+}
+
+/// Resolves every identifier and computes the type of every expression, returns every problem
+/// found if there are any.
+pub fn analyze(modules: &[SourceModule]) -> Result<Program, Vec<Diagnostic>> {
+    let (analyzer, modules) = run(modules);
+    if analyzer.diagnostics.is_empty() {
+        Ok(Program { modules })
+    } else {
+        Err(analyzer.diagnostics)
+    }
+}
+
+/// Every problem and every reference in the modules, analysis goes on after problems so this
+/// is as complete as it can be
+pub fn analyze_references(modules: &[SourceModule]) -> (Vec<Diagnostic>, Vec<Reference>) {
+    let (analyzer, _) = run(modules);
+    (analyzer.diagnostics, analyzer.references)
+}
+
+/// Every `test` of the program, WebAssembly has no `setjmp` so they can't catch bullets there
+pub fn uncaught_tests(program: &Program) -> Vec<Diagnostic> {
+    fn visit(module: &str, tree: &TypedTree, diagnostics: &mut Vec<Diagnostic>) {
+        for node in tree {
+            match &node.node {
+                TypedNode::Loop(body) => visit(module, body, diagnostics),
+                TypedNode::If(cases, r#else) => {
+                    for (_, body) in cases {
+                        visit(module, body, diagnostics);
+                    }
+                    if let Some(body) = r#else {
+                        visit(module, body, diagnostics);
+                    }
+                }
+                TypedNode::TryCatch(r#try, _, catch) => {
+                    diagnostics.push(Diagnostic {
+                        module: module.to_string(),
+                        span: node.span,
+                        message:
+                            "Bullets can't be caught in WebAssembly, every bullet stops the program"
+                                .to_string(),
+                    });
+                    visit(module, r#try, diagnostics);
+                    visit(module, catch, diagnostics);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut diagnostics = vec![];
+    for module in &program.modules {
+        visit(&module.name, &module.body, &mut diagnostics);
+        for function in &module.functions {
+            visit(&module.name, &function.body, &mut diagnostics);
+        }
+    }
+    diagnostics
+}
+
+fn run(modules: &[SourceModule]) -> (Analyzer, Vec<TypedModule>) {
+    let mut analyzer = Analyzer::new(modules);
+    let mut modules = modules
+        .iter()
+        .map(|module| analyzer.module(module))
+        .collect::<Vec<_>>();
+    analyzer.finish(&mut modules);
+    (analyzer, modules)
+}
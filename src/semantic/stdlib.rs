@@ -0,0 +1,98 @@
+use std::sync::LazyLock;
+
+use super::{Signature, ValidType};
+
+/// A standard library function, implemented by the runtime
+pub struct StdFunction {
+    pub name: &'static str,
+    /// Module that has to be imported to use the function, ex. `io` for `weneed "std/io"`
+    pub module: &'static str,
+    pub params: Vec<ValidType>,
+    /// None if the function does not return a value
+    pub ret: Option<ValidType>,
+}
+
+impl StdFunction {
+    pub fn signature(&self) -> Signature {
+        Signature {
+            params: self.params.clone(),
+            ret: self.ret.clone(),
+        }
+    }
+}
+
+/// Module that is available in every module without importing it
+pub const PRELUDE: &str = "io";
+
+pub static STD_FUNCTIONS: LazyLock<Vec<StdFunction>> = LazyLock::new(|| {
+    vec![
+        StdFunction {
+            name: "coitusinterruptus",
+            module: "io",
+            params: vec![ValidType::String],
+            ret: None,
+        },
+        StdFunction {
+            name: "pulloutnt",
+            module: "io",
+            params: vec![],
+            ret: Some(ValidType::String),
+        },
+        StdFunction {
+            name: "nums",
+            module: "conv",
+            params: vec![ValidType::Number],
+            ret: Some(ValidType::String),
+        },
+        StdFunction {
+            name: "unnums",
+            module: "conv",
+            params: vec![ValidType::String],
+            ret: Some(ValidType::Number),
+        },
+        StdFunction {
+            name: "length",
+            module: "strings",
+            params: vec![ValidType::String],
+            ret: Some(ValidType::Number),
+        },
+        StdFunction {
+            name: "substring",
+            module: "strings",
+            params: vec![ValidType::String, ValidType::Number, ValidType::Number],
+            ret: Some(ValidType::String),
+        },
+        StdFunction {
+            name: "split",
+            module: "strings",
+            params: vec![ValidType::String, ValidType::String],
+            ret: Some(ValidType::Array(Box::new(ValidType::String))),
+        },
+        StdFunction {
+            name: "contains",
+            module: "strings",
+            params: vec![ValidType::String, ValidType::String],
+            ret: Some(ValidType::Boolean),
+        },
+        StdFunction {
+            name: "exit",
+            module: "sys",
+            params: vec![ValidType::Number],
+            ret: None,
+        },
+        StdFunction {
+            name: "zzz",
+            module: "time",
+            params: vec![ValidType::Number],
+            ret: None,
+        },
+    ]
+});
+
+pub fn std_function(name: &str) -> Option<&'static StdFunction> {
+    STD_FUNCTIONS.iter().find(|x| x.name == name)
+}
+
+pub fn is_std_module(module: &str) -> bool {
+    STD_FUNCTIONS.iter().any(|x| x.module == module)
+}
@@ -0,0 +1,38 @@
+//! Runs programs with `walter serve`
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Writes a project with `source` as its `main.rl`
+pub fn project(name: &str, source: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("walter-test-{}", name));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(
+        dir.join("walter.yml"),
+        format!("name: {}\nversion: 0.0.1\n", name),
+    )
+    .unwrap();
+    fs::write(dir.join("src/main.rl"), source).unwrap();
+    dir
+}
+
+/// Output of the program and exit code of `walter serve` with `flags`, the logs of walter are
+/// left out
+pub fn serve(dir: &Path, flags: &[&str]) -> (String, Option<i32>) {
+    let output = Command::new(env!("CARGO_BIN_EXE_walter"))
+        .arg("serve")
+        .args(flags)
+        .env("NO_COLOR", "1")
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|x| !x.starts_with("info: ") && !x.starts_with("warn: "))
+        .map(|x| format!("{}\n", x))
+        .collect();
+    (stdout, output.status.code())
+}
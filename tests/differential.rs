@@ -0,0 +1,56 @@
+//! Runs the same programs with the interpreter and the JIT, they must print the same and exit
+//! with the same code
+#![cfg(feature = "llvm")]
+
+use std::fs;
+
+use common::{project, serve};
+
+mod common;
+
+/// The JIT runs both unoptimized and optimized, optimizations must not change the program
+fn differential(name: &str, source: &str) {
+    let dir = project(name, source);
+    let interpreted = serve(&dir, &["--interpret"]);
+    let jit = serve(&dir, &["--jit"]);
+    let optimized = serve(&dir, &["--jit", "--release"]);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        interpreted, jit,
+        "{} differs between --interpret and --jit",
+        name
+    );
+    assert_eq!(
+        interpreted, optimized,
+        "{} differs between --interpret and --jit --release",
+        name
+    );
+}
+
+#[test]
+fn indexing() {
+    differential("indexing", include_str!("programs/indexing.rl"));
+}
+
+#[test]
+fn strings() {
+    differential("strings", include_str!("programs/strings.rl"));
+}
+
+#[test]
+fn numbers() {
+    differential("numbers", include_str!("programs/numbers.rl"));
+}
+
+#[test]
+fn uncaught_bullet() {
+    differential(
+        "uncaught_bullet",
+        include_str!("programs/uncaught_bullet.rl"),
+    );
+}
+
+#[test]
+fn try_locals() {
+    differential("try_locals", include_str!("programs/try_locals.rl"));
+}
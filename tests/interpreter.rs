@@ -0,0 +1,93 @@
+//! Runs programs with the interpreter and compares what they print and their exit code, these
+//! run without LLVM
+
+use std::fs;
+
+use common::{project, serve};
+
+mod common;
+
+fn golden(name: &str, source: &str, stdout: &str, code: i32) {
+    let dir = project(name, source);
+    let interpreted = serve(&dir, &["--interpret"]);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(interpreted, (stdout.to_string(), Some(code)), "{}", name);
+}
+
+#[test]
+fn indexing() {
+    golden(
+        "indexing",
+        include_str!("programs/indexing.rl"),
+        "10
+30
+10
+30
+Index 0 is out of bounds of an array with 3 elements
+Index 4 is out of bounds of an array with 3 elements
+Index 0.5 is out of bounds of an array with 3 elements
+Index -1 is out of bounds of an array with 3 elements
+Index -nan is out of bounds of an array with 3 elements
+Index inf is out of bounds of an array with 3 elements
+Index 1 is out of bounds of an array with 0 elements
+",
+        1,
+    );
+}
+
+#[test]
+fn strings() {
+    golden(
+        "strings",
+        include_str!("programs/strings.rl"),
+        "12 éllo Yup
+a|b||c
+Yup Nope Yup Yup
+Yup 0
+Substring of 2 characters at 3 is out of bounds of a string with 3 characters
+Substring of inf characters at 0.5 is out of bounds of a string with 3 characters
+Cannot split with an empty separator
+",
+        0,
+    );
+}
+
+#[test]
+fn numbers() {
+    golden(
+        "numbers",
+        include_str!("programs/numbers.rl"),
+        "0.30000000000000004 0.3333333333333333 20 -0 1 6
+1e+20 inf 1.0000000000000001e-07
+9
+Cannot convert \"four\" to a Number
+Cannot convert \"0x10\" to a Number
+12 -1000 inf
+",
+        3,
+    );
+}
+
+#[test]
+fn uncaught_bullet() {
+    golden(
+        "uncaught_bullet",
+        include_str!("programs/uncaught_bullet.rl"),
+        "before\n",
+        1,
+    );
+}
+
+#[test]
+fn try_locals() {
+    golden(
+        "try_locals",
+        include_str!("programs/try_locals.rl"),
+        "200 200 ab
+200
+outer abc
+top 5
+",
+        0,
+    );
+}
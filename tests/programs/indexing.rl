@@ -0,0 +1,25 @@
+weneed "std/conv"
+
+callmeonmycellphone at(a damn Number >> Array, i damn Number,) {
+    test {
+        call coitusinterruptus("{a[i]}",)
+    } wall bullet {
+        call coitusinterruptus(bullet,)
+    }
+}
+
+meth a ∑ [10, 20, 30,]
+call at(a, 1,)
+call at(a, 3,)
+call at(a, 1.5,)
+call at(a, 3.9,)
+call at(a, 0,)
+call at(a, 4,)
+call at(a, 0.5,)
+call at(a, –1,)
+call at(a, 0 ⎲ 0,)
+call at(a, 1 ⎲ 0,)
+meth b damn Number >> Array ∑ wat
+call at(b, 1,)
+call coitusinterruptus("{a[5]}",)
+call coitusinterruptus("not printed",)
@@ -0,0 +1,18 @@
+weneed "std/conv"
+weneed "std/sys"
+
+call coitusinterruptus("{0.1 ⨋ 0.2} {1 ⎲ 3} {20} {–0} {7 ⨊ 3} {5 ⊕ 3}",)
+call coitusinterruptus("{100000 ⋇ 100000 ⋇ 100000 ⋇ 100000} {1 ⎲ 0} {0.0001 ⎲ 1000}",)
+call coitusinterruptus(call nums((call unnums("4.5",)) ⋇ 2,),)
+test {
+    call unnums("four",)
+} wall bullet {
+    call coitusinterruptus(bullet,)
+}
+test {
+    call unnums("0x10",)
+} wall bullet {
+    call coitusinterruptus(bullet,)
+}
+call coitusinterruptus("{call unnums(" 12",)} {call unnums("-1e3",)} {call unnums("inf",)}",)
+call exit(3,)
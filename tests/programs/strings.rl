@@ -0,0 +1,25 @@
+weneed "std/strings"
+weneed "std/conv"
+
+meth s ∑ "héllo" ⨋ ", " ⨋ "wörld"
+call coitusinterruptus("{call length(s,)} {call substring(s, 2, 4,)} {call contains(s, "wö",)}",)
+meth parts ∑ call split("a,b,,c", ",",)
+call coitusinterruptus("{parts[1]}|{parts[2]}|{parts[3]}|{parts[4]}",)
+call coitusinterruptus("{"a" < "b"} {"b" ⋜ "a"} {"abc" ⅀ "abc"} {"é" > "z"}",)
+meth w damn String ∑ wat
+call coitusinterruptus("{w < ""} {call length(w,)}",)
+test {
+    call substring("abc", 3, 2,)
+} wall bullet {
+    call coitusinterruptus(bullet,)
+}
+test {
+    call substring("abc", 0.5, 1 ⎲ 0,)
+} wall bullet {
+    call coitusinterruptus(bullet,)
+}
+test {
+    call split("abc", "",)
+} wall bullet {
+    call coitusinterruptus(bullet,)
+}
@@ -0,0 +1,44 @@
+callmeonmycellphone count damn Number() {
+    meth n ∑ 1
+    meth words ∑ "a"
+    test {
+        n ∑ n ⨋ 1
+        words ∑ words ⨋ "b"
+        repeatdatshid {
+            n ∑ n ⋇ 10
+            is n > 100 {
+                shoot "{n}"
+            }
+        }
+    } wall bullet {
+        call coitusinterruptus("{bullet} {n} {words}",)
+    }
+    spez n
+}
+
+callmeonmycellphone nested() {
+    meth steps ∑ ""
+    test {
+        test {
+            steps ∑ steps ⨋ "a"
+            shoot "inner"
+        } wall {
+            steps ∑ steps ⨋ "b"
+        }
+        steps ∑ steps ⨋ "c"
+        shoot "outer"
+    } wall bullet {
+        call coitusinterruptus("{bullet} {steps}",)
+    }
+}
+
+call coitusinterruptus("{call count()}",)
+call nested()
+
+meth total ∑ 0
+test {
+    total ∑ total ⨋ 5
+    shoot "top"
+} wall bullet {
+    call coitusinterruptus("{bullet} {total}",)
+}
@@ -0,0 +1,7 @@
+callmeonmycellphone fail() {
+    shoot "down"
+}
+
+call coitusinterruptus("before",)
+call fail()
+call coitusinterruptus("after",)